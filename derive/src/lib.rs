@@ -0,0 +1,563 @@
+//! Derives for the `*WithToken` traits in `demo::with_token` and `demo::serialize`,
+//! and for `BrandedStruct` and `BrandedEnum` in `demo::branded_struct` and `demo::branded_enum`.
+//!
+//! Each derive implements its trait for every brand `'id` at which all of the
+//! fields implement it, so for `Entity<'content>` the impl only applies when
+//! `'id` is `'content`, without the brand having to be named.
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::Parse;
+use syn::visit::Visit;
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, GenericParam, Ident, Lifetime, Path, PathArguments, Type, parse_macro_input,
+    parse_quote,
+};
+
+#[proc_macro_derive(DebugWithToken)]
+pub fn derive_debug_with_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let arms = variants(&input).into_iter().map(|v| {
+        let (path, pattern, bindings) = (&v.path, v.pattern("self_"), v.bindings("self_"));
+        let name = v.ident.to_string();
+        let body = match v.fields {
+            Fields::Named(named) => {
+                let names = named.named.iter().map(|f| f.ident.as_ref().unwrap().to_string());
+                quote! {
+                    f.debug_struct(#name)
+                        #(.field(#names, &::demo::with_token::WithToken::new(#bindings, token)))*
+                        .finish()
+                }
+            }
+            Fields::Unnamed(_) => quote! {
+                f.debug_tuple(#name)
+                    #(.field(&::demo::with_token::WithToken::new(#bindings, token)))*
+                    .finish()
+            },
+            Fields::Unit => quote! { f.write_str(#name) },
+        };
+        quote! { #path #pattern => #body, }
+    });
+    implement(
+        &input,
+        quote!(::demo::with_token::DebugWithToken),
+        quote! {
+            fn fmt(
+                &self,
+                token: &::demo::GhostToken<'__id>,
+                f: &mut ::core::fmt::Formatter<'_>,
+            ) -> ::core::fmt::Result {
+                match self { #(#arms)* }
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(PartialEqWithToken)]
+pub fn derive_partial_eq_with_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = variants(&input);
+    let arms = variants.iter().map(|v| {
+        let (path, a, b) = (&v.path, v.pattern("self_"), v.pattern("other_"));
+        let (a_bindings, b_bindings) = (v.bindings("self_"), v.bindings("other_"));
+        quote! {
+            (#path #a, #path #b) => true #(&& ::demo::with_token::PartialEqWithToken::eq(
+                #a_bindings, #b_bindings, token,
+            ))*,
+        }
+    });
+    let mismatch = (variants.len() > 1).then(|| quote!(_ => false,));
+    implement(
+        &input,
+        quote!(::demo::with_token::PartialEqWithToken),
+        quote! {
+            fn eq(&self, other: &Self, token: &::demo::GhostToken<'__id>) -> bool {
+                match (self, other) { #(#arms)* #mismatch }
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(OrdWithToken)]
+pub fn derive_ord_with_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = variants(&input);
+    let arms = variants.iter().map(|v| {
+        let (path, a, b) = (&v.path, v.pattern("self_"), v.pattern("other_"));
+        let (a_bindings, b_bindings) = (v.bindings("self_"), v.bindings("other_"));
+        quote! {
+            (#path #a, #path #b) => ::core::cmp::Ordering::Equal
+                #(.then_with(|| ::demo::with_token::OrdWithToken::cmp(#a_bindings, #b_bindings, token)))*,
+        }
+    });
+    let mismatch = (variants.len() > 1).then(|| {
+        let indices = index_arms(&variants);
+        quote! {
+            _ => {
+                let index = |x: &Self| match x { #(#indices)* };
+                ::core::cmp::Ord::cmp(&index(self), &index(other))
+            }
+        }
+    });
+    implement(
+        &input,
+        quote!(::demo::with_token::OrdWithToken),
+        quote! {
+            fn cmp(
+                &self,
+                other: &Self,
+                token: &::demo::GhostToken<'__id>,
+            ) -> ::core::cmp::Ordering {
+                match (self, other) { #(#arms)* #mismatch }
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(HashWithToken)]
+pub fn derive_hash_with_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let variants = variants(&input);
+    let tagged = variants.len() > 1;
+    let arms = variants.iter().enumerate().map(|(i, v)| {
+        let (path, pattern, bindings) = (&v.path, v.pattern("self_"), v.bindings("self_"));
+        let tag = tagged.then(|| quote!(::core::hash::Hasher::write_usize(state, #i);));
+        quote! {
+            #path #pattern => {
+                #tag
+                #(::demo::with_token::HashWithToken::hash(#bindings, token, state);)*
+            }
+        }
+    });
+    implement(
+        &input,
+        quote!(::demo::with_token::HashWithToken),
+        quote! {
+            fn hash<__H: ::core::hash::Hasher>(
+                &self,
+                token: &::demo::GhostToken<'__id>,
+                state: &mut __H,
+            ) {
+                match self { #(#arms)* }
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(SerializeWithToken)]
+pub fn derive_serialize_with_token(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let serde = quote!(::demo::serialize::__serde);
+    let is_enum = matches!(input.data, Data::Enum(_));
+    let type_name = input.ident.to_string();
+    let arms = variants(&input).into_iter().enumerate().map(|(i, v)| {
+        let (path, pattern, bindings) = (&v.path, v.pattern("self_"), v.bindings("self_"));
+        let index = i as u32;
+        let name = v.ident.to_string();
+        let len = bindings.len();
+        let wrapped = bindings
+            .iter()
+            .map(|b| quote!(&::demo::with_token::WithToken::new(#b, token)))
+            .collect::<Vec<_>>();
+        // mirrors the calls `#[derive(Serialize)]` would make
+        let body = match (v.fields, is_enum) {
+            (Fields::Named(named), _) => {
+                let names = named.named.iter().map(|f| f.ident.as_ref().unwrap().to_string());
+                let (start, state) = if is_enum {
+                    (
+                        quote!(serialize_struct_variant(serializer, #type_name, #index, #name, #len)),
+                        quote!(#serde::ser::SerializeStructVariant),
+                    )
+                } else {
+                    (
+                        quote!(serialize_struct(serializer, #name, #len)),
+                        quote!(#serde::ser::SerializeStruct),
+                    )
+                };
+                quote! {{
+                    let mut state = #serde::Serializer::#start?;
+                    #(#state::serialize_field(&mut state, #names, #wrapped)?;)*
+                    #state::end(state)
+                }}
+            }
+            (Fields::Unnamed(_), false) if len == 1 => quote! {
+                #serde::Serializer::serialize_newtype_struct(serializer, #name, #(#wrapped)*)
+            },
+            (Fields::Unnamed(_), true) if len == 1 => quote! {
+                #serde::Serializer::serialize_newtype_variant(serializer, #type_name, #index, #name, #(#wrapped)*)
+            },
+            (Fields::Unnamed(_), _) => {
+                let (start, state) = if is_enum {
+                    (
+                        quote!(serialize_tuple_variant(serializer, #type_name, #index, #name, #len)),
+                        quote!(#serde::ser::SerializeTupleVariant),
+                    )
+                } else {
+                    (
+                        quote!(serialize_tuple_struct(serializer, #name, #len)),
+                        quote!(#serde::ser::SerializeTupleStruct),
+                    )
+                };
+                quote! {{
+                    let mut state = #serde::Serializer::#start?;
+                    #(#state::serialize_field(&mut state, #wrapped)?;)*
+                    #state::end(state)
+                }}
+            }
+            (Fields::Unit, false) => quote! {
+                #serde::Serializer::serialize_unit_struct(serializer, #name)
+            },
+            (Fields::Unit, true) => quote! {
+                #serde::Serializer::serialize_unit_variant(serializer, #type_name, #index, #name)
+            },
+        };
+        quote! { #path #pattern => #body, }
+    });
+    implement(
+        &input,
+        quote!(::demo::serialize::SerializeWithToken),
+        quote! {
+            fn serialize<__S: #serde::Serializer>(
+                &self,
+                token: &::demo::GhostToken<'__id>,
+                serializer: __S,
+            ) -> ::core::result::Result<__S::Ok, __S::Error> {
+                match self { #(#arms)* }
+            }
+        },
+    )
+}
+
+#[proc_macro_derive(BrandedStruct, attributes(branded))]
+pub fn derive_branded_struct(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    branded_struct(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn branded_struct(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(ident, "only structs can be opened field by field"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(ident, "the fields must be named, to name their brands after"));
+    };
+    let brand = brand(input)?;
+    let open: Path = branded_attr(input, "open", "the open type")?;
+
+    // the field's own cell takes the first brand, and the cells inside it
+    // share the second
+    let brands: Vec<_> = fields
+        .named
+        .iter()
+        .map(|field| {
+            let name = field.ident.as_ref().unwrap();
+            let mut count = BrandCount { brand, count: 0 };
+            count.visit_type(&field.ty);
+            let field_brand = (count.count > 0).then(|| name.to_string());
+            let content_brand = (count.count > 1).then(|| format!("{name}_content"));
+            (name, field_brand, content_brand)
+        })
+        .collect();
+    let open_brands = brands.iter().map(|(_, a, b)| usize::from(a.is_some()) + usize::from(b.is_some())).sum();
+    let open_at = |lifetime: &Lifetime| {
+        let lifetimes = vec![lifetime; open_brands];
+        quote!(#open<#(#lifetimes),*>)
+    };
+    let static_lifetime: Lifetime = parse_quote!('static);
+    let (open_type, open_static) = (open_at(brand), open_at(&static_lifetime));
+
+    let entries = brands.iter().map(|(name, field_brand, content_brand)| {
+        let name_str = name.to_string();
+        let (field_brand, content_brand) = (option(field_brand), option(content_brand));
+        quote! {
+            ::demo::branded_struct::BrandedField {
+                name: #name_str,
+                brand: #field_brand,
+                content_brand: #content_brand,
+                offset: ::core::mem::offset_of!(Self, #name),
+            }
+        }
+    });
+    let names = brands.iter().map(|(name, _, _)| name);
+    Ok(quote! {
+        #[automatically_derived]
+        unsafe impl<#brand> ::demo::branded_struct::BrandedStruct for #ident<#brand> {
+            type Open = #open_type;
+            const FIELDS: &'static [::demo::branded_struct::BrandedField] = &[#(#entries),*];
+        }
+        const _: () = ::demo::assert_same_layout!(#ident<'static>, #open_static; #(#names),*);
+    })
+}
+
+#[proc_macro_derive(BrandedEnum, attributes(branded))]
+pub fn derive_branded_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    branded_enum(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn branded_enum(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let (ident, vis) = (&input.ident, &input.vis);
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(ident, "only enums can be split variant by variant"));
+    };
+    let brand = brand(input)?;
+    let variants_ident: Ident = branded_attr(input, "variants", "the struct of variant tokens")?;
+
+    let mut metadata = Vec::new();
+    let mut projected = Vec::new();
+    for variant in &data.variants {
+        let name = variant.ident.to_string();
+        // only a variant whose payload is a single cell of the brand can be
+        // projected out
+        let cell = match variant.fields.iter().collect::<Vec<_>>()[..] {
+            [field] => cell_value(&field.ty, brand).map(|value| (field, value)),
+            _ => None,
+        };
+        let variant_brand = cell.as_ref().map(|_| snake_case(&name));
+        let brand_str = option(&variant_brand);
+        metadata.push(quote! {
+            ::demo::branded_enum::BrandedVariant { name: #name, brand: #brand_str }
+        });
+        if let Some((field, value)) = cell {
+            projected.push((variant, field, value, variant_brand.unwrap()));
+        }
+    }
+    let index_arms = index_arms(&variants(input));
+
+    let mut items = Vec::new();
+    let mut token_fields = Vec::new();
+    let mut brands = Vec::new();
+    for (variant, field, value, variant_brand) in &projected {
+        let variant_ident = &variant.ident;
+        let marker = format_ident!("{}{}", ident, variant_ident);
+        let pattern = match &field.ident {
+            Some(name) => quote!(#ident::#variant_ident { #name: cell }),
+            None => quote!(#ident::#variant_ident(cell)),
+        };
+        let doc = format!("The `{ident}::{variant_ident}` variant, to project with `GhostCell::project_variant`.");
+        items.push(quote! {
+            #[doc = #doc]
+            #vis struct #marker;
+            #[automatically_derived]
+            unsafe impl<#brand> ::demo::branded_enum::Variant<#brand, #ident<#brand>> for #marker {
+                type Field = #value;
+                #[inline]
+                fn cell<'__a>(value: &'__a #ident<#brand>) -> ::core::option::Option<&'__a ::demo::GhostCell<#brand, #value>> {
+                    match value {
+                        #pattern => ::core::option::Option::Some(cell),
+                        #[allow(unreachable_patterns)]
+                        _ => ::core::option::Option::None,
+                    }
+                }
+            }
+        });
+        let (field_ident, lifetime) = (format_ident!("{}", variant_brand), Lifetime::new(&format!("'{variant_brand}"), Span::call_site()));
+        token_fields.push(quote! {
+            pub #field_ident: ::demo::branded_enum::VariantToken<#brand, #lifetime, #marker>
+        });
+        brands.push(lifetime);
+    }
+    let doc = format!("One token per variant of `{ident}` with a cell of its own, split off the token for the enums' content by `{ident}::split_variants`.");
+    let content_brands = vec![brand; brands.len()];
+    let fresh_brands = vec![quote!('_); brands.len()];
+    Ok(quote! {
+        #[automatically_derived]
+        unsafe impl<#brand> ::demo::branded_enum::BrandedEnum for #ident<#brand> {
+            const VARIANTS: &'static [::demo::branded_enum::BrandedVariant] = &[#(#metadata),*];
+            #[inline]
+            fn variant_index(&self) -> usize {
+                match self { #(#index_arms)* }
+            }
+        }
+        #(#items)*
+        #[doc = #doc]
+        #vis struct #variants_ident<#brand #(, #brands)*> {
+            #(#token_fields,)*
+            _content: ::core::marker::PhantomData<fn(&#brand ()) -> &#brand ()>,
+        }
+        impl<#brand> #ident<#brand> {
+            /// Splits the token for the enums' content into a token per
+            /// variant, and runs `f` with them.
+            #vis fn split_variants<__R>(
+                token: &mut ::demo::GhostToken<#brand>,
+                f: impl for<#(#brands),*> ::core::ops::FnOnce(&mut #variants_ident<#brand #(, #brands)*>) -> __R,
+            ) -> __R {
+                ::demo::assert_same_layout!(::demo::GhostToken<#brand>, #variants_ident<#brand #(, #content_brands)*>);
+                // The variant tokens are as zero-sized as the token, and take
+                // its place for as long as `f` runs; each variant's brand is
+                // fresh, and only reaches that variant's cells.
+                f(unsafe { &mut *(token as *mut ::demo::GhostToken<#brand>).cast::<#variants_ident<#brand #(, #fresh_brands)*>>() })
+            }
+        }
+    })
+}
+
+/// `X`, if `ty` is `GhostCell<'brand, X>`.
+fn cell_value(ty: &Type, brand: &Lifetime) -> Option<Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    let PathArguments::AngleBracketed(args) = &segment.arguments else { return None };
+    if segment.ident != "GhostCell" {
+        return None;
+    }
+    match args.args.iter().collect::<Vec<_>>()[..] {
+        [GenericArgument::Lifetime(lifetime), GenericArgument::Type(value)] if lifetime == brand => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+/// The input's only generic parameter, the brand of all of its cells.
+fn brand(input: &DeriveInput) -> syn::Result<&Lifetime> {
+    match input.generics.params.iter().collect::<Vec<_>>()[..] {
+        [GenericParam::Lifetime(param)] => Ok(&param.lifetime),
+        _ => Err(syn::Error::new_spanned(
+            &input.generics,
+            "expected exactly one lifetime parameter, the brand of every cell",
+        )),
+    }
+}
+
+/// The value of `key` in the input's `#[branded(key = ...)]`, which names
+/// `what`.
+fn branded_attr<T: Parse>(input: &DeriveInput, key: &str, what: &str) -> syn::Result<T> {
+    let mut value = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("branded")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                value = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(format!("expected `{key} = ...`")))
+            }
+        })?;
+    }
+    value.ok_or_else(|| syn::Error::new_spanned(&input.ident, format!("expected `#[branded({key} = ...)]`, naming {what}")))
+}
+
+/// Counts the uses of `brand` in a type.
+struct BrandCount<'a> {
+    brand: &'a Lifetime,
+    count: usize,
+}
+impl<'ast> Visit<'ast> for BrandCount<'_> {
+    fn visit_lifetime(&mut self, lifetime: &'ast Lifetime) {
+        if lifetime == self.brand {
+            self.count += 1;
+        }
+    }
+}
+
+/// `value` as an `Option<&'static str>` expression.
+fn option(value: &Option<String>) -> TokenStream2 {
+    match value {
+        Some(value) => quote!(::core::option::Option::Some(#value)),
+        None => quote!(::core::option::Option::None),
+    }
+}
+
+/// A struct, or one variant of an enum.
+struct Variant<'a> {
+    ident: &'a Ident,
+    path: TokenStream2,
+    fields: &'a Fields,
+}
+impl Variant<'_> {
+    /// Names to bind each field to in a pattern.
+    fn bindings(&self, prefix: &str) -> Vec<Ident> {
+        (0..self.fields.len()).map(|i| format_ident!("{}{}", prefix, i)).collect()
+    }
+    /// A pattern binding every field, to follow `self.path`.
+    fn pattern(&self, prefix: &str) -> TokenStream2 {
+        let bindings = self.bindings(prefix);
+        match self.fields {
+            Fields::Named(named) => {
+                let names = named.named.iter().map(|f| &f.ident);
+                quote!({ #(#names: #bindings),* })
+            }
+            Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+            Fields::Unit => quote!(),
+        }
+    }
+}
+
+fn variants(input: &DeriveInput) -> Vec<Variant<'_>> {
+    match &input.data {
+        Data::Struct(data) => vec![Variant {
+            ident: &input.ident,
+            path: quote!(Self),
+            fields: &data.fields,
+        }],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|v| {
+                let ident = &v.ident;
+                Variant {
+                    ident,
+                    path: quote!(Self::#ident),
+                    fields: &v.fields,
+                }
+            })
+            .collect(),
+        Data::Union(_) => Vec::new(),
+    }
+}
+
+/// Match arms mapping each variant to its position in the declaration.
+fn index_arms(variants: &[Variant<'_>]) -> Vec<TokenStream2> {
+    variants
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let path = &v.path;
+            match v.fields {
+                Fields::Named(_) => quote!(#path { .. } => #i,),
+                Fields::Unnamed(_) => quote!(#path(..) => #i,),
+                Fields::Unit => quote!(#path => #i,),
+            }
+        })
+        .collect()
+}
+
+/// Wraps `body` in an impl of `trait_path<'__id>` for the input type, bounded
+/// on every field type implementing the trait too.
+fn implement(input: &DeriveInput, trait_path: TokenStream2, body: TokenStream2) -> TokenStream {
+    if let Data::Union(_) = input.data {
+        return syn::Error::new_spanned(&input.ident, "unions cannot be read field by field")
+            .to_compile_error()
+            .into();
+    }
+    let ident = &input.ident;
+    let mut generics = input.generics.clone();
+    let field_types: Vec<_> = variants(input)
+        .iter()
+        .flat_map(|v| v.fields.iter().map(|f| f.ty.clone()))
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for ty in field_types {
+        where_clause.predicates.push(parse_quote!(#ty: #trait_path<'__id>));
+    }
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let mut impl_generics = generics.clone();
+    impl_generics.params.insert(0, parse_quote!('__id));
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #trait_path<'__id> for #ident #ty_generics #where_clause {
+            #body
+        }
+    }
+    .into()
+}
@@ -0,0 +1,456 @@
+//! Group borrowing through a `GhostToken` against the usual ways of sharing
+//! mutable data: `RefCell`, `Rc<RefCell>`, `Mutex`, `RwLock`, `qcell::QCell`,
+//! and plain `&mut` as the baseline with no cells at all.
+//!
+//! Each workload is written once, over a `Family` of cells, so every variant
+//! runs the same code apart from how a cell is opened:
+//!
+//! - `attack`: every entity attacks the next, following `demo_game::attack`.
+//! - `list`: walks a linked list, bumping every value.
+//! - `dfs`: a depth-first search over a random graph, marking nodes visited.
+//!
+//! Throughput is reported per entity, node or vertex. Criterion doesn't
+//! measure memory, so the bytes each kind of cell takes for a `u32`,
+//! counting any heap allocation of its own, are printed before the runs.
+//!
+//! ```text
+//! cargo bench --bench borrowing
+//! ```
+use std::{
+    cell::RefCell,
+    hint::black_box,
+    mem::size_of,
+    rc::Rc,
+    sync::{Mutex, RwLock},
+};
+
+use criterion::{criterion_group, BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use criterion::measurement::WallTime;
+use demo::{make_guard, GhostCell, GhostToken};
+use qcell::{QCell, QCellOwner};
+
+const ENTITIES: usize = 1_000;
+const LIST_NODES: usize = 10_000;
+const GRAPH_NODES: usize = 1_000;
+const GRAPH_DEGREE: usize = 4;
+
+/// A kind of cell, and what has to be held to open one.
+trait Family<'id> {
+    const NAME: &'static str;
+    type Cell<T>;
+    type Owner;
+    fn new<T>(owner: &Self::Owner, value: T) -> Self::Cell<T>;
+    fn read<T, R>(cell: &Self::Cell<T>, owner: &Self::Owner, f: impl FnOnce(&T) -> R) -> R;
+    fn write<T, R>(cell: &Self::Cell<T>, owner: &mut Self::Owner, f: impl FnOnce(&mut T) -> R) -> R;
+    /// The bytes a cell of `T` takes, including its own heap allocation.
+    fn cell_bytes<T>() -> usize {
+        size_of::<Self::Cell<T>>()
+    }
+}
+
+struct Ghost;
+impl<'id> Family<'id> for Ghost {
+    const NAME: &'static str = "GhostCell";
+    type Cell<T> = GhostCell<'id, T>;
+    type Owner = GhostToken<'id>;
+    #[inline]
+    fn new<T>(_: &Self::Owner, value: T) -> Self::Cell<T> {
+        GhostCell::new(value)
+    }
+    #[inline]
+    fn read<T, R>(cell: &Self::Cell<T>, owner: &Self::Owner, f: impl FnOnce(&T) -> R) -> R {
+        f(cell.borrow(owner))
+    }
+    #[inline]
+    fn write<T, R>(cell: &Self::Cell<T>, owner: &mut Self::Owner, f: impl FnOnce(&mut T) -> R) -> R {
+        f(cell.borrow_mut(owner))
+    }
+}
+
+struct RefCells;
+impl<'id> Family<'id> for RefCells {
+    const NAME: &'static str = "RefCell";
+    type Cell<T> = RefCell<T>;
+    type Owner = ();
+    #[inline]
+    fn new<T>(_: &(), value: T) -> RefCell<T> {
+        RefCell::new(value)
+    }
+    #[inline]
+    fn read<T, R>(cell: &RefCell<T>, _: &(), f: impl FnOnce(&T) -> R) -> R {
+        f(&cell.borrow())
+    }
+    #[inline]
+    fn write<T, R>(cell: &RefCell<T>, _: &mut (), f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut cell.borrow_mut())
+    }
+}
+
+struct RcRefCells;
+impl<'id> Family<'id> for RcRefCells {
+    const NAME: &'static str = "Rc<RefCell>";
+    type Cell<T> = Rc<RefCell<T>>;
+    type Owner = ();
+    #[inline]
+    fn new<T>(_: &(), value: T) -> Rc<RefCell<T>> {
+        Rc::new(RefCell::new(value))
+    }
+    #[inline]
+    fn read<T, R>(cell: &Rc<RefCell<T>>, _: &(), f: impl FnOnce(&T) -> R) -> R {
+        f(&cell.borrow())
+    }
+    #[inline]
+    fn write<T, R>(cell: &Rc<RefCell<T>>, _: &mut (), f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut cell.borrow_mut())
+    }
+    fn cell_bytes<T>() -> usize {
+        // the pointer, then the strong and weak counts next to the `RefCell`
+        size_of::<Rc<RefCell<T>>>() + 2 * size_of::<usize>() + size_of::<RefCell<T>>()
+    }
+}
+
+struct Mutexes;
+impl<'id> Family<'id> for Mutexes {
+    const NAME: &'static str = "Mutex";
+    type Cell<T> = Mutex<T>;
+    type Owner = ();
+    #[inline]
+    fn new<T>(_: &(), value: T) -> Mutex<T> {
+        Mutex::new(value)
+    }
+    #[inline]
+    fn read<T, R>(cell: &Mutex<T>, _: &(), f: impl FnOnce(&T) -> R) -> R {
+        f(&cell.lock().unwrap())
+    }
+    #[inline]
+    fn write<T, R>(cell: &Mutex<T>, _: &mut (), f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut cell.lock().unwrap())
+    }
+}
+
+struct RwLocks;
+impl<'id> Family<'id> for RwLocks {
+    const NAME: &'static str = "RwLock";
+    type Cell<T> = RwLock<T>;
+    type Owner = ();
+    #[inline]
+    fn new<T>(_: &(), value: T) -> RwLock<T> {
+        RwLock::new(value)
+    }
+    #[inline]
+    fn read<T, R>(cell: &RwLock<T>, _: &(), f: impl FnOnce(&T) -> R) -> R {
+        f(&cell.read().unwrap())
+    }
+    #[inline]
+    fn write<T, R>(cell: &RwLock<T>, _: &mut (), f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut cell.write().unwrap())
+    }
+}
+
+struct QCells;
+impl<'id> Family<'id> for QCells {
+    const NAME: &'static str = "QCell";
+    type Cell<T> = QCell<T>;
+    type Owner = QCellOwner;
+    #[inline]
+    fn new<T>(owner: &QCellOwner, value: T) -> QCell<T> {
+        QCell::new(owner, value)
+    }
+    #[inline]
+    fn read<T, R>(cell: &QCell<T>, owner: &QCellOwner, f: impl FnOnce(&T) -> R) -> R {
+        f(owner.ro(cell))
+    }
+    #[inline]
+    fn write<T, R>(cell: &QCell<T>, owner: &mut QCellOwner, f: impl FnOnce(&mut T) -> R) -> R {
+        f(owner.rw(cell))
+    }
+}
+
+/// Runs `f` once per family, each with an owner of its own.
+macro_rules! for_each_family {
+    ($f:ident($($arg:expr),*)) => {{
+        make_guard!(token);
+        $f::<Ghost>($($arg,)* token);
+        $f::<RefCells>($($arg,)* ());
+        $f::<RcRefCells>($($arg,)* ());
+        $f::<Mutexes>($($arg,)* ());
+        $f::<RwLocks>($($arg,)* ());
+        $f::<QCells>($($arg,)* QCellOwner::new());
+    }};
+}
+
+fn report_memory() {
+    fn row<'id, F: Family<'id>>(_: F::Owner) {
+        println!("{:>12}: {:>2} bytes per cell, {:>2} per owner", F::NAME, F::cell_bytes::<u32>(), size_of::<F::Owner>());
+    }
+    println!("memory per cell of u32:");
+    for_each_family!(row());
+    println!("{:>12}: {:>2} bytes per value", "&mut", size_of::<u32>());
+}
+
+enum Hand<D> {
+    Shield { durability: D },
+    Sword,
+}
+/// `demo_game::Entity`, with each cell of the given family.
+struct Entity<'id, F: Family<'id>> {
+    hp: F::Cell<u32>,
+    rings: F::Cell<Vec<F::Cell<u32>>>,
+    hand: F::Cell<Hand<F::Cell<u32>>>,
+    energy: F::Cell<i32>,
+}
+fn entities<'id, F: Family<'id>>(owner: &F::Owner) -> Vec<Entity<'id, F>> {
+    (0..ENTITIES as u32)
+        .map(|i| Entity {
+            hp: F::new(owner, 1_000),
+            rings: F::new(owner, vec![F::new(owner, i % 7), F::new(owner, 1)]),
+            hand: F::new(owner, if i % 3 == 0 {
+                Hand::Sword
+            } else {
+                Hand::Shield { durability: F::new(owner, i % 5) }
+            }),
+            energy: F::new(owner, 100),
+        })
+        .collect()
+}
+fn damage<'id, F: Family<'id>>(a: &Entity<'id, F>, d: &Entity<'id, F>, owner: &F::Owner) -> u32 {
+    let power: u32 = F::read(&a.rings, owner, |rings| rings.iter().map(|ring| F::read(ring, owner, |p| *p)).sum());
+    let armor = F::read(&d.hand, owner, |hand| match hand {
+        Hand::Shield { durability } => F::read(durability, owner, |d| *d),
+        Hand::Sword => 0,
+    });
+    (10 + power).saturating_sub(armor)
+}
+fn attack<'id, F: Family<'id>>(a: &Entity<'id, F>, d: &Entity<'id, F>, owner: &mut F::Owner) {
+    let dealt = damage(a, d, owner);
+    let defend_cost = damage(d, a, owner) / 4;
+    F::write(&a.energy, owner, |energy| *energy -= (dealt / 2) as i32);
+    F::write(&d.energy, owner, |energy| *energy -= defend_cost as i32);
+    F::write(&d.hp, owner, |hp| *hp = hp.saturating_sub(dealt));
+}
+fn bench_attack<'id, F: Family<'id>>(group: &mut BenchmarkGroup<'_, WallTime>, mut owner: F::Owner) {
+    let entities = entities::<F>(&owner);
+    group.bench_function(F::NAME, |b| {
+        b.iter(|| {
+            for (a, d) in entities.iter().zip(entities.iter().cycle().skip(1)) {
+                attack(a, d, &mut owner);
+            }
+        })
+    });
+}
+
+struct ListNode {
+    value: u64,
+    next: Option<usize>,
+}
+/// The order nodes are linked in, scattered so that walking the list isn't
+/// just walking the `Vec`.
+fn list_order() -> Vec<usize> {
+    let mut order: Vec<usize> = (0..LIST_NODES).collect();
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    for i in (1..order.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        order.swap(i, state as usize % (i + 1));
+    }
+    order
+}
+fn list_nodes() -> Vec<ListNode> {
+    let order = list_order();
+    let mut nodes: Vec<_> = (0..LIST_NODES as u64).map(|value| ListNode { value, next: None }).collect();
+    for pair in order.windows(2) {
+        nodes[pair[0]].next = Some(pair[1]);
+    }
+    nodes
+}
+fn bench_list<'id, F: Family<'id>>(group: &mut BenchmarkGroup<'_, WallTime>, head: usize, mut owner: F::Owner) {
+    let nodes: Vec<F::Cell<ListNode>> = list_nodes().into_iter().map(|node| F::new(&owner, node)).collect();
+    group.bench_function(F::NAME, |b| {
+        b.iter(|| {
+            let mut sum = 0;
+            let mut cur = Some(head);
+            while let Some(i) = cur {
+                cur = F::write(&nodes[i], &mut owner, |node| {
+                    node.value += 1;
+                    sum += node.value;
+                    node.next
+                });
+            }
+            black_box(sum)
+        })
+    });
+}
+
+struct Vertex {
+    value: u64,
+    visited: u32,
+    edges: Vec<usize>,
+}
+fn vertices() -> Vec<Vertex> {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    (0..GRAPH_NODES as u64)
+        .map(|value| Vertex {
+            value,
+            visited: 0,
+            edges: (0..GRAPH_DEGREE)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    state as usize % GRAPH_NODES
+                })
+                .collect(),
+        })
+        .collect()
+}
+fn bench_dfs<'id, F: Family<'id>>(group: &mut BenchmarkGroup<'_, WallTime>, mut owner: F::Owner) {
+    let vertices: Vec<F::Cell<Vertex>> = vertices().into_iter().map(|v| F::new(&owner, v)).collect();
+    // Each run marks vertices with its own number, so nothing needs resetting.
+    let mut run = 0;
+    let mut stack = Vec::new();
+    group.bench_function(F::NAME, |b| {
+        b.iter(|| {
+            run += 1;
+            let mut sum = 0;
+            stack.push(0);
+            while let Some(i) = stack.pop() {
+                F::write(&vertices[i], &mut owner, |v| {
+                    if v.visited != run {
+                        v.visited = run;
+                        sum += v.value;
+                        stack.extend_from_slice(&v.edges);
+                    }
+                });
+            }
+            black_box(sum)
+        })
+    });
+}
+
+/// The same workloads without cells, borrowing through `&mut` alone.
+mod plain {
+    use super::*;
+
+    pub struct Entity {
+        hp: u32,
+        rings: Vec<u32>,
+        hand: Hand<u32>,
+        energy: i32,
+    }
+    pub fn entities() -> Vec<Entity> {
+        (0..ENTITIES as u32)
+            .map(|i| Entity {
+                hp: 1_000,
+                rings: vec![i % 7, 1],
+                hand: if i % 3 == 0 {
+                    Hand::Sword
+                } else {
+                    Hand::Shield { durability: i % 5 }
+                },
+                energy: 100,
+            })
+            .collect()
+    }
+    fn damage(a: &Entity, d: &Entity) -> u32 {
+        let power: u32 = a.rings.iter().sum();
+        let armor = match d.hand {
+            Hand::Shield { durability } => durability,
+            Hand::Sword => 0,
+        };
+        (10 + power).saturating_sub(armor)
+    }
+    pub fn attack(a: &mut Entity, d: &mut Entity) {
+        let dealt = damage(a, d);
+        let defend_cost = damage(d, a) / 4;
+        a.energy -= (dealt / 2) as i32;
+        d.energy -= defend_cost as i32;
+        d.hp = d.hp.saturating_sub(dealt);
+    }
+    pub fn attack_all(entities: &mut [Entity]) {
+        for i in 0..entities.len() - 1 {
+            let (a, d) = entities.split_at_mut(i + 1);
+            attack(&mut a[i], &mut d[0]);
+        }
+        let (d, a) = entities.split_at_mut(entities.len() - 1);
+        attack(&mut a[0], &mut d[0]);
+    }
+}
+
+fn attack_loop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attack");
+    group.throughput(Throughput::Elements(ENTITIES as u64));
+    for_each_family!(bench_attack(&mut group));
+    let mut entities = plain::entities();
+    group.bench_function("&mut", |b| b.iter(|| plain::attack_all(&mut entities)));
+    // The real `Entity` and `attack`, for comparison with the port above.
+    {
+        make_guard!(token);
+        let mut token = token;
+        let entities: Vec<_> = (0..ENTITIES).map(|_| demo_game::Entity::new()).collect();
+        group.bench_function(BenchmarkId::new("GhostCell", "demo_game::attack"), |b| {
+            b.iter(|| {
+                for (a, d) in entities.iter().zip(entities.iter().cycle().skip(1)) {
+                    demo_game::attack(a, d, &mut token);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn list_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list");
+    group.throughput(Throughput::Elements(LIST_NODES as u64));
+    let head = list_order()[0];
+    for_each_family!(bench_list(&mut group, head));
+    let mut nodes = list_nodes();
+    group.bench_function("&mut", |b| {
+        b.iter(|| {
+            let mut sum = 0;
+            let mut cur = Some(head);
+            while let Some(i) = cur {
+                let node = &mut nodes[i];
+                node.value += 1;
+                sum += node.value;
+                cur = node.next;
+            }
+            black_box(sum)
+        })
+    });
+    group.finish();
+}
+
+fn graph_dfs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dfs");
+    group.throughput(Throughput::Elements(GRAPH_NODES as u64));
+    for_each_family!(bench_dfs(&mut group));
+    let mut vertices = vertices();
+    let mut run = 0;
+    let mut stack = Vec::new();
+    group.bench_function("&mut", |b| {
+        b.iter(|| {
+            run += 1;
+            let mut sum = 0;
+            stack.push(0);
+            while let Some(i) = stack.pop() {
+                let v = &mut vertices[i];
+                if v.visited != run {
+                    v.visited = run;
+                    sum += v.value;
+                    stack.extend_from_slice(&v.edges);
+                }
+            }
+            black_box(sum)
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, attack_loop, list_traversal, graph_dfs);
+
+fn main() {
+    report_memory();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,59 @@
+//! Runs a scenario file and prints what happened:
+//!
+//! ```text
+//! cargo run -p demo-game --features sim --bin group-sim -- game/scenarios/duel.toml
+//! ```
+//!
+//! Files ending in `.toml` are read as TOML, and anything else as JSON. See
+//! `demo_game::scenario` for what a scenario holds.
+//!
+//! With `--record <log>`, every change the scenario makes is also written to
+//! `<log>`, and `group-sim --replay <log>` replays such a log and checks that
+//! it ends in the state it recorded.
+use std::{env, ffi::OsString, fs, path::Path, process::ExitCode};
+
+use demo_game::{replay::ReplayLog, scenario::Scenario};
+
+const USAGE: &str = "usage: group-sim <scenario.toml | scenario.json> [--record <log>]\n       group-sim --replay <log>";
+
+fn main() -> ExitCode {
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let (path, result) = match args.as_slice() {
+        [flag, log] if flag == "--replay" => (log, replay(Path::new(log))),
+        [scenario] => (scenario, run(Path::new(scenario), None)),
+        [scenario, flag, log] if flag == "--record" => (scenario, run(Path::new(scenario), Some(Path::new(log)))),
+        _ => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match result {
+        Ok(output) => {
+            print!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("group-sim: {}: {err}", Path::new(path).display());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(path: &Path, record: Option<&Path>) -> Result<String, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(path)?;
+    let scenario = if path.extension().is_some_and(|ext| ext == "toml") {
+        Scenario::from_toml(&text)?
+    } else {
+        Scenario::from_json(&text)?
+    };
+    let report = scenario.run()?;
+    if let Some(log) = record {
+        fs::write(log, scenario.record()?.as_bytes())?;
+    }
+    Ok(report.to_string())
+}
+
+fn replay(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let changes = ReplayLog::from_bytes(fs::read(path)?).replay()?;
+    Ok(format!("replayed {changes} changes to the recorded final state\n"))
+}
@@ -0,0 +1,465 @@
+//! The group borrowing demo: entities with rings and a hand, attacking each
+//! other, with the casts that open an entity's group into a group per field.
+//!
+//! This is kept apart from `demo` itself, which only has the cell and token
+//! API and builds without `std`.
+
+#[cfg(kani)]
+mod verification;
+pub mod replay;
+pub mod scenario;
+pub mod world;
+
+pub use world::World;
+
+use demo::{BrandedEnum, BrandedStruct, DebugWithToken, GhostCell, GhostToken, HashWithToken, OrdWithToken, PartialEqWithToken};
+#[cfg(feature = "serde")]
+use demo::SerializeWithToken;
+use demo::make_guard;
+use demo::ghost_dot::{Trace, Tracer};
+
+
+/// GhostCell doesn't know about field project.
+/// on a technicality this is no problem for these examples, since all signatures can just be extended to transitively
+/// have lifetime parameters for all fields of the structure. However besides the obvious ergonomic issues with this,
+/// it also doesnt support recursion correctly.
+/// 
+/// and the ergonomic issue is a real one: so here are a couple casts I've added. I believe they seem reasonable!
+/// but please inspect them carefully. very curious to know any issues anyone finds.
+
+/// This is quite a cool rule if true, but definitely needs to be more
+/// thoroughly inspected.
+/// "when the r region contains r2, any object that can be accessed via 'r2 can be accessed via 'r"
+/// this is used below for the union of groups.
+///
+/// The result is tied to `x` as well as `r`, so `'r2`'s token stays shut in `x` for as long as the entity is in `'r`.
+fn entity_cast_group_mut<'r, 'r2, 'a>(
+    x: &'a GhostCell<'r, &mut GhostToken<'r2>>,
+    r: &'a mut Entity<'r2>,
+) -> &'a mut Entity<'r> {
+    unsafe {
+        &mut *(r as *mut _ as *mut _)
+    }
+}
+/// You can see the definition of `OpenEntity` below. the intention is that these types are derived from every struct.
+/// here we make the claim that a GhostToken can be projected to the ghost tokens for every field of a type,
+/// where the higher-ranked closure is effectively implementing an existential: With system F, we'd give `Entity`
+/// an existential for the lifetimes on each field, and this function is opening it.
+///
+/// The field brands used to be return lifetimes, picked by the caller, who could pick them all the same and get two
+/// tokens for one group. Bound by the closure, they are fresh by parametricity, as in `World::open`, and can't
+/// escape it: only values without them, like a `&mut Ring` borrowed for `'t`, can be returned.
+fn token_as_entity1_mut<'t, 'r, R>(
+    t: &'t mut GhostToken<'r>,
+    f: impl for<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy> FnOnce(
+        &'t mut EntityAccess<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy>,
+        for<'b> fn(&'b Entity<'r>) -> &'b OpenEntity<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy>,
+    ) -> R,
+) -> R {
+    demo::assert_same_layout!(GhostToken<'r>, EntityAccess<'r, 'r, 'r, 'r, 'r, 'r>);
+    // the field types are compared by `#[derive(BrandedStruct)]` on `Entity`
+    demo::assert_same_layout!(Entity<'r>, OpenEntity<'r, 'r, 'r, 'r, 'r, 'r>);
+    fn cast<'b, 'r>(e: &'b Entity<'r>) -> &'b OpenEntity<'r, 'r, 'r, 'r, 'r, 'r> {
+        unsafe { &*(e as *const Entity<'r>).cast() }
+    }
+    // The token is borrowed for `'t`, so no cell of `'r` is reachable but
+    // through the split tokens, and the closure can't tell its brands from
+    // `'r`.
+    f(unsafe { &mut *(t as *mut GhostToken<'r>).cast() }, cast)
+}
+
+
+
+
+fn example() {
+    let my_list: Vec<i64> = vec![1, 2, 3, 4];
+    // "every variable introduces a group:"
+    generativity::make_guard!(my_list_group);
+    let mut my_list_group = my_list_group;
+    let my_list = GhostCell::new(my_list);
+    let list_ref_a = &my_list;
+    let list_ref_b = &my_list;
+    list_ref_a.borrow_mut(&mut my_list_group).push(5);
+    list_ref_b.borrow_mut(&mut my_list_group).push(6);
+}
+/// With the commented-out line restored, this fails to compile: see
+/// `tests/ui/example_2.rs`.
+fn example_2() {
+    let my_list = vec![1, 2, 3, 4];
+    generativity::make_guard!(my_list_group);
+    let mut my_list_group = my_list_group;
+    let my_list = GhostCell::new(my_list);
+    let list_ref = &my_list;
+    let el_ref = &my_list.borrow(&my_list_group)[0];
+    // list_ref.borrow_mut(&mut my_list_group).push(5);
+    println!("{:?}", el_ref)
+}
+// Really these should all also be using GhostCell for the fields,
+// but for the sake of the demo I'll just use plain old data.
+#[derive(Clone, Debug, PartialEq, Eq, DebugWithToken, PartialEqWithToken, OrdWithToken, HashWithToken)]
+#[cfg_attr(feature = "serde", derive(SerializeWithToken, serde::Deserialize))]
+pub struct Ring {
+    pub power: u32,
+}
+#[derive(DebugWithToken, PartialEqWithToken, HashWithToken, BrandedEnum)]
+#[cfg_attr(feature = "serde", derive(SerializeWithToken, serde::Deserialize))]
+#[branded(variants = HandVariants)]
+pub enum Hand<'content> {
+    Shield {
+        durability: GhostCell<'content, u32>,
+    },
+    Sword { sharpness: u32 },
+}
+#[derive(DebugWithToken, PartialEqWithToken, HashWithToken, BrandedStruct)]
+#[cfg_attr(feature = "serde", derive(SerializeWithToken, serde::Deserialize))]
+#[branded(open = OpenEntity)]
+#[repr(C)]
+pub struct Entity<'content> {
+    pub hp: GhostCell<'content, u32>,
+    pub rings: GhostCell<'content, Vec<GhostCell<'content, Ring>>>,
+    pub hand: GhostCell<'content, Hand<'content>>,
+    pub energy: GhostCell<'content, i32>,
+}
+#[repr(C)]
+pub struct OpenEntity<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy> {
+    pub hp: GhostCell<'hp, u32>,
+    pub rings: GhostCell<'rings, Vec<GhostCell<'rings_content, Ring>>>,
+    pub hand: GhostCell<'hand, Hand<'hand_content>>,
+    pub energy: GhostCell<'energy, i32>,
+}
+/// One token per field of an `Entity`, produced by splitting the token for the
+/// whole entity.
+pub struct EntityAccess<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy> {
+    pub hp: GhostToken<'hp>,
+    pub rings: GhostToken<'rings>,
+    pub rings_content: GhostToken<'rings_content>,
+    pub hand: GhostToken<'hand>,
+    pub hand_content: GhostToken<'hand_content>,
+    pub energy: GhostToken<'energy>,
+}
+
+impl<'r> Entity<'r> {
+    pub fn new() -> Self {
+        Entity {
+            hp: GhostCell::new(100),
+            rings: GhostCell::new(vec![GhostCell::new(Ring { power: 1 })]),
+            hand: GhostCell::new(Hand::Shield { durability: GhostCell::new(5) }),
+            energy: GhostCell::new(100),
+        }
+    }
+    pub fn calculate_damage(&self, other: &Entity<'r>, access: &GhostToken<'r>) -> u32 {
+        let power = self.rings.borrow(access).iter().fold(0u32, |power, ring| power.saturating_add(ring.borrow(access).power));
+        let armor = match other.hand.borrow(access) {
+            Hand::Shield { durability } => *durability.borrow(access),
+            Hand::Sword { .. } => 0,
+        };
+        10u32.saturating_add(power).saturating_sub(armor)
+    }
+    pub fn calculate_attack_cost(&self, other: &Entity<'r>, access: &GhostToken<'r>) -> u32 {
+        self.calculate_damage(other, access) / 2
+    }
+    pub fn calculate_defend_cost(&self, other: &Entity<'r>, access: &GhostToken<'r>) -> u32 {
+        other.calculate_damage(self, access) / 4
+    }
+    pub fn use_energy(&self, cost: u32, access: &mut GhostToken<'r>) {
+        let energy = self.energy.borrow_mut(access);
+        *energy = energy.saturating_sub_unsigned(cost);
+    }
+    pub fn damage(&self, cost: u32, access: &mut GhostToken<'r>) {
+        let hp = self.hp.borrow_mut(access);
+        *hp = hp.saturating_sub(cost);
+    }
+    /// Powers up the ring at `ring` with a quarter of the entity's energy, as
+    /// `complex_attack` does for the first ring. The ring is mutated through
+    /// the `rings_content` token while the list stays borrowed through
+    /// `rings`.
+    pub fn power_up_ring(&self, ring: usize, access: &mut GhostToken<'r>) {
+        let boost = self.ring_boost(access);
+        token_as_entity1_mut(access, |entity_access, entity_cast| {
+            let open = entity_cast(self);
+            let ring = open.rings.borrow(&entity_access.rings)[ring].borrow_mut(&mut entity_access.rings_content);
+            ring.power = ring.power.saturating_add(boost);
+        })
+    }
+    /// How much powering up a ring adds to its power: a quarter of the
+    /// entity's energy, if it has any.
+    pub fn ring_boost(&self, access: &GhostToken<'r>) -> u32 {
+        (*self.energy.borrow(access) / 4).max(0) as u32
+    }
+}
+impl<'r> Default for Entity<'r> {
+    fn default() -> Self {
+        Entity::new()
+    }
+}
+
+pub fn attack<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let damage = a.calculate_damage(d, token);
+    let a_energy_cost = a.calculate_attack_cost(d, token);
+    let d_energy_cost = d.calculate_defend_cost(a, token);
+    a.use_energy(a_energy_cost, token);
+    d.use_energy(d_energy_cost, token);
+    d.damage(damage, token);
+}
+/// With `ring_ref` used at the end, this fails to compile: see
+/// `tests/ui/attack2.rs`.
+/// 
+/// demonstrating accessing field content and child groups
+pub fn attack2<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp = &a.hp;
+    let ring_ref = &a.rings.borrow(&token)[0];
+    
+    let damage = a.calculate_damage(d, token);
+    let a_energy_cost = a.calculate_attack_cost(d, token);
+    let d_energy_cost = d.calculate_defend_cost(a, token);
+    a.use_energy(a_energy_cost, token);
+    d.use_energy(d_energy_cost, token);
+    d.damage(damage , token);
+    println!("{:?}", hp.borrow(&token));
+    // println!("{:?}", ring_ref);
+}
+
+fn attack3<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp_ref = &d.hp;
+    let rings_list_ref = &d.rings;
+    let rand_n = a as *const _ as usize;
+    let ring_ref = &d.rings.borrow(&token)[rand_n];
+
+    let durability = match &*d.hand.borrow(&token) {
+        Hand::Shield { durability } => {
+            durability
+        }
+        Hand::Sword { sharpness } => {
+            panic!("irrelevant to the demo :)");
+        }
+    };
+    println!("{:?}", hp_ref.borrow(&token));
+    println!("{:?}", rings_list_ref.borrow(&token).len());
+    println!("{:?}", ring_ref.borrow(&token).power);
+    println!("{:?}", durability.borrow(&token));
+}
+/// With `ring_ref` or `durability` used at the end, this fails to compile: see
+/// `tests/ui/attack4.rs` and `tests/ui/attack4_durability.rs`.
+fn attack4<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp_ref = &d.hp;
+    let rings_list_ref = &d.rings;
+    let rand_n = a as *const _ as usize;
+    let ring_ref = &d.rings.borrow(&token)[rand_n];
+
+    let durability = match &*d.hand.borrow(&token) {
+        Hand::Shield { durability } => {
+            durability
+        }
+        Hand::Sword { sharpness } => {
+            panic!("irrelevant to the demo :)");
+        }
+    };
+    d.damage(10, token);
+    println!("{:?}", hp_ref.borrow(&token));
+    println!("{:?}", rings_list_ref.borrow(&token).len());
+    // println!("{:?}", ring_ref.borrow(&token).power);
+    // println!("{:?}", durability);
+}
+// fn union_groups<'r>(a: &mut GhostToken<'a>, b: &mut GhostToken<'b>, r: GhostToken<'r>) ->  {
+//     GhostToken::new(id)
+// }
+pub fn invoke_demo() {
+    let entity_a = Entity::new();
+    generativity::make_guard!(entity_a_content_group);
+    let mut entity_a_content_group = entity_a_content_group;
+    generativity::make_guard!(entity_a_group);
+    let mut entity_a_group = entity_a_group;
+    let entity_a = GhostCell::new(entity_a);
+
+    let entity_b = Entity::new();
+    generativity::make_guard!(entity_b_content_group);
+    let mut entity_b_content_group = entity_b_content_group;
+    generativity::make_guard!(entity_b_group);
+    let mut entity_b_group = entity_b_group;
+    let entity_b = GhostCell::new(entity_b);
+
+    _ = entity_a.borrow_mut(&mut entity_a_group).damage(0, &mut entity_a_content_group);
+    _ = entity_b.borrow_mut(&mut entity_b_group).damage(0, &mut entity_b_content_group);
+    // could eg try to pass just one of their groups,
+    // which rust will unify with the `'r` on both entities.
+    // so we actually get an error from trying to extend 
+    // entity_b_content_group to live as long as entity_a_group.
+    // (see tests/ui/invoke_demo_single_group.rs)
+    // attack(
+    //     entity_a.borrow(&entity_a_group),
+    //     entity_b.borrow(&entity_b_group),
+    //     &mut entity_b_content_group,
+    // );
+    make_guard!(a_b_content_union);
+    let mut a_b_content_union = a_b_content_union;
+    let a_in_group = GhostCell::new(&mut entity_a_content_group);
+    let b_in_group = GhostCell::new(&mut entity_b_content_group);
+    attack(
+        entity_cast_group_mut(&a_in_group, entity_a.borrow_mut(&mut entity_a_group)),
+        entity_cast_group_mut(&b_in_group, entity_b.borrow_mut(&mut entity_b_group)),
+        &mut a_b_content_union
+    );
+}
+
+pub fn complex_example_main() {
+    let entities = vec![
+        GhostCell::new(Entity::new()),
+        GhostCell::new(Entity::new()),
+    ];
+    generativity::make_guard!(entity_content_group);
+    let mut entity_content_group = entity_content_group;
+    generativity::make_guard!(entities_content_group);
+    let mut entities_content_group = entities_content_group;
+    generativity::make_guard!(entities_group);
+    let mut entities_group = entities_group;
+    let entities = GhostCell::new(entities);
+    attack(
+        entities.borrow(&entities_group)[0].borrow(&entities_content_group),
+        entities.borrow(&entities_group)[1].borrow(&entities_content_group),
+        &mut entity_content_group
+    );
+}
+//fn attack[mut r: group Entity](
+//    ref[r] a: Entity,
+//    ref[r] d: Entity):
+//  ref armor_ref = a.armor # Ref to a's armor
+//
+//  # Modifies a.rings' contents
+//  power_up_ring(a, a.rings[0])
+//
+//  # Valid, compiler knows we only modified a.rings' contents
+//  armor_ref.hardness += 2
+pub fn complex_attack<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    token_as_entity1_mut(token, |entity_access, entity_cast| {
+        let open_a = entity_cast(a);
+        // The shield's durability gets a brand of its own, split off the hand's
+        // content: holding it only borrows the `hand` token, which nothing below
+        // mutates.
+        Hand::split_variants(&mut entity_access.hand_content, |variants| {
+            let armor_ref = open_a.hand.project_variant(&entity_access.hand, &variants.shield)
+                .expect("irrelevant to the demo :)");
+
+            complex_power_up_ring(
+                open_a,
+                open_a.rings.borrow(&entity_access.rings)[0].borrow_mut(&mut entity_access.rings_content),
+                &entity_access.hp,
+                &entity_access.rings,
+                // &entity_access.rings_content,
+                &entity_access.hand,
+                variants,
+                &entity_access.energy,
+            );
+            let armor = armor_ref.borrow_mut(&mut variants.shield);
+            *armor = armor.saturating_add(2);
+        });
+    })
+}
+// # Wielder Entity's energy will power up the ring.
+// # Changes the ring, but does not change the wielder Entity.
+// fn complex_power_up_ring[e: group Entity, mut rr: group Ring = e.rings*](
+//     ref[e] entity: Entity,
+//     ref[rr] a_ring: Ring
+// ):
+fn complex_power_up_ring<'l1, 'l2, 'rings_content, 'l3, 'l4, 'l5>(
+    entity: &OpenEntity<'l1, 'l2, 'rings_content, 'l3, 'l4, 'l5>,
+    a_ring: &mut Ring,
+
+    // So rust can't reason about borrows already existing in the sigature
+    // - e.rings is lovely - but we can mimic it by just exhaustively listing
+    // the disjunction
+    token1: &GhostToken<'l1>,
+    token2: &GhostToken<'l2>,
+    token3: &GhostToken<'l3>,
+    // the hand's content, split per variant
+    token4: &HandVariants<'l4, '_>,
+    token5: &GhostToken<'l5>,
+
+) {
+    a_ring.power = a_ring.power.saturating_add((*entity.energy.borrow(token5) / 4).max(0) as u32)
+}
+
+impl<'id> Trace<'id> for Ring {}
+impl<'id> Trace<'id> for Hand<'id> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        if let Hand::Shield { durability } = self {
+            tracer.cell(durability);
+        }
+    }
+}
+/// An `Entity` is always held in a different group than its fields, like
+/// `entity_a_group` and `entity_a_content_group`, so the fields are drawn as
+/// foreign cells, to be filled in by tracing the content group.
+impl<'id, 'content> Trace<'id> for Entity<'content> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        tracer.foreign(&self.hp);
+        tracer.foreign(&self.rings);
+        tracer.foreign(&self.hand);
+        tracer.foreign(&self.energy);
+    }
+}
+
+/// The casts stay private, since their output brands are chosen by the
+/// caller, so they are run here rather than from `tests/unsafe_casts.rs`.
+/// Under Miri: `cargo +nightly miri test -p demo-game --lib`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `entity_cast_group_mut` changes only the brand of an `&mut Entity`,
+    /// which is the same pointer, so both entities can then be attacked
+    /// through the union of their content groups and the results read back
+    /// through their own.
+    #[test]
+    fn entity_cast_group_mut_union() {
+        let entity_a = Entity::new();
+        make_guard!(entity_a_content_group);
+        let mut entity_a_content_group = entity_a_content_group;
+        make_guard!(entity_a_group);
+        let mut entity_a_group = entity_a_group;
+        let entity_a = GhostCell::new(entity_a);
+
+        let entity_b = Entity::new();
+        make_guard!(entity_b_content_group);
+        let mut entity_b_content_group = entity_b_content_group;
+        make_guard!(entity_b_group);
+        let mut entity_b_group = entity_b_group;
+        let entity_b = GhostCell::new(entity_b);
+
+        make_guard!(a_b_content_union);
+        let mut a_b_content_union = a_b_content_union;
+        {
+            let a_in_group = GhostCell::new(&mut entity_a_content_group);
+            let b_in_group = GhostCell::new(&mut entity_b_content_group);
+            attack(
+                entity_cast_group_mut(&a_in_group, entity_a.borrow_mut(&mut entity_a_group)),
+                entity_cast_group_mut(&b_in_group, entity_b.borrow_mut(&mut entity_b_group)),
+                &mut a_b_content_union,
+            );
+        }
+
+        let a = entity_a.borrow(&entity_a_group);
+        let b = entity_b.borrow(&entity_b_group);
+        // 10 + 1 ring power - 5 durability = 6 damage, costing 3 and 1 energy.
+        assert_eq!(*b.hp.borrow(&entity_b_content_group), 94);
+        assert_eq!(*a.energy.borrow(&entity_a_content_group), 97);
+        assert_eq!(*b.energy.borrow(&entity_b_content_group), 99);
+    }
+
+    /// `token_as_entity1_mut` splits the token per field: a ring is mutated
+    /// through `rings_content` while the list stays borrowed through `rings`
+    /// and the hp is read through `hp`.
+    #[test]
+    fn token_as_entity1_mut_fields() {
+        make_guard!(token);
+        let mut token = token;
+        let entity = Entity::new();
+        token_as_entity1_mut(&mut token, |access, cast| {
+            let open = cast(&entity);
+            let rings = open.rings.borrow(&access.rings);
+            let hp = open.hp.borrow(&access.hp);
+            rings[0].borrow_mut(&mut access.rings_content).power += *hp;
+            assert_eq!(rings[0].borrow(&access.rings_content).power, 101);
+        });
+        assert_eq!(entity.rings.borrow(&token)[0].borrow(&token).power, 101);
+    }
+}
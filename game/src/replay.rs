@@ -0,0 +1,518 @@
+//! Recording every change to a group of entities, to replay it for a bug
+//! report.
+//!
+//! A `RecordingToken` stands in for the `GhostToken` of a group of `Entity`s,
+//! the way a `HistoryToken` does for its `HistoryCell`s. It only lends the
+//! token out shared, so every change goes through it, and each is appended
+//! to a `ReplayLog`: the cell it changed, the operation, and the cell's value
+//! before and after, encoded by `Recordable`. The nested cells, a ring or a
+//! shield's durability, are reached by splitting the token like
+//! `complex_attack` does, so they can be changed while the list or hand
+//! holding them is borrowed.
+//!
+//! The game's actions aren't written out again here: `run` lends the token
+//! to the real `attack`, `complex_attack` or `Entity::power_up_ring`, then
+//! records every cell whose value changed.
+//!
+//! The log starts with the group's state when recording began and ends with
+//! its state when it finished. `ReplayLog::replay` builds a fresh group from
+//! the first, applies every change, and checks that it ends up at the second,
+//! failing at the first change that doesn't start from the value it was
+//! recorded from.
+//!
+//! ```
+//! use demo::make_guard;
+//! use demo_game::{replay::RecordingToken, Entity};
+//!
+//! make_guard!(token);
+//! let entities = vec![Entity::new(), Entity::new()];
+//! let mut token = RecordingToken::new(token, &entities);
+//! token.attack(0, 1);
+//! token.power_up_ring(0, 0);
+//! assert_eq!(*entities[1].hp.borrow(token.token()), 94);
+//!
+//! let (_, log) = token.finish();
+//! assert_eq!(log.replay(), Ok(4));
+//! ```
+use std::{fmt, mem};
+
+use demo::{make_guard, GhostCell, GhostToken, Snapshot};
+
+use crate::{attack, complex_attack, token_as_entity1_mut, Entity, EntityAccess, Hand, Ring};
+use crate::scenario::{EntityState, HandState};
+
+/// Marks the end of the changes, before the final state.
+const END: u8 = 0xff;
+
+/// A value that can be written to a `ReplayLog`, and read back.
+pub trait Recordable: Sized {
+    /// Appends `self` to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+    /// Reads a value from the start of `input`, and advances past it.
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+}
+
+impl Recordable for u64 {
+    /// As a LEB128 varint, so that small values take a byte.
+    fn encode(&self, out: &mut Vec<u8>) {
+        let mut n = *self;
+        while n >= 0x80 {
+            out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        out.push(n as u8);
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let mut n = 0;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = input.split_first()?;
+            *input = rest;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(n);
+            }
+        }
+        None
+    }
+}
+impl Recordable for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        u64::from(*self).encode(out)
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        u64::decode(input)?.try_into().ok()
+    }
+}
+impl Recordable for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out)
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        u64::decode(input)?.try_into().ok()
+    }
+}
+impl Recordable for i32 {
+    /// Zigzagged, so that small negative values are short too.
+    fn encode(&self, out: &mut Vec<u8>) {
+        ((*self << 1) ^ (*self >> 31)).cast_unsigned().encode(out)
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let n = u32::decode(input)?;
+        Some((n >> 1).cast_signed() ^ -((n & 1) as i32))
+    }
+}
+impl Recordable for Ring {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.power.encode(out)
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Ring { power: u32::decode(input)? })
+    }
+}
+
+/// A cell of an entity in a group, by the entity's index in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CellId {
+    Hp(usize),
+    Energy(usize),
+    /// The entity's index, then the ring's.
+    Ring(usize, usize),
+    Durability(usize),
+}
+impl Recordable for CellId {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (tag, entity) = match *self {
+            CellId::Hp(entity) => (0, entity),
+            CellId::Energy(entity) => (1, entity),
+            CellId::Ring(entity, _) => (2, entity),
+            CellId::Durability(entity) => (3, entity),
+        };
+        write_tagged(tag, entity, out);
+        if let CellId::Ring(_, ring) = self {
+            ring.encode(out);
+        }
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(match read_tagged(input)? {
+            (0, entity) => CellId::Hp(entity),
+            (1, entity) => CellId::Energy(entity),
+            (2, entity) => CellId::Ring(entity, usize::decode(input)?),
+            (3, entity) => CellId::Durability(entity),
+            _ => return None,
+        })
+    }
+}
+/// A byte telling which kind of thing follows, and a number.
+fn write_tagged(tag: u8, n: usize, out: &mut Vec<u8>) {
+    out.push(tag);
+    n.encode(out);
+}
+fn read_tagged(input: &mut &[u8]) -> Option<(u8, usize)> {
+    let (&tag, rest) = input.split_first()?;
+    *input = rest;
+    Some((tag, usize::decode(input)?))
+}
+
+/// How a recorded change was made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Operation {
+    /// The value was replaced, by `RecordingToken::set`.
+    Set = 0,
+    /// The value was changed in place, by `RecordingToken::update`.
+    Update = 1,
+}
+impl Recordable for Operation {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8)
+    }
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let (&op, rest) = input.split_first()?;
+        *input = rest;
+        match op {
+            0 => Some(Operation::Set),
+            1 => Some(Operation::Update),
+            _ => None,
+        }
+    }
+}
+
+type Resolve<T> = for<'x, 'r> fn(&'x [Entity<'r>], &'x mut GhostToken<'r>, CellId) -> Option<&'x mut T>;
+
+/// A cell holding a `T`, in any group of entities, by its `CellId`.
+pub struct EntityCell<T> {
+    id: CellId,
+    resolve: Resolve<T>,
+}
+impl<T> EntityCell<T> {
+    /// The cell's id, as recorded.
+    pub fn id(&self) -> CellId {
+        self.id
+    }
+}
+impl EntityCell<u32> {
+    pub fn hp(entity: usize) -> Self {
+        EntityCell { id: CellId::Hp(entity), resolve: resolve_hp }
+    }
+    /// The durability of the entity's shield, which only resolves while it
+    /// holds one.
+    pub fn durability(entity: usize) -> Self {
+        EntityCell { id: CellId::Durability(entity), resolve: resolve_durability }
+    }
+}
+impl EntityCell<i32> {
+    pub fn energy(entity: usize) -> Self {
+        EntityCell { id: CellId::Energy(entity), resolve: resolve_energy }
+    }
+}
+impl EntityCell<Ring> {
+    pub fn ring(entity: usize, ring: usize) -> Self {
+        EntityCell { id: CellId::Ring(entity, ring), resolve: resolve_ring }
+    }
+}
+
+fn resolve_hp<'x, 'r>(entities: &'x [Entity<'r>], token: &'x mut GhostToken<'r>, id: CellId) -> Option<&'x mut u32> {
+    let CellId::Hp(entity) = id else { return None };
+    Some(entities.get(entity)?.hp.borrow_mut(token))
+}
+fn resolve_energy<'x, 'r>(entities: &'x [Entity<'r>], token: &'x mut GhostToken<'r>, id: CellId) -> Option<&'x mut i32> {
+    let CellId::Energy(entity) = id else { return None };
+    Some(entities.get(entity)?.energy.borrow_mut(token))
+}
+fn resolve_ring<'x, 'r>(entities: &'x [Entity<'r>], token: &'x mut GhostToken<'r>, id: CellId) -> Option<&'x mut Ring> {
+    let CellId::Ring(entity, ring) = id else { return None };
+    token_as_entity1_mut(token, |access, cast| {
+        let EntityAccess { rings, rings_content, .. } = access;
+        let ring = cast(entities.get(entity)?).rings.borrow(rings).get(ring)?;
+        Some(ring.borrow_mut(rings_content))
+    })
+}
+fn resolve_durability<'x, 'r>(entities: &'x [Entity<'r>], token: &'x mut GhostToken<'r>, id: CellId) -> Option<&'x mut u32> {
+    let CellId::Durability(entity) = id else { return None };
+    token_as_entity1_mut(token, |access, cast| {
+        let EntityAccess { hand, hand_content, .. } = access;
+        match cast(entities.get(entity)?).hand.borrow(hand) {
+            Hand::Shield { durability } => Some(durability.borrow_mut(hand_content)),
+            Hand::Sword { .. } => None,
+        }
+    })
+}
+
+/// Wraps the token for a group of entities, and records every change made
+/// through it.
+pub struct RecordingToken<'a, 'r> {
+    token: GhostToken<'r>,
+    entities: &'a [Entity<'r>],
+    log: Vec<u8>,
+    entries: usize,
+}
+impl<'a, 'r> RecordingToken<'a, 'r> {
+    /// Starts recording changes to `entities`, from their state now.
+    pub fn new(token: GhostToken<'r>, entities: &'a [Entity<'r>]) -> Self {
+        let mut log = Vec::new();
+        write_state(entities, &token, &mut log);
+        RecordingToken { token, entities, log, entries: 0 }
+    }
+    /// The token, for reading the entities.
+    #[inline]
+    pub fn token(&self) -> &GhostToken<'r> {
+        &self.token
+    }
+    /// The entities being recorded.
+    #[inline]
+    pub fn entities(&self) -> &'a [Entity<'r>] {
+        self.entities
+    }
+    /// The number of changes recorded so far.
+    #[inline]
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+    /// Replaces the value in `cell` and returns the old one, or returns
+    /// `None` if there is no such cell.
+    pub fn set<T: Recordable>(&mut self, cell: EntityCell<T>, value: T) -> Option<T> {
+        let slot = (cell.resolve)(self.entities, &mut self.token, cell.id)?;
+        let old = mem::replace(slot, value);
+        record(&mut self.log, cell.id, Operation::Set, &old, slot);
+        self.entries += 1;
+        Some(old)
+    }
+    /// Changes the value in `cell` with `f`, or returns `None` if there is
+    /// no such cell.
+    pub fn update<T: Recordable + Clone, R>(&mut self, cell: EntityCell<T>, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let slot = (cell.resolve)(self.entities, &mut self.token, cell.id)?;
+        let old = slot.clone();
+        let result = f(slot);
+        record(&mut self.log, cell.id, Operation::Update, &old, slot);
+        self.entries += 1;
+        Some(result)
+    }
+    /// Stops recording, and returns the token and the log, which ends with
+    /// the entities' state now.
+    pub fn finish(mut self) -> (GhostToken<'r>, ReplayLog) {
+        self.log.push(END);
+        write_state(self.entities, &self.token, &mut self.log);
+        (self.token, ReplayLog { bytes: self.log })
+    }
+
+    /// Runs `f` on the entities and the token, and records each cell it
+    /// changed as an `Update`, by comparing the cells before and after. This
+    /// is how the game's own functions are recorded, rules and all.
+    ///
+    /// # Panics
+    ///
+    /// If `f` adds or removes rings, or changes the hand an entity holds
+    /// other than through a shield's durability: those aren't changes to a
+    /// cell, and can't be recorded.
+    pub fn run<R>(&mut self, f: impl FnOnce(&'a [Entity<'r>], &mut GhostToken<'r>) -> R) -> R {
+        let before: Vec<EntityState> = self.entities.iter().map(|entity| entity.snapshot(&self.token)).collect();
+        let result = f(self.entities, &mut self.token);
+        for (i, (old, entity)) in before.iter().zip(self.entities).enumerate() {
+            let new = entity.snapshot(&self.token);
+            let same_shape = old.rings.len() == new.rings.len() && match (old.hand, new.hand) {
+                (HandState::Shield { .. }, HandState::Shield { .. }) => true,
+                (old, new) => old == new,
+            };
+            assert!(same_shape, "entity {} changed outside its cells, which can't be recorded", i);
+            self.record_update(CellId::Hp(i), &old.hp, &new.hp);
+            self.record_update(CellId::Energy(i), &old.energy, &new.energy);
+            for (ring, (&old, &new)) in old.rings.iter().zip(&new.rings).enumerate() {
+                self.record_update(CellId::Ring(i, ring), &Ring { power: old }, &Ring { power: new });
+            }
+            if let (HandState::Shield { durability: old }, HandState::Shield { durability: new }) = (old.hand, new.hand) {
+                self.record_update(CellId::Durability(i), &old, &new);
+            }
+        }
+        result
+    }
+    /// Records that the cell `id` went from `old` to `new`, if it changed.
+    fn record_update<T: Recordable + PartialEq>(&mut self, id: CellId, old: &T, new: &T) {
+        if old != new {
+            record(&mut self.log, id, Operation::Update, old, new);
+            self.entries += 1;
+        }
+    }
+    /// `attack`, between the entities at `a` and `d`.
+    pub fn attack(&mut self, a: usize, d: usize) {
+        self.run(|entities, token| attack(&entities[a], &entities[d], token))
+    }
+    /// `complex_attack`, between the entities at `a` and `d`.
+    ///
+    /// # Panics
+    ///
+    /// If the attacker has no rings or holds no shield, like
+    /// `complex_attack`.
+    pub fn complex_attack(&mut self, a: usize, d: usize) {
+        self.run(|entities, token| complex_attack(&entities[a], &entities[d], token))
+    }
+    /// `Entity::power_up_ring`, for the entity at `entity`.
+    ///
+    /// # Panics
+    ///
+    /// If the entity has no ring at `ring`.
+    pub fn power_up_ring(&mut self, entity: usize, ring: usize) {
+        self.run(|entities, token| entities[entity].power_up_ring(ring, token))
+    }
+}
+impl<'a, 'r> fmt::Debug for RecordingToken<'a, 'r> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingToken")
+            .field("entities", &self.entities.len())
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+fn record<T: Recordable>(log: &mut Vec<u8>, id: CellId, op: Operation, old: &T, new: &T) {
+    id.encode(log);
+    op.encode(log);
+    old.encode(log);
+    new.encode(log);
+}
+
+/// Appends the state of every entity: the number of them, then each one's
+/// fields in order, with a byte for the kind of hand before its value.
+fn write_state<'r>(entities: &[Entity<'r>], token: &GhostToken<'r>, out: &mut Vec<u8>) {
+    entities.len().encode(out);
+    for entity in entities {
+        entity.hp.borrow(token).encode(out);
+        let rings = entity.rings.borrow(token);
+        rings.len().encode(out);
+        for ring in rings {
+            ring.borrow(token).encode(out);
+        }
+        match entity.hand.borrow(token) {
+            Hand::Shield { durability } => write_tagged(0, *durability.borrow(token) as usize, out),
+            Hand::Sword { sharpness } => write_tagged(1, *sharpness as usize, out),
+        }
+        entity.energy.borrow(token).encode(out);
+    }
+}
+fn read_state<'r>(input: &mut &[u8]) -> Option<Vec<Entity<'r>>> {
+    let len = usize::decode(input)?;
+    (0..len).map(|_| {
+        let hp = u32::decode(input)?;
+        let rings = usize::decode(input)?;
+        let rings = (0..rings).map(|_| Some(GhostCell::new(Ring::decode(input)?))).collect::<Option<_>>()?;
+        let hand = match read_tagged(input)? {
+            (0, durability) => Hand::Shield { durability: GhostCell::new(durability.try_into().ok()?) },
+            (1, sharpness) => Hand::Sword { sharpness: sharpness.try_into().ok()? },
+            _ => return None,
+        };
+        Some(Entity {
+            hp: GhostCell::new(hp),
+            rings: GhostCell::new(rings),
+            hand: GhostCell::new(hand),
+            energy: GhostCell::new(i32::decode(input)?),
+        })
+    }).collect()
+}
+
+/// The changes made through a `RecordingToken`, between the states of its
+/// entities when it started and when it finished.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ReplayLog {
+    bytes: Vec<u8>,
+}
+impl ReplayLog {
+    /// A log from bytes written out by `as_bytes`.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        ReplayLog { bytes }
+    }
+    /// The encoded log, to write out.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    /// Applies every change to a fresh group of entities in the recorded
+    /// starting state, checking that each starts from the value recorded,
+    /// and that the group ends in the recorded final state. Returns the
+    /// number of changes applied.
+    pub fn replay(&self) -> Result<usize, ReplayError> {
+        self.replay_to_end().map(|(entries, _)| entries)
+    }
+    /// The state the group ends up in when the log is replayed, without
+    /// names, which the log doesn't keep.
+    pub fn final_state(&self) -> Result<Vec<EntityState>, ReplayError> {
+        self.replay_to_end().map(|(_, states)| states)
+    }
+    /// `replay`, returning the final state along with the number of changes.
+    fn replay_to_end(&self) -> Result<(usize, Vec<EntityState>), ReplayError> {
+        let mut input = self.as_bytes();
+        let malformed = |input: &[u8]| ReplayError::Malformed { offset: self.bytes.len() - input.len() };
+        make_guard!(token);
+        let mut token = token;
+        let entities = read_state(&mut input).ok_or_else(|| malformed(input))?;
+
+        let mut entry = 0;
+        while input.first() != Some(&END) {
+            let at = input;
+            let cell = CellId::decode(&mut input)
+                .zip(Operation::decode(&mut input))
+                .ok_or_else(|| malformed(at))?
+                .0;
+            match cell {
+                CellId::Hp(i) => apply(&entities, &mut token, EntityCell::hp(i), &mut input, entry),
+                CellId::Energy(i) => apply(&entities, &mut token, EntityCell::energy(i), &mut input, entry),
+                CellId::Ring(i, ring) => apply(&entities, &mut token, EntityCell::ring(i, ring), &mut input, entry),
+                CellId::Durability(i) => apply(&entities, &mut token, EntityCell::durability(i), &mut input, entry),
+            }.map_err(|err| err.unwrap_or_else(|| malformed(at)))?;
+            entry += 1;
+        }
+
+        let mut end = vec![END];
+        write_state(&entities, &token, &mut end);
+        if input != end {
+            return Err(ReplayError::FinalState);
+        }
+        Ok((entry, entities.iter().map(|entity| EntityState::of(String::new(), entity, &token)).collect()))
+    }
+}
+impl fmt::Debug for ReplayLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplayLog").field("bytes", &self.bytes.len()).finish()
+    }
+}
+
+/// Applies the change at the start of `input` to `cell`. Fails with `None`
+/// if its values can't be decoded.
+fn apply<'r, T: Recordable + PartialEq>(
+    entities: &[Entity<'r>],
+    token: &mut GhostToken<'r>,
+    cell: EntityCell<T>,
+    input: &mut &[u8],
+    entry: usize,
+) -> Result<(), Option<ReplayError>> {
+    let (old, new) = T::decode(input).zip(T::decode(input)).ok_or(None)?;
+    let slot = (cell.resolve)(entities, token, cell.id)
+        .ok_or(ReplayError::UnknownCell { entry, cell: cell.id })?;
+    if *slot != old {
+        return Err(Some(ReplayError::Diverged { entry, cell: cell.id }));
+    }
+    *slot = new;
+    Ok(())
+}
+
+/// A `ReplayLog` that doesn't replay to the state it recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The log can't be decoded from this offset, in bytes, on.
+    Malformed { offset: usize },
+    /// A change is to a cell the group doesn't have.
+    UnknownCell { entry: usize, cell: CellId },
+    /// A cell didn't hold the value a change was recorded from.
+    Diverged { entry: usize, cell: CellId },
+    /// Every change applied, but the group didn't end up in the recorded
+    /// final state.
+    FinalState,
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Malformed { offset } => write!(f, "malformed replay log at byte {offset}"),
+            ReplayError::UnknownCell { entry, cell } => write!(f, "change {entry}: no cell {cell:?}"),
+            ReplayError::Diverged { entry, cell } => write!(f, "change {entry}: {cell:?} doesn't hold the value recorded"),
+            ReplayError::FinalState => write!(f, "replay didn't end in the recorded final state"),
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
@@ -0,0 +1,341 @@
+//! Scripted fights between entities, for trying out balance changes.
+//!
+//! A `Scenario` describes each entity as plain values, and a list of actions
+//! between them by name. `Scenario::run` builds an `Entity` for each, all in
+//! one group, applies the actions in order with `attack`, `complex_attack`
+//! and `Entity::power_up_ring`, and reports what each action did along with
+//! the entities' final state. `Scenario::record` runs them through a
+//! `RecordingToken` instead, for a `ReplayLog` to attach to a bug report. The
+//! `group-sim` binary, behind the `sim` feature, runs scenarios from TOML or
+//! JSON files.
+//!
+//! ```
+//! use demo_game::scenario::{Action, EntityState, HandState, Scenario};
+//!
+//! let scenario = Scenario {
+//!     entities: vec![
+//!         EntityState { name: "knight".into(), hp: 100, energy: 100, rings: vec![1], hand: HandState::Shield { durability: 5 } },
+//!         EntityState { name: "goblin".into(), hp: 30, energy: 20, rings: vec![], hand: HandState::Sword { sharpness: 3 } },
+//!     ],
+//!     actions: vec![Action::Attack { attacker: "knight".into(), defender: "goblin".into() }],
+//! };
+//!
+//! let report = scenario.run().unwrap();
+//! assert_eq!(report.entities[1].hp, 19);
+//! assert_eq!(report.events[0].to_string(), "knight attacks goblin for 11 damage: goblin hp 19, knight energy 95, goblin energy 18");
+//! ```
+use std::{collections::HashMap, fmt};
+
+use demo::{make_guard, GhostCell, GhostToken, Snapshot};
+
+use crate::{attack, complex_attack, Entity, Hand, Ring};
+use crate::replay::{RecordingToken, ReplayLog};
+
+/// Entities, and the actions to run between them in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "sim", derive(serde::Deserialize))]
+pub struct Scenario {
+    pub entities: Vec<EntityState>,
+    #[cfg_attr(feature = "sim", serde(default))]
+    pub actions: Vec<Action>,
+}
+
+/// An entity, named, as plain values: what a scenario starts from, and what
+/// its report ends with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "sim", derive(serde::Deserialize))]
+pub struct EntityState {
+    pub name: String,
+    pub hp: u32,
+    pub energy: i32,
+    /// The power of each ring.
+    #[cfg_attr(feature = "sim", serde(default))]
+    pub rings: Vec<u32>,
+    pub hand: HandState,
+}
+
+/// What an entity holds, as plain values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "sim", derive(serde::Deserialize))]
+#[cfg_attr(feature = "sim", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum HandState {
+    Shield { durability: u32 },
+    Sword { sharpness: u32 },
+}
+
+/// One step of a scenario, naming the entities it involves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "sim", derive(serde::Deserialize))]
+#[cfg_attr(feature = "sim", serde(tag = "action", rename_all = "snake_case"))]
+pub enum Action {
+    /// `attack(attacker, defender)`.
+    Attack { attacker: String, defender: String },
+    /// `complex_attack(attacker, defender)`, which needs the attacker to hold
+    /// a shield and wear at least one ring.
+    ComplexAttack { attacker: String, defender: String },
+    /// `entity.power_up_ring(ring)`.
+    PowerUpRing { entity: String, ring: usize },
+}
+
+/// What an action did, with the values it changed as they were after it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Attack {
+        attacker: String,
+        defender: String,
+        damage: u32,
+        defender_hp: u32,
+        attacker_energy: i32,
+        defender_energy: i32,
+    },
+    ComplexAttack {
+        attacker: String,
+        defender: String,
+        ring_power: u32,
+        durability: u32,
+    },
+    PowerUpRing {
+        entity: String,
+        ring: usize,
+        power: u32,
+    },
+}
+
+/// The outcome of `Scenario::run`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// One event per action, in order.
+    pub events: Vec<Event>,
+    /// The entities' final state, in the order the scenario lists them.
+    pub entities: Vec<EntityState>,
+}
+
+/// A scenario that can't be run. Every action is checked before any of them
+/// is, so a scenario either runs to the end or not at all.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScenarioError {
+    /// Two entities have the same name.
+    DuplicateEntity(String),
+    /// An action names an entity the scenario doesn't have.
+    UnknownEntity { action: usize, name: String },
+    /// An action names a ring the entity doesn't wear.
+    NoSuchRing { action: usize, entity: String, ring: usize },
+    /// A `complex_attack`'s attacker doesn't hold a shield.
+    NoShield { action: usize, entity: String },
+}
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::DuplicateEntity(name) => write!(f, "there are two entities named `{name}`"),
+            ScenarioError::UnknownEntity { action, name } => write!(f, "action {action}: no entity named `{name}`"),
+            ScenarioError::NoSuchRing { action, entity, ring } => write!(f, "action {action}: `{entity}` has no ring {ring}"),
+            ScenarioError::NoShield { action, entity } => write!(f, "action {action}: `{entity}` needs a shield for a complex attack"),
+        }
+    }
+}
+impl std::error::Error for ScenarioError {}
+
+impl Scenario {
+    /// Parses a scenario from TOML.
+    #[cfg(feature = "sim")]
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+    /// Parses a scenario from JSON.
+    #[cfg(feature = "sim")]
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    /// Runs the actions in order on a fresh group of entities.
+    pub fn run(&self) -> Result<Report, ScenarioError> {
+        let indices = self.check()?;
+        make_guard!(token);
+        let mut token = token;
+        let entities: Vec<Entity<'_>> = self.entities.iter().map(EntityState::to_entity).collect();
+        let name = |i: usize| self.entities[i].name.clone();
+
+        let events = self.actions.iter().zip(indices).map(|(action, (i, j))| match action {
+            Action::Attack { .. } => {
+                let (a, d) = (&entities[i], &entities[j]);
+                let damage = a.calculate_damage(d, &token);
+                attack(a, d, &mut token);
+                Event::Attack {
+                    attacker: name(i),
+                    defender: name(j),
+                    damage,
+                    defender_hp: *d.hp.borrow(&token),
+                    attacker_energy: *a.energy.borrow(&token),
+                    defender_energy: *d.energy.borrow(&token),
+                }
+            }
+            Action::ComplexAttack { .. } => {
+                let a = &entities[i];
+                complex_attack(a, &entities[j], &mut token);
+                let durability = match a.hand.borrow(&token) {
+                    Hand::Shield { durability } => *durability.borrow(&token),
+                    Hand::Sword { .. } => unreachable!("checked by `Scenario::check`"),
+                };
+                Event::ComplexAttack {
+                    attacker: name(i),
+                    defender: name(j),
+                    ring_power: a.rings.borrow(&token)[0].borrow(&token).power,
+                    durability,
+                }
+            }
+            &Action::PowerUpRing { ring, .. } => {
+                let e = &entities[i];
+                e.power_up_ring(ring, &mut token);
+                Event::PowerUpRing {
+                    entity: name(i),
+                    ring,
+                    power: e.rings.borrow(&token)[ring].borrow(&token).power,
+                }
+            }
+        }).collect();
+
+        let entities = self.entities.iter().zip(&entities)
+            .map(|(state, entity)| EntityState::of(state.name.clone(), entity, &token))
+            .collect();
+        Ok(Report { events, entities })
+    }
+
+    /// Runs the actions in order on a fresh group of entities, recording
+    /// every change they make.
+    pub fn record(&self) -> Result<ReplayLog, ScenarioError> {
+        let indices = self.check()?;
+        make_guard!(token);
+        let entities: Vec<Entity<'_>> = self.entities.iter().map(EntityState::to_entity).collect();
+        let mut token = RecordingToken::new(token, &entities);
+        for (action, (i, j)) in self.actions.iter().zip(indices) {
+            match *action {
+                Action::Attack { .. } => token.attack(i, j),
+                Action::ComplexAttack { .. } => token.complex_attack(i, j),
+                Action::PowerUpRing { ring, .. } => token.power_up_ring(i, ring),
+            }
+        }
+        Ok(token.finish().1)
+    }
+
+    /// Finds the entities each action involves, by index, and checks that
+    /// the action can be applied to them: neither the number of rings nor
+    /// the kind of hand ever changes, so this holds for the whole run.
+    fn check(&self) -> Result<Vec<(usize, usize)>, ScenarioError> {
+        let mut by_name = HashMap::new();
+        for (i, entity) in self.entities.iter().enumerate() {
+            if by_name.insert(entity.name.as_str(), i).is_some() {
+                return Err(ScenarioError::DuplicateEntity(entity.name.clone()));
+            }
+        }
+        self.actions.iter().enumerate().map(|(action, step)| {
+            let find = |name: &String| {
+                by_name.get(name.as_str()).copied()
+                    .ok_or_else(|| ScenarioError::UnknownEntity { action, name: name.clone() })
+            };
+            match step {
+                Action::Attack { attacker, defender } => Ok((find(attacker)?, find(defender)?)),
+                Action::ComplexAttack { attacker, defender } => {
+                    let (i, j) = (find(attacker)?, find(defender)?);
+                    let state = &self.entities[i];
+                    if !matches!(state.hand, HandState::Shield { .. }) {
+                        return Err(ScenarioError::NoShield { action, entity: attacker.clone() });
+                    }
+                    if state.rings.is_empty() {
+                        return Err(ScenarioError::NoSuchRing { action, entity: attacker.clone(), ring: 0 });
+                    }
+                    Ok((i, j))
+                }
+                Action::PowerUpRing { entity, ring } => {
+                    let i = find(entity)?;
+                    if *ring >= self.entities[i].rings.len() {
+                        return Err(ScenarioError::NoSuchRing { action, entity: entity.clone(), ring: *ring });
+                    }
+                    Ok((i, i))
+                }
+            }
+        }).collect()
+    }
+}
+
+impl EntityState {
+    /// A fresh `Entity` with this state.
+    pub fn to_entity<'r>(&self) -> Entity<'r> {
+        Entity {
+            hp: GhostCell::new(self.hp),
+            rings: GhostCell::new(self.rings.iter().map(|&power| GhostCell::new(Ring { power })).collect()),
+            hand: GhostCell::new(match self.hand {
+                HandState::Shield { durability } => Hand::Shield { durability: GhostCell::new(durability) },
+                HandState::Sword { sharpness } => Hand::Sword { sharpness },
+            }),
+            energy: GhostCell::new(self.energy),
+        }
+    }
+    /// The state of `entity`, under `name`.
+    pub fn of<'r>(name: String, entity: &Entity<'r>, token: &GhostToken<'r>) -> Self {
+        EntityState {
+            name,
+            hp: *entity.hp.borrow(token),
+            energy: *entity.energy.borrow(token),
+            rings: entity.rings.borrow(token).iter().map(|ring| ring.borrow(token).power).collect(),
+            hand: match entity.hand.borrow(token) {
+                Hand::Shield { durability } => HandState::Shield { durability: *durability.borrow(token) },
+                Hand::Sword { sharpness } => HandState::Sword { sharpness: *sharpness },
+            },
+        }
+    }
+}
+
+/// A `HistoryVec` of entities snapshots each one as its state, without a
+/// name.
+impl<'r> Snapshot<'r> for Entity<'r> {
+    type State = EntityState;
+    fn snapshot(&self, token: &GhostToken<'r>) -> EntityState {
+        EntityState::of(String::new(), self, token)
+    }
+    fn restore(&self, state: EntityState, token: &mut GhostToken<'r>) {
+        let Entity { hp, rings, hand, energy } = state.to_entity();
+        *self.hp.borrow_mut(token) = hp.into_inner();
+        *self.rings.borrow_mut(token) = rings.into_inner();
+        *self.hand.borrow_mut(token) = hand.into_inner();
+        *self.energy.borrow_mut(token) = energy.into_inner();
+    }
+}
+
+impl fmt::Display for EntityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: hp {}, energy {}, rings {:?}, ", self.name, self.hp, self.energy, self.rings)?;
+        match self.hand {
+            HandState::Shield { durability } => write!(f, "shield (durability {durability})"),
+            HandState::Sword { sharpness } => write!(f, "sword (sharpness {sharpness})"),
+        }
+    }
+}
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Attack { attacker, defender, damage, defender_hp, attacker_energy, defender_energy } => write!(
+                f,
+                "{attacker} attacks {defender} for {damage} damage: {defender} hp {defender_hp}, \
+                 {attacker} energy {attacker_energy}, {defender} energy {defender_energy}",
+            ),
+            Event::ComplexAttack { attacker, defender, ring_power, durability } => write!(
+                f,
+                "{attacker} makes a complex attack on {defender}: ring 0 power {ring_power}, shield durability {durability}",
+            ),
+            Event::PowerUpRing { entity, ring, power } => write!(f, "{entity} powers up ring {ring}: power {power}"),
+        }
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "events:")?;
+        for (i, event) in self.events.iter().enumerate() {
+            writeln!(f, "  {i}: {event}")?;
+        }
+        writeln!(f, "final state:")?;
+        for entity in &self.entities {
+            writeln!(f, "  {entity}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! Kani harnesses for the layout side of the demo's casts, to be run with
+//! `cargo kani -p demo-game`.
+//!
+//! `entity_cast_group_mut` and `token_as_entity1_mut` go beyond what the
+//! paper's Coq proof covers, and what makes them sound is a type-level
+//! argument: that no two live `&mut` can reach one cell because the brands
+//! they hand out are fresh, or stand for a token that stays shut away while
+//! they are used. Kani checks a program with its lifetimes erased, so it
+//! can't see a caller picking two brands to be the same, which is how these
+//! casts were unsound while their brands were return lifetimes. That argument
+//! is in their signatures and doc comments, and only the compiler checks it.
+//!
+//! The harnesses here only check what is left at runtime, for symbolic
+//! values: the same addresses, the same field offsets, and writes seen
+//! through either side. They are no evidence about aliasing between brands.
+//!
+//! **Unverified:** no `cargo kani` run has checked these harnesses, and CI
+//! doesn't build them either, since nothing sets `cfg(kani)`.
+use core::{mem, ptr};
+
+use demo::{make_guard, GhostCell, GhostToken};
+
+use crate::{entity_cast_group_mut, token_as_entity1_mut, Entity, EntityAccess, OpenEntity};
+
+/// `entity_cast_group_mut` returns the same entity under another brand, so
+/// writes through either are seen by the other.
+#[kani::proof]
+fn entity_cast_group_mut_is_identity() {
+    make_guard!(content);
+    let mut content = content;
+    make_guard!(union);
+    let mut union = union;
+    let mut entity = Entity::new();
+    let address = &entity as *const Entity<'_> as usize;
+    let (hp, energy) = (kani::any(), kani::any());
+    {
+        let in_union = GhostCell::new(&mut content);
+        let cast = entity_cast_group_mut(&in_union, &mut entity);
+        assert!(cast as *const Entity<'_> as usize == address);
+        *cast.hp.borrow_mut(&mut union) = hp;
+        *cast.energy.borrow_mut(&mut union) = energy;
+    }
+    assert!(*entity.hp.borrow(&content) == hp);
+    assert!(*entity.energy.borrow(&content) == energy);
+}
+
+/// `token_as_entity1_mut` reads an `Entity` as an `OpenEntity` and a token as
+/// an `EntityAccess`, so the two structs need the same fields at the same
+/// offsets, and the access needs to be as free to conjure as the token.
+#[kani::proof]
+fn token_as_entity1_mut_layout() {
+    make_guard!(token);
+    let mut token = token;
+    let entity = Entity::new();
+    let hp = kani::any();
+    *entity.hp.borrow_mut(&mut token) = hp;
+    token_as_entity1_mut(&mut token, |access, open| {
+        let opened = open(&entity);
+        assert!(ptr::eq(opened as *const OpenEntity<'_, '_, '_, '_, '_, '_> as *const u8, &entity as *const Entity<'_> as *const u8));
+        assert!(mem::size_of::<OpenEntity<'_, '_, '_, '_, '_, '_>>() == mem::size_of::<Entity<'_>>());
+        assert!(mem::offset_of!(OpenEntity<'_, '_, '_, '_, '_, '_>, hp) == mem::offset_of!(Entity<'_>, hp));
+        assert!(mem::offset_of!(OpenEntity<'_, '_, '_, '_, '_, '_>, rings) == mem::offset_of!(Entity<'_>, rings));
+        assert!(mem::offset_of!(OpenEntity<'_, '_, '_, '_, '_, '_>, hand) == mem::offset_of!(Entity<'_>, hand));
+        assert!(mem::offset_of!(OpenEntity<'_, '_, '_, '_, '_, '_>, energy) == mem::offset_of!(Entity<'_>, energy));
+        assert!(mem::size_of::<EntityAccess<'_, '_, '_, '_, '_, '_>>() == mem::size_of::<GhostToken<'_>>());
+        assert!(*opened.hp.borrow(&access.hp) == hp);
+    });
+}
@@ -0,0 +1,206 @@
+//! Entity-component storage where every component gets its own group.
+//!
+//! `World<'id>` keeps each field of an `Entity` in its own column, so the
+//! `hp` of every entity is stored together, then every entity's `rings`, and
+//! so on. Outside a query the whole world belongs to one `GhostToken<'id>`.
+//! `World::open` splits that token per component, the same way
+//! `token_as_entity1_mut` splits an entity's token into an `EntityAccess`, so a
+//! system can mutate one column while reading the others:
+//!
+//! ```
+//! use demo::{make_guard, GhostCell};
+//! use demo_game::{Hand, Ring, World};
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let mut world = World::new();
+//! let knight = world.spawn(10, vec![Ring { power: 3 }], Hand::Sword { sharpness: 2 }, 4);
+//! let squire = world.spawn(5, vec![], Hand::Shield { durability: GhostCell::new(1) }, -1);
+//!
+//! // Regenerate: hp grows by the entity's energy plus the power of its rings.
+//! world.open(&mut token, |world, access| {
+//!     let energy = world.energy.borrow(&access.energy);
+//!     let rings = world.rings.borrow(&access.rings);
+//!     for ((hp, energy), rings) in world.hp.borrow_mut(&mut access.hp).iter_mut().zip(energy).zip(rings) {
+//!         let power: u32 = rings.iter().map(|ring| ring.borrow(&access.rings_content).power).sum();
+//!         *hp = hp.saturating_add_signed(*energy) + power;
+//!     }
+//! });
+//!
+//! assert_eq!(*world.hp.get(knight).unwrap().borrow(&token), 17);
+//! assert_eq!(*world.hp.get(squire).unwrap().borrow(&token), 4);
+//! ```
+use core::slice;
+
+use demo::{GhostCell, GhostToken};
+
+use crate::{EntityAccess, Hand, Ring};
+
+/// Identifies an entity by its row in every column of a `World`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct EntityId(usize);
+impl EntityId {
+    /// The row this entity occupies.
+    #[inline]
+    pub const fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A column of one component, with a cell per entity.
+#[repr(transparent)]
+pub struct Column<'id, C> {
+    cells: Vec<GhostCell<'id, C>>,
+}
+impl<'id, C> Column<'id, C> {
+    const fn new() -> Self {
+        Column { cells: Vec::new() }
+    }
+    /// The number of entities with a value in this column.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+    /// Whether the column is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+    /// The cell holding `entity`'s component.
+    #[inline]
+    pub fn get(&self, entity: EntityId) -> Option<&GhostCell<'id, C>> {
+        self.cells.get(entity.0)
+    }
+    /// Iterates over the cells of the column, in entity order.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, GhostCell<'id, C>> {
+        self.cells.iter()
+    }
+    /// Borrows the whole column immutably.
+    #[inline]
+    pub fn borrow<'a>(&'a self, _token: &'a GhostToken<'id>) -> &'a [C] {
+        demo::assert_same_layout!(GhostCell<'id, C>, C);
+        unsafe {
+            // `GhostCell<'id, C>` is a transparent wrapper around `C`, and the
+            // token being borrowed immutably means every cell in the column
+            // could be borrowed immutably at once anyway.
+            &*(self.cells.as_slice() as *const [GhostCell<'id, C>] as *const [C])
+        }
+    }
+    /// Borrows the whole column mutably.
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, _token: &'a mut GhostToken<'id>) -> &'a mut [C] {
+        demo::assert_same_layout!(GhostCell<'id, C>, C);
+        unsafe {
+            // As above, but every cell is distinct, so the exclusive access the
+            // token grants to each of them extends to all of them together.
+            // The cells are `UnsafeCell`s, so writing through a pointer derived
+            // from the shared borrow of the column is permitted.
+            slice::from_raw_parts_mut(self.cells.as_ptr() as *mut C, self.cells.len())
+        }
+    }
+}
+impl<'a, 'id, C> IntoIterator for &'a Column<'id, C> {
+    type Item = &'a GhostCell<'id, C>;
+    type IntoIter = slice::Iter<'a, GhostCell<'id, C>>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The columns of a `World`, with a separate brand for each component, like
+/// `OpenEntity` is to `Entity`.
+pub struct OpenWorld<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy> {
+    pub hp: Column<'hp, u32>,
+    pub rings: Column<'rings, Vec<GhostCell<'rings_content, Ring>>>,
+    pub hand: Column<'hand, Hand<'hand_content>>,
+    pub energy: Column<'energy, i32>,
+}
+
+/// Column-major storage for the components of many entities, all belonging
+/// to the group `'id`.
+///
+/// The columns can be read directly through `Deref`; to mutate one column
+/// while borrowing another, `open` the world.
+#[repr(transparent)]
+pub struct World<'id> {
+    columns: OpenWorld<'id, 'id, 'id, 'id, 'id, 'id>,
+}
+impl<'id> World<'id> {
+    /// Creates a world with no entities.
+    pub const fn new() -> Self {
+        World {
+            columns: OpenWorld {
+                hp: Column::new(),
+                rings: Column::new(),
+                hand: Column::new(),
+                energy: Column::new(),
+            },
+        }
+    }
+    /// The number of entities in the world.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.columns.hp.len()
+    }
+    /// Whether the world has no entities.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.columns.hp.is_empty()
+    }
+    /// Adds an entity, with a value for every component.
+    pub fn spawn(&mut self, hp: u32, rings: Vec<Ring>, hand: Hand<'id>, energy: i32) -> EntityId {
+        let id = EntityId(self.len());
+        let columns = &mut self.columns;
+        columns.hp.cells.push(GhostCell::new(hp));
+        columns.rings.cells.push(GhostCell::new(rings.into_iter().map(GhostCell::new).collect()));
+        columns.hand.cells.push(GhostCell::new(hand));
+        columns.energy.cells.push(GhostCell::new(energy));
+        id
+    }
+    /// Iterates over the ids of every entity in the world.
+    pub fn entities(&self) -> impl ExactSizeIterator<Item = EntityId> + use<> {
+        (0..self.len()).map(EntityId)
+    }
+    /// Splits the world's token into one token per component, and runs `query`
+    /// with the columns branded to match.
+    ///
+    /// As in `token_as_entity1_mut`, the component brands are introduced by
+    /// the higher-ranked closure, so they are fresh by parametricity and
+    /// cannot escape the query.
+    pub fn open<R>(
+        &self,
+        token: &mut GhostToken<'id>,
+        query: impl for<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy> FnOnce(
+            &OpenWorld<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy>,
+            &mut EntityAccess<'hp, 'rings, 'rings_content, 'hand, 'hand_content, 'energy>,
+        ) -> R,
+    ) -> R {
+        demo::assert_same_layout!(GhostToken<'id>, EntityAccess<'id, 'id, 'id, 'id, 'id, 'id>);
+        let (columns, access) = unsafe {
+            // `OpenWorld` differs from `World`'s field only in its lifetimes,
+            // so the layouts agree, and the token and `EntityAccess` are both
+            // zero-sized. The token is exclusively borrowed for the duration
+            // of the query, so no cell of `'id` is otherwise reachable, and
+            // the closure cannot tell its fresh brands apart from `'id`.
+            (
+                &*(&self.columns as *const OpenWorld<'id, 'id, 'id, 'id, 'id, 'id>).cast::<OpenWorld<'_, '_, '_, '_, '_, '_>>(),
+                &mut *(token as *mut GhostToken<'id>).cast::<EntityAccess<'_, '_, '_, '_, '_, '_>>(),
+            )
+        };
+        query(columns, access)
+    }
+}
+impl<'id> Default for World<'id> {
+    fn default() -> Self {
+        World::new()
+    }
+}
+impl<'id> core::ops::Deref for World<'id> {
+    type Target = OpenWorld<'id, 'id, 'id, 'id, 'id, 'id>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.columns
+    }
+}
@@ -0,0 +1,58 @@
+//! `Trace for Entity`, drawn with `ghost_dot` the way `invoke_demo` keeps
+//! entities: the entity in `entity_a_group`, and its fields in
+//! `entity_a_content_group`.
+use demo::ghost_dot::{Dot, Trace};
+use demo::{make_guard, GhostCell};
+use demo_game::{Entity, Hand, Ring};
+
+#[test]
+fn entity_and_content_groups() {
+    make_guard!(entity_a_group);
+    make_guard!(entity_a_content_group);
+    let entity_a = GhostCell::new(Entity {
+        hp: GhostCell::new(100),
+        rings: GhostCell::new(vec![GhostCell::new(Ring { power: 1 }), GhostCell::new(Ring { power: 2 })]),
+        hand: GhostCell::new(Hand::Shield { durability: GhostCell::new(5) }),
+        energy: GhostCell::new(100),
+    });
+    let entity = entity_a.borrow(&entity_a_group);
+    let fields: [&dyn Trace<'_>; 4] = [&entity.hp, &entity.rings, &entity.hand, &entity.energy];
+
+    // Traced alone, the entity's group only draws the fields, dashed, since
+    // they can't be read without the content group's token.
+    let dot = Dot::new().group("entity_a_group", &entity_a_group, &[&entity_a]).finish();
+    assert!(dot.contains(r#"n0 [label="Entity", fillcolor="lightblue"];"#));
+    for (i, field) in ["u32", "Vec<GhostCell<Ring>>", "Hand", "i32"].into_iter().enumerate() {
+        assert!(dot.contains(&format!("n{} [label={:?}, style=dashed];", i + 1, field)));
+        assert!(dot.contains(&format!("n0 -> n{};", i + 1)));
+    }
+    assert!(!dot.contains("Ring\""));
+
+    // Tracing the content group next fills the same nodes in, and goes on to
+    // the rings and the shield's durability.
+    let dot = Dot::new()
+        .group("entity_a_group", &entity_a_group, &[&entity_a])
+        .group("entity_a_content_group", &entity_a_content_group, &fields)
+        .finish();
+    assert_eq!(dot, r#"digraph {
+    node [shape=box, style=filled, fillcolor=white];
+    g0 [label="entity_a_group", shape=note, fillcolor="lightblue"];
+    g1 [label="entity_a_content_group", shape=note, fillcolor="palegreen"];
+    n0 [label="Entity", fillcolor="lightblue"];
+    n1 [label="u32", fillcolor="palegreen"];
+    n2 [label="Vec<GhostCell<Ring>>", fillcolor="palegreen"];
+    n3 [label="Hand", fillcolor="palegreen"];
+    n4 [label="i32", fillcolor="palegreen"];
+    n5 [label="Ring", fillcolor="palegreen"];
+    n6 [label="Ring", fillcolor="palegreen"];
+    n7 [label="u32", fillcolor="palegreen"];
+    n0 -> n1;
+    n0 -> n2;
+    n0 -> n3;
+    n0 -> n4;
+    n2 -> n5;
+    n2 -> n6;
+    n3 -> n7;
+}
+"#);
+}
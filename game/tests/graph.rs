@@ -0,0 +1,100 @@
+//! Entities sharing `Ring`s, saved and loaded with `demo::graph`.
+//!
+//! An `Entity` owns its rings, so a ring two entities share is modelled as a
+//! node of its own, which both wearers have an edge to. The wearers are
+//! built from, and checked against, the game's own `Entity`s.
+#![cfg(feature = "graph")]
+use demo::graph::{self, Arena, GraphNode, SavedGraph};
+use demo::{make_guard, GhostCell, GhostToken};
+use demo_game::{Entity, Ring};
+
+type Link<'arena, 'id> = &'arena GhostCell<'id, Piece<'arena, 'id>>;
+
+enum Piece<'arena, 'id> {
+    Wearer { hp: u32, energy: i32, rings: Vec<Link<'arena, 'id>> },
+    Ring(Ring),
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+enum PieceData {
+    Wearer { hp: u32, energy: i32 },
+    Ring { power: u32 },
+}
+impl<'arena, 'id> GraphNode<'arena, 'id> for Piece<'arena, 'id> {
+    type Data = PieceData;
+    fn data(&self) -> PieceData {
+        match self {
+            Piece::Wearer { hp, energy, .. } => PieceData::Wearer { hp: *hp, energy: *energy },
+            Piece::Ring(ring) => PieceData::Ring { power: ring.power },
+        }
+    }
+    fn edges(&self) -> Vec<Option<Link<'arena, 'id>>> {
+        match self {
+            Piece::Wearer { rings, .. } => rings.iter().copied().map(Some).collect(),
+            Piece::Ring(_) => Vec::new(),
+        }
+    }
+    fn from_data(data: PieceData) -> Self {
+        match data {
+            PieceData::Wearer { hp, energy } => Piece::Wearer { hp, energy, rings: Vec::new() },
+            PieceData::Ring { power } => Piece::Ring(Ring { power }),
+        }
+    }
+    fn set_edges(&mut self, edges: Vec<Option<Link<'arena, 'id>>>) {
+        if let Piece::Wearer { rings, .. } = self {
+            *rings = edges.into_iter().flatten().collect();
+        }
+    }
+}
+
+/// A wearer with an entity's hp and energy, and the rings given.
+fn wearer<'arena, 'id, 'r>(entity: &Entity<'r>, token: &GhostToken<'r>, rings: Vec<Link<'arena, 'id>>) -> Piece<'arena, 'id> {
+    Piece::Wearer { hp: *entity.hp.borrow(token), energy: *entity.energy.borrow(token), rings }
+}
+
+/// The power of every ring a wearer has on, as `calculate_damage` adds it up.
+fn power<'arena, 'id>(piece: Link<'arena, 'id>, token: &GhostToken<'id>) -> u32 {
+    let Piece::Wearer { rings, .. } = piece.borrow(token) else { panic!("not a wearer") };
+    rings.iter().map(|ring| match ring.borrow(token) {
+        Piece::Ring(ring) => ring.power,
+        Piece::Wearer { .. } => panic!("not a ring"),
+    }).sum()
+}
+
+#[test]
+fn shared_rings() {
+    let json = {
+        make_guard!(content);
+        let mut content = content;
+        let (knight, squire) = (Entity::new(), Entity::new());
+        knight.damage(30, &mut content);
+        squire.use_energy(40, &mut content);
+
+        make_guard!(token);
+        let arena = Arena::new();
+        let shared: Link<'_, '_> = arena.alloc(GhostCell::new(Piece::Ring(Ring { power: 3 })));
+        let own = arena.alloc(GhostCell::new(Piece::Ring(knight.rings.borrow(&content)[0].borrow(&content).clone())));
+        let knight = arena.alloc(GhostCell::new(wearer(&knight, &content, vec![own, shared])));
+        let squire = arena.alloc(GhostCell::new(wearer(&squire, &content, vec![shared])));
+        assert_eq!((power(knight, &token), power(squire, &token)), (4, 3));
+        graph::save(&[&*knight, &*squire], &token).to_json().unwrap()
+    };
+    // The shared ring is saved once, and both wearers point at it.
+    assert_eq!(json, concat!(
+        r#"{"nodes":[{"data":{"Wearer":{"hp":70,"energy":100}},"edges":[2,3]},"#,
+        r#"{"data":{"Wearer":{"hp":100,"energy":60}},"edges":[3]},"#,
+        r#"{"data":{"Ring":{"power":1}},"edges":[]},{"data":{"Ring":{"power":3}},"edges":[]}],"roots":[0,1]}"#,
+    ));
+
+    make_guard!(token);
+    let mut token = token;
+    let arena = Arena::new();
+    let roots = graph::load(SavedGraph::from_json(&json).unwrap(), &arena, &mut token).unwrap();
+    let (knight, squire) = (roots[0], roots[1]);
+    let Piece::Wearer { rings, .. } = squire.borrow(&token) else { panic!("not a wearer") };
+    let shared = rings[0];
+    // Powering up the ring on the squire powers it up on the knight too.
+    if let Piece::Ring(ring) = shared.borrow_mut(&mut token) {
+        ring.power += 10;
+    }
+    assert_eq!((power(knight, &token), power(squire, &token)), (14, 13));
+}
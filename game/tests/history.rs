@@ -0,0 +1,43 @@
+//! Undoing the game's actions on entities kept in a `HistoryVec`.
+use demo::{make_guard, HistoryVec};
+use demo_game::{attack, complex_attack, Entity};
+use demo_game::scenario::{EntityState, HandState};
+
+fn state(name: &str, hp: u32, energy: i32, rings: Vec<u32>, hand: HandState) -> EntityState {
+    EntityState { name: name.into(), hp, energy, rings, hand }
+}
+
+fn states<'r>(entities: &HistoryVec<'r, Entity<'r>>) -> Vec<EntityState> {
+    let (entities, token) = entities.values();
+    entities.iter().map(|entity| EntityState::of(String::new(), entity, token)).collect()
+}
+
+#[test]
+fn undo_attack() {
+    let knight = state("", 100, 100, vec![1, 2], HandState::Shield { durability: 5 });
+    let goblin = state("", 30, 20, vec![], HandState::Sword { sharpness: 3 });
+    make_guard!(token);
+    let mut entities = HistoryVec::new(token, vec![knight.to_entity(), goblin.to_entity()]);
+    let start = states(&entities);
+    assert_eq!(start, [knight, goblin]);
+
+    let (values, token) = entities.split();
+    attack(&values[0], &values[1], token);
+    let attacked = entities.commit();
+    let after_attack = states(&entities);
+    assert_eq!(after_attack[1].hp, 17);
+
+    // Changes the knight's rings and the cell inside its shield.
+    let (values, token) = entities.split();
+    complex_attack(&values[0], &values[1], token);
+    let after_complex = states(&entities);
+    assert_ne!(after_complex, after_attack);
+    assert!(entities.undo());
+    assert_eq!(states(&entities), after_attack);
+
+    assert!(entities.undo());
+    assert_eq!(states(&entities), start);
+    assert!(!entities.undo());
+    assert!(entities.redo());
+    assert_eq!((states(&entities), entities.version()), (after_attack, attacked));
+}
@@ -0,0 +1,142 @@
+use demo::{make_guard, GhostCell};
+use demo_game::replay::{CellId, EntityCell, Recordable, RecordingToken, ReplayError, ReplayLog};
+use demo_game::scenario::{Action, EntityState, HandState, Scenario};
+use demo_game::{attack, complex_attack, Entity, Hand, Ring};
+
+fn entities<'r>() -> Vec<Entity<'r>> {
+    let rogue = Entity {
+        hp: GhostCell::new(40),
+        rings: GhostCell::new(vec![]),
+        hand: GhostCell::new(Hand::Sword { sharpness: 2 }),
+        energy: GhostCell::new(-3),
+    };
+    vec![Entity::new(), rogue]
+}
+
+fn states<'r>(entities: &[Entity<'r>], token: &demo::GhostToken<'r>) -> Vec<EntityState> {
+    entities.iter().map(|entity| EntityState::of(String::new(), entity, token)).collect()
+}
+
+#[test]
+fn same_as_unrecorded() {
+    make_guard!(plain);
+    let mut plain = plain;
+    let expected = entities();
+    attack(&expected[0], &expected[1], &mut plain);
+    attack(&expected[1], &expected[0], &mut plain);
+    complex_attack(&expected[0], &expected[1], &mut plain);
+    expected[0].power_up_ring(0, &mut plain);
+
+    make_guard!(token);
+    let recorded = entities();
+    let mut token = RecordingToken::new(token, &recorded);
+    token.attack(0, 1);
+    token.attack(1, 0);
+    token.complex_attack(0, 1);
+    token.power_up_ring(0, 0);
+    assert_eq!(token.entries(), 9);
+    assert_eq!(states(&recorded, token.token()), states(&expected, &plain));
+
+    let (_, log) = token.finish();
+    assert_eq!(ReplayLog::from_bytes(log.as_bytes().to_vec()).replay(), Ok(9));
+}
+
+#[test]
+fn set() {
+    make_guard!(token);
+    let entities = entities();
+    let mut token = RecordingToken::new(token, &entities);
+    assert_eq!(token.set(EntityCell::energy(1), -40), Some(-3));
+    assert_eq!(token.set(EntityCell::ring(0, 0), Ring { power: 9 }), Some(Ring { power: 1 }));
+    // no such cells: nothing is recorded
+    assert_eq!(token.set(EntityCell::hp(2), 0), None);
+    assert_eq!(token.update(EntityCell::durability(1), |durability| *durability += 1), None);
+    assert_eq!(token.entries(), 2);
+    assert_eq!(token.finish().1.replay(), Ok(2));
+}
+
+#[test]
+fn errors() {
+    make_guard!(token);
+    let entities = entities();
+    let mut token = RecordingToken::new(token, &entities);
+    token.attack(0, 1);
+    let (_, log) = token.finish();
+    let bytes = log.as_bytes();
+
+    let mut diverged = bytes.to_vec();
+    // the first change, to the attacker's energy, is recorded from 100
+    let at = diverged.windows(3).position(|window| window == [1, 0, 1]).unwrap();
+    let mut old = &diverged[at + 3..];
+    assert_eq!(i32::decode(&mut old), Some(100));
+    diverged[at + 3] ^= 2;
+    let err = ReplayLog::from_bytes(diverged).replay().unwrap_err();
+    assert_eq!(err, ReplayError::Diverged { entry: 0, cell: CellId::Energy(0) });
+
+    let mut wrong_end = bytes.to_vec();
+    *wrong_end.last_mut().unwrap() ^= 2;
+    assert_eq!(ReplayLog::from_bytes(wrong_end).replay(), Err(ReplayError::FinalState));
+
+    let truncated = bytes[..bytes.len() - 1].to_vec();
+    assert_eq!(ReplayLog::from_bytes(truncated).replay(), Err(ReplayError::FinalState));
+    let err = ReplayLog::from_bytes(bytes[..at + 4].to_vec()).replay().unwrap_err();
+    assert_eq!(err, ReplayError::Malformed { offset: at });
+}
+
+#[test]
+fn encoding() {
+    let mut out = Vec::new();
+    for n in [0, 1, -1, 63, -64, 64, i32::MAX, i32::MIN] {
+        n.encode(&mut out);
+    }
+    u64::MAX.encode(&mut out);
+    CellId::Ring(3, 300).encode(&mut out);
+    assert_eq!(out[..5], [0, 2, 1, 126, 127]);
+
+    let mut input = &out[..];
+    for n in [0, 1, -1, 63, -64, 64, i32::MAX, i32::MIN] {
+        assert_eq!(i32::decode(&mut input), Some(n));
+    }
+    assert_eq!(u64::decode(&mut input), Some(u64::MAX));
+    assert_eq!(CellId::decode(&mut input), Some(CellId::Ring(3, 300)));
+    assert!(input.is_empty());
+    assert_eq!(u32::decode(&mut &[0x80][..]), None);
+}
+
+#[test]
+fn scenario() {
+    let knight = EntityState { name: "knight".into(), hp: 50, energy: 12, rings: vec![1, 2], hand: HandState::Shield { durability: 1 } };
+    let orc = EntityState { name: "orc".into(), hp: 80, energy: 30, rings: vec![], hand: HandState::Sword { sharpness: 4 } };
+    let scenario = Scenario {
+        entities: vec![knight, orc],
+        actions: vec![
+            Action::Attack { attacker: "orc".into(), defender: "knight".into() },
+            Action::ComplexAttack { attacker: "knight".into(), defender: "orc".into() },
+            Action::PowerUpRing { entity: "knight".into(), ring: 1 },
+        ],
+    };
+    assert_eq!(scenario.record().unwrap().replay(), Ok(6));
+}
+
+/// Recording a scenario applies the same rules as running it, down to the
+/// values that saturate.
+#[test]
+fn record_matches_run() {
+    let titan = EntityState { name: "titan".into(), hp: 5, energy: i32::MAX, rings: vec![u32::MAX, 3], hand: HandState::Shield { durability: u32::MAX - 1 } };
+    let husk = EntityState { name: "husk".into(), hp: u32::MAX, energy: i32::MIN, rings: vec![7], hand: HandState::Sword { sharpness: 0 } };
+    let scenario = Scenario {
+        entities: vec![titan, husk],
+        actions: vec![
+            Action::Attack { attacker: "husk".into(), defender: "titan".into() },
+            Action::ComplexAttack { attacker: "titan".into(), defender: "husk".into() },
+            Action::PowerUpRing { entity: "titan".into(), ring: 1 },
+            Action::Attack { attacker: "titan".into(), defender: "husk".into() },
+            Action::PowerUpRing { entity: "husk".into(), ring: 0 },
+        ],
+    };
+    let mut ran = scenario.run().unwrap().entities;
+    for entity in &mut ran {
+        entity.name.clear();
+    }
+    assert_eq!(scenario.record().unwrap().final_state(), Ok(ran));
+}
@@ -0,0 +1,143 @@
+use demo_game::scenario::{Action, EntityState, Event, HandState, Scenario, ScenarioError};
+
+fn entity(name: &str, rings: Vec<u32>, hand: HandState) -> EntityState {
+    EntityState { name: name.into(), hp: 100, energy: 100, rings, hand }
+}
+
+fn attack(attacker: &str, defender: &str) -> Action {
+    Action::Attack { attacker: attacker.into(), defender: defender.into() }
+}
+
+#[test]
+fn run() {
+    let scenario = Scenario {
+        entities: vec![
+            entity("knight", vec![2], HandState::Shield { durability: 5 }),
+            entity("rogue", vec![], HandState::Sword { sharpness: 1 }),
+        ],
+        actions: vec![
+            Action::ComplexAttack { attacker: "knight".into(), defender: "rogue".into() },
+            attack("knight", "rogue"),
+            Action::PowerUpRing { entity: "knight".into(), ring: 0 },
+        ],
+    };
+    let report = scenario.run().unwrap();
+    assert_eq!(report.events[0], Event::ComplexAttack {
+        attacker: "knight".into(),
+        defender: "rogue".into(),
+        ring_power: 27,
+        durability: 7,
+    });
+    assert_eq!(report.events[1], Event::Attack {
+        attacker: "knight".into(),
+        defender: "rogue".into(),
+        damage: 37,
+        defender_hp: 63,
+        attacker_energy: 82,
+        defender_energy: 91,
+    });
+    assert_eq!(report.events[2], Event::PowerUpRing { entity: "knight".into(), ring: 0, power: 47 });
+    assert_eq!(report.entities[0].rings, [47]);
+    assert_eq!(report.entities[0].hand, HandState::Shield { durability: 7 });
+    assert_eq!(report.entities[1].hp, 63);
+    // a fresh group every time
+    assert_eq!(scenario.run().unwrap(), report);
+}
+
+#[test]
+fn errors() {
+    let knight = || entity("knight", vec![], HandState::Sword { sharpness: 1 });
+    let scenario = |entities, actions| Scenario { entities, actions };
+
+    let err = scenario(vec![knight(), knight()], vec![]).run().unwrap_err();
+    assert_eq!(err, ScenarioError::DuplicateEntity("knight".into()));
+
+    // checked before anything runs
+    let err = scenario(vec![knight()], vec![attack("knight", "knight"), attack("knight", "rogue")]).run().unwrap_err();
+    assert_eq!(err.to_string(), "action 1: no entity named `rogue`");
+
+    let complex = Action::ComplexAttack { attacker: "knight".into(), defender: "knight".into() };
+    let err = scenario(vec![knight()], vec![complex]).run().unwrap_err();
+    assert_eq!(err, ScenarioError::NoShield { action: 0, entity: "knight".into() });
+
+    let power_up = Action::PowerUpRing { entity: "knight".into(), ring: 0 };
+    let err = scenario(vec![knight()], vec![power_up]).run().unwrap_err();
+    assert_eq!(err, ScenarioError::NoSuchRing { action: 0, entity: "knight".into(), ring: 0 });
+}
+
+/// Values at the ends of their types' ranges saturate instead of
+/// overflowing.
+#[test]
+fn extreme_values() {
+    let titan = EntityState {
+        name: "titan".into(),
+        hp: u32::MAX,
+        energy: i32::MAX,
+        rings: vec![u32::MAX, u32::MAX],
+        hand: HandState::Shield { durability: u32::MAX },
+    };
+    let husk = EntityState {
+        name: "husk".into(),
+        hp: u32::MAX,
+        energy: i32::MIN,
+        rings: vec![],
+        hand: HandState::Sword { sharpness: u32::MAX },
+    };
+    let scenario = Scenario {
+        entities: vec![titan, husk],
+        actions: vec![
+            Action::PowerUpRing { entity: "titan".into(), ring: 0 },
+            Action::ComplexAttack { attacker: "titan".into(), defender: "husk".into() },
+            attack("husk", "husk"),
+            attack("titan", "husk"),
+        ],
+    };
+    let report = scenario.run().unwrap();
+    assert_eq!(report.events[1], Event::ComplexAttack {
+        attacker: "titan".into(),
+        defender: "husk".into(),
+        ring_power: u32::MAX,
+        durability: u32::MAX,
+    });
+    assert_eq!(report.events[2], Event::Attack {
+        attacker: "husk".into(),
+        defender: "husk".into(),
+        damage: 10,
+        defender_hp: u32::MAX - 10,
+        attacker_energy: i32::MIN,
+        defender_energy: i32::MIN,
+    });
+    assert_eq!((report.entities[0].energy, report.entities[0].rings.as_slice()), (0, &[u32::MAX; 2][..]));
+    assert_eq!((report.entities[1].hp, report.entities[1].energy), (0, i32::MIN));
+}
+
+#[cfg(feature = "sim")]
+#[test]
+fn duel() {
+    let scenario = Scenario::from_toml(include_str!("../scenarios/duel.toml")).unwrap();
+    let report = scenario.run().unwrap();
+    assert_eq!(report.events.len(), 5);
+    assert_eq!(report.entities[0], EntityState {
+        name: "knight".into(),
+        hp: 95,
+        energy: 62,
+        rings: vec![24, 26],
+        hand: HandState::Shield { durability: 7 },
+    });
+    assert_eq!(report.entities[1].hp, 0);
+}
+
+#[cfg(feature = "sim")]
+#[test]
+fn json() {
+    let scenario = Scenario::from_json(r#"{
+        "entities": [
+            { "name": "knight", "hp": 100, "energy": 100, "rings": [2], "hand": { "kind": "shield", "durability": 5 } },
+            { "name": "rogue", "hp": 100, "energy": 100, "hand": { "kind": "sword", "sharpness": 1 } }
+        ],
+        "actions": [{ "action": "attack", "attacker": "rogue", "defender": "knight" }]
+    }"#).unwrap();
+    assert!(scenario.entities[1].rings.is_empty());
+    assert_eq!(scenario.actions, [attack("rogue", "knight")]);
+    assert_eq!(scenario.run().unwrap().entities[0].hp, 95);
+}
@@ -0,0 +1,37 @@
+//! `Entity` through JSON and back, behind the `serde` feature.
+#![cfg(feature = "serde")]
+
+use demo::{make_guard, serialize::CellSeed, GhostCell, WithTokenExt};
+use demo_game::{Entity, Hand, Ring};
+use serde::de::DeserializeSeed;
+
+fn knight<'content>() -> Entity<'content> {
+    Entity {
+        hp: GhostCell::new(80),
+        rings: GhostCell::new(vec![GhostCell::new(Ring { power: 3 }), GhostCell::new(Ring { power: 7 })]),
+        hand: GhostCell::new(Hand::Shield { durability: GhostCell::new(5) }),
+        energy: GhostCell::new(-2),
+    }
+}
+
+#[test]
+fn entity_round_trip() {
+    make_guard!(token);
+    let entity = knight();
+    let json = serde_json::to_string(&entity.with(&token)).unwrap();
+    assert_eq!(json, r#"{"hp":80,"rings":[{"power":3},{"power":7}],"hand":{"Shield":{"durability":5}},"energy":-2}"#);
+
+    // into a fresh brand, for the entity's cell and for its content
+    make_guard!(group);
+    make_guard!(content);
+    let mut content = content;
+    let mut json_in = serde_json::Deserializer::from_str(&json);
+    let loaded = CellSeed::<Entity<'_>>::new(&group).deserialize(&mut json_in).unwrap();
+    let loaded = loaded.borrow(&group);
+    assert!(loaded.with(&content) == knight().with(&content));
+    assert_eq!(serde_json::to_string(&loaded.with(&content)).unwrap(), json);
+
+    // the entity's cells belong to the new content group
+    loaded.damage(1, &mut content);
+    assert!(loaded.with(&content) != knight().with(&content));
+}
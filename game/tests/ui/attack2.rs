@@ -0,0 +1,22 @@
+// `ring_ref` points into the rings list, which belongs to the same group as
+// everything `d.damage` might modify.
+use demo::GhostToken;
+use demo_game::Entity;
+
+fn attack2<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp = &a.hp;
+    let ring_ref = &a.rings.borrow(&token)[0];
+
+    let damage = a.calculate_damage(d, token);
+    let a_energy_cost = a.calculate_attack_cost(d, token);
+    let d_energy_cost = d.calculate_defend_cost(a, token);
+    a.use_energy(a_energy_cost, token);
+    d.use_energy(d_energy_cost, token);
+    d.damage(damage, token);
+    println!("{:?}", hp.borrow(&token));
+    println!("{:?}", ring_ref.borrow(&token));
+}
+
+fn main() {
+    let _ = attack2;
+}
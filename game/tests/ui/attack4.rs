@@ -0,0 +1,25 @@
+// `ring_ref` is reached through `d.rings`, whose group `d.damage` borrows
+// mutably, so the reference can't be used afterwards.
+use demo::GhostToken;
+use demo_game::{Entity, Hand};
+
+fn attack4<'r>(a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp_ref = &d.hp;
+    let rings_list_ref = &d.rings;
+    let rand_n = a as *const _ as usize;
+    let ring_ref = &d.rings.borrow(&token)[rand_n];
+
+    let durability = match &*d.hand.borrow(&token) {
+        Hand::Shield { durability } => durability,
+        Hand::Sword { .. } => panic!("irrelevant to the demo :)"),
+    };
+    d.damage(10, token);
+    println!("{:?}", hp_ref.borrow(&token));
+    println!("{:?}", rings_list_ref.borrow(&token).len());
+    println!("{:?}", ring_ref.borrow(&token).power);
+    println!("{:?}", durability.borrow(&token));
+}
+
+fn main() {
+    let _ = attack4;
+}
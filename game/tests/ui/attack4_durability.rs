@@ -0,0 +1,21 @@
+// The same goes for `durability`, reached through `d.hand`.
+use demo::GhostToken;
+use demo_game::{Entity, Hand};
+
+fn attack4<'r>(_a: &Entity<'r>, d: &Entity<'r>, token: &mut GhostToken<'r>) {
+    let hp_ref = &d.hp;
+    let rings_list_ref = &d.rings;
+
+    let durability = match &*d.hand.borrow(&token) {
+        Hand::Shield { durability } => durability,
+        Hand::Sword { .. } => panic!("irrelevant to the demo :)"),
+    };
+    d.damage(10, token);
+    println!("{:?}", hp_ref.borrow(&token));
+    println!("{:?}", rings_list_ref.borrow(&token).len());
+    println!("{:?}", durability.borrow(&token));
+}
+
+fn main() {
+    let _ = attack4;
+}
@@ -0,0 +1,14 @@
+// `el_ref` borrows the list's group, so the group can't be mutably borrowed
+// to push to the list until `el_ref` is done.
+use demo::{make_guard, GhostCell};
+
+fn main() {
+    let my_list = vec![1, 2, 3, 4];
+    make_guard!(my_list_group);
+    let mut my_list_group = my_list_group;
+    let my_list = GhostCell::new(my_list);
+    let list_ref = &my_list;
+    let el_ref = &my_list.borrow(&my_list_group)[0];
+    list_ref.borrow_mut(&mut my_list_group).push(5);
+    println!("{:?}", el_ref)
+}
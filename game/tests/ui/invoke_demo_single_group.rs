@@ -0,0 +1,29 @@
+// Passing only one entity's content group to `attack` makes rust unify the
+// `'r` of both entities, so `entity_b_content_group` would have to be the
+// same group as `entity_a_content_group`.
+use demo::{make_guard, GhostCell};
+use demo_game::{attack, Entity};
+
+fn main() {
+    let entity_a = Entity::new();
+    make_guard!(entity_a_content_group);
+    let mut entity_a_content_group = entity_a_content_group;
+    make_guard!(entity_a_group);
+    let mut entity_a_group = entity_a_group;
+    let entity_a = GhostCell::new(entity_a);
+
+    let entity_b = Entity::new();
+    make_guard!(entity_b_content_group);
+    let mut entity_b_content_group = entity_b_content_group;
+    make_guard!(entity_b_group);
+    let mut entity_b_group = entity_b_group;
+    let entity_b = GhostCell::new(entity_b);
+
+    entity_a.borrow_mut(&mut entity_a_group).damage(0, &mut entity_a_content_group);
+    entity_b.borrow_mut(&mut entity_b_group).damage(0, &mut entity_b_content_group);
+    attack(
+        entity_a.borrow(&entity_a_group),
+        entity_b.borrow(&entity_b_group),
+        &mut entity_b_content_group,
+    );
+}
@@ -0,0 +1,15 @@
+// `World::open` brands its component tokens in a higher-ranked closure, so
+// none of them can be used as a `GhostToken<'static>`, which would open
+// every `RtCell`.
+use demo::{make_guard, GhostCell};
+use demo_game::World;
+
+fn main() {
+    make_guard!(token);
+    let mut token = token;
+    let world = World::new();
+    let placeholder: GhostCell<'static, u32> = GhostCell::new(1);
+    world.open(&mut token, |_, access| {
+        *placeholder.borrow_mut(&mut access.hp) += 1;
+    });
+}
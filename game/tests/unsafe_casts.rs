@@ -0,0 +1,84 @@
+//! Runs the demo's unsafe casts, which open up an `Entity` or a `World`, in
+//! the patterns most likely to break them, so that the aliasing models can
+//! check them:
+//!
+//! ```text
+//! cargo +nightly miri test -p demo-game --test unsafe_casts
+//! MIRIFLAGS=-Zmiri-tree-borrows cargo +nightly miri test -p demo-game --test unsafe_casts
+//! ```
+//!
+//! The casts themselves are private, so the tests that call them directly are
+//! in the crate, and run under Miri with `--lib` instead of `--test`.
+//!
+//! Every test should pass under both Stacked and Tree Borrows. The comment on
+//! each one says what it relies on, so a failure points at the cast to blame.
+use demo::{make_guard, GhostCell, GhostToken};
+use demo_game::{
+    complex_attack, complex_example_main, invoke_demo, Entity, Hand, Ring, World,
+};
+
+fn durability<'r>(entity: &Entity<'r>, token: &GhostToken<'r>) -> u32 {
+    match entity.hand.borrow(token) {
+        Hand::Shield { durability } => *durability.borrow(token),
+        Hand::Sword { .. } => panic!("entities start with a shield"),
+    }
+}
+
+/// `invoke_demo` casts two entities into the union of their content
+/// groups, as the crate's own `entity_cast_group_mut_union` test does with
+/// checks.
+#[test]
+fn invoke_demo_runs() {
+    invoke_demo();
+}
+
+/// `token_as_entity1_mut` reinterprets `&Entity` as `&OpenEntity`, and the
+/// token as an `EntityAccess`. `complex_attack` holds a reference into the
+/// shield while mutating a ring through a different field token, which is
+/// exactly the overlap the split is meant to allow.
+#[test]
+fn token_as_entity1_mut_split() {
+    make_guard!(token);
+    let mut token = token;
+    let (a, d) = (Entity::new(), Entity::new());
+    complex_attack(&a, &d, &mut token);
+    // The ring gains a quarter of the wielder's 100 energy, the shield 2.
+    assert_eq!(a.rings.borrow(&token)[0].borrow(&token).power, 26);
+    assert_eq!(durability(&a, &token), 7);
+    assert_eq!(*a.energy.borrow(&token), 100);
+    assert_eq!(durability(&d, &token), 5);
+}
+
+/// `complex_example_main` borrows two entities out of a `Vec` in one group
+/// and attacks through their content group.
+#[test]
+fn complex_example_main_runs() {
+    complex_example_main();
+}
+
+/// `Column::borrow` and `borrow_mut` reinterpret `[GhostCell<C>]` as `[C]`.
+/// A system reads two columns while writing a third, then single cells of
+/// the written column are read through `get`.
+#[test]
+fn world_columns() {
+    make_guard!(token);
+    let mut token = token;
+    let mut world = World::new();
+    let a = world.spawn(10, vec![Ring { power: 3 }], Hand::Sword { sharpness: 2 }, 4);
+    let b = world.spawn(5, vec![], Hand::Shield { durability: GhostCell::new(1) }, -1);
+    let first = world.hp.get(a).unwrap();
+    world.open(&mut token, |world, access| {
+        let energy = world.energy.borrow(&access.energy);
+        let rings = world.rings.borrow(&access.rings);
+        let hp = world.hp.borrow_mut(&mut access.hp);
+        for ((hp, energy), rings) in hp.iter_mut().zip(energy).zip(rings) {
+            let power: u32 = rings.iter().map(|ring| ring.borrow(&access.rings_content).power).sum();
+            *hp = hp.saturating_add_signed(*energy) + power;
+        }
+        // A `&[u32]` taken after the write, alongside the energy still held.
+        assert_eq!(world.hp.borrow(&access.hp), [17, 4]);
+        assert_eq!(energy, [4, -1]);
+    });
+    assert_eq!(*first.borrow(&token), 17);
+    assert_eq!(*world.hp.get(b).unwrap().borrow(&token), 4);
+}
@@ -0,0 +1,226 @@
+//! A lock that owns a `GhostToken`, for holding it across `.await`s.
+//!
+//! Tasks that share a group can't each keep a `&mut GhostToken<'id>`, so
+//! `AsyncGhostLock` keeps the one token and lends it out a task at a time.
+//! `lock().await` resolves to a guard that derefs to the token, so it can be
+//! passed straight to `borrow_mut`, and that is `Send` for as long as it is
+//! held.
+//!
+//! Tasks get the token in the order they first polled `lock()`: an unlock
+//! hands it straight to the task at the front of the queue, so a late task
+//! can't barge in. Dropping a `Lock` future is always safe, whether it is
+//! still queued or has just been handed the token, which passes on to the
+//! next task in line.
+//!
+//! ```
+//! use demo::{make_guard, AsyncGhostLock, GhostCell};
+//! use futures::{executor::block_on, future::join};
+//!
+//! make_guard!(token);
+//! let lock = AsyncGhostLock::new(token);
+//! let hp = GhostCell::new(10);
+//!
+//! let heal = async {
+//!     let mut token = lock.lock().await;
+//!     *hp.borrow_mut(&mut token) += 5;
+//! };
+//! let hit = async {
+//!     let mut token = lock.lock().await;
+//!     *hp.borrow_mut(&mut token) -= 3;
+//! };
+//! block_on(join(heal, hit));
+//!
+//! let token = lock.into_inner();
+//! assert_eq!(*hp.borrow(&token), 12);
+//! ```
+use std::{
+    cell::UnsafeCell,
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+};
+
+use crate::GhostToken;
+
+/// Owns a `GhostToken` and lends it to one task at a time, first come first
+/// served.
+pub struct AsyncGhostLock<'id> {
+    token: UnsafeCell<GhostToken<'id>>,
+    state: Mutex<State>,
+}
+/// The token is only reached through a guard, and there is at most one.
+unsafe impl<'id> Sync for AsyncGhostLock<'id> {}
+
+#[derive(Default)]
+struct State {
+    locked: bool,
+    next_ticket: u64,
+    /// The tasks waiting for the token, oldest first.
+    waiters: VecDeque<(u64, Waker)>,
+    /// The task the token was handed to, which hasn't polled since.
+    granted: Option<u64>,
+}
+impl State {
+    /// Gives the token up, to the next task in line if there is one, and
+    /// returns that task's waker. It is woken once the state is unlocked, in
+    /// case waking it polls it straight away.
+    #[must_use]
+    fn release(&mut self) -> Option<Waker> {
+        match self.waiters.pop_front() {
+            Some((ticket, waker)) => {
+                self.granted = Some(ticket);
+                Some(waker)
+            }
+            None => {
+                self.locked = false;
+                None
+            }
+        }
+    }
+}
+
+impl<'id> AsyncGhostLock<'id> {
+    /// Creates an unlocked lock owning `token`.
+    pub fn new(token: GhostToken<'id>) -> Self {
+        AsyncGhostLock {
+            token: UnsafeCell::new(token),
+            state: Mutex::default(),
+        }
+    }
+    /// Returns the token.
+    pub fn into_inner(self) -> GhostToken<'id> {
+        self.token.into_inner()
+    }
+    /// Returns the token, which needs no locking since the lock is borrowed
+    /// mutably.
+    pub fn get_mut(&mut self) -> &mut GhostToken<'id> {
+        self.token.get_mut()
+    }
+    /// Waits for the token. The returned future is cancel-safe.
+    pub fn lock(&self) -> Lock<'_, 'id> {
+        Lock {
+            lock: self,
+            ticket: None,
+            done: false,
+        }
+    }
+    /// Takes the token if it is free and no task is waiting for it.
+    pub fn try_lock(&self) -> Option<AsyncGhostGuard<'_, 'id>> {
+        let mut state = self.state();
+        if state.locked {
+            return None;
+        }
+        state.locked = true;
+        Some(AsyncGhostGuard { lock: self })
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Every update to the state is finished before anything that could
+        // panic runs, so it is consistent even if poisoned.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+impl<'id> fmt::Debug for AsyncGhostLock<'id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("AsyncGhostLock")
+            .field("locked", &state.locked)
+            .field("waiting", &state.waiters.len())
+            .finish()
+    }
+}
+
+/// The future returned by `AsyncGhostLock::lock`.
+#[must_use = "futures do nothing unless polled"]
+pub struct Lock<'a, 'id> {
+    lock: &'a AsyncGhostLock<'id>,
+    /// Our place in the queue, once we have one.
+    ticket: Option<u64>,
+    done: bool,
+}
+impl<'a, 'id> Future for Lock<'a, 'id> {
+    type Output = AsyncGhostGuard<'a, 'id>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(!self.done, "`Lock` polled after completion");
+        let lock = self.lock;
+        let mut state = lock.state();
+        match self.ticket {
+            None if !state.locked => state.locked = true,
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                state.waiters.push_back((ticket, cx.waker().clone()));
+                drop(state);
+                self.ticket = Some(ticket);
+                return Poll::Pending;
+            }
+            Some(ticket) if state.granted == Some(ticket) => state.granted = None,
+            Some(ticket) => {
+                if let Some((_, waker)) = state.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+                    waker.clone_from(cx.waker());
+                }
+                return Poll::Pending;
+            }
+        }
+        drop(state);
+        self.done = true;
+        Poll::Ready(AsyncGhostGuard { lock })
+    }
+}
+impl<'a, 'id> Drop for Lock<'a, 'id> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket.filter(|_| !self.done) else {
+            return;
+        };
+        let mut state = self.lock.state();
+        if state.granted == Some(ticket) {
+            state.granted = None;
+            let next = state.release();
+            drop(state);
+            if let Some(next) = next {
+                next.wake();
+            }
+        } else {
+            state.waiters.retain(|(t, _)| *t != ticket);
+        }
+    }
+}
+impl<'a, 'id> fmt::Debug for Lock<'a, 'id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lock").field("ticket", &self.ticket).finish()
+    }
+}
+
+/// The token, lent out by an `AsyncGhostLock` until this is dropped.
+pub struct AsyncGhostGuard<'a, 'id> {
+    lock: &'a AsyncGhostLock<'id>,
+}
+impl<'a, 'id> Deref for AsyncGhostGuard<'a, 'id> {
+    type Target = GhostToken<'id>;
+    fn deref(&self) -> &GhostToken<'id> {
+        // We hold the lock, so nobody else is reaching the token.
+        unsafe { &*self.lock.token.get() }
+    }
+}
+impl<'a, 'id> DerefMut for AsyncGhostGuard<'a, 'id> {
+    fn deref_mut(&mut self) -> &mut GhostToken<'id> {
+        // We hold the lock, so nobody else is reaching the token.
+        unsafe { &mut *self.lock.token.get() }
+    }
+}
+impl<'a, 'id> Drop for AsyncGhostGuard<'a, 'id> {
+    fn drop(&mut self) {
+        let next = self.lock.state().release();
+        if let Some(next) = next {
+            next.wake();
+        }
+    }
+}
+impl<'a, 'id> fmt::Debug for AsyncGhostGuard<'a, 'id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AsyncGhostGuard")
+    }
+}
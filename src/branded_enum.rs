@@ -0,0 +1,133 @@
+//! Reaching into one variant of an enum of cells, under a brand for that
+//! variant.
+//!
+//! Matching on `hand.borrow(&token)` to get at a `Hand::Shield`'s durability
+//! cell keeps the token borrowed for as long as the cell is used, and the
+//! cell stays in the hand's content group, along with every other variant's
+//! cells. `#[derive(BrandedEnum)]` gives each variant whose payload is a
+//! single cell a brand of its own instead: `split_variants` splits the
+//! token for the enum's content into a `VariantToken` per such variant, and
+//! `GhostCell::project_variant` hands out the variant's cell under its
+//! token's brand.
+//!
+//! The projection still borrows the token of the cell holding the enum, as
+//! any reference into it must, since replacing the enum would drop the cell.
+//! But that can be a token of its own too, like `EntityAccess::hand`, and
+//! then nothing else is held up: the other fields, and the other variants'
+//! cells, can all be mutated while the projected cell is in use.
+//!
+//! The derive names the struct of variant tokens with
+//! `#[branded(variants = ...)]`. Its fields, and the variants' brands, are
+//! named after the variants in `snake_case`, and each variant gets a marker
+//! type, named after the enum and the variant, to pick it out with.
+//!
+//! ```
+//! use demo::{make_guard, BrandedEnum, GhostCell};
+//!
+//! #[derive(BrandedEnum)]
+//! #[branded(variants = HandVariants)]
+//! enum Hand<'content> {
+//!     Shield { durability: GhostCell<'content, u32> },
+//!     Sword { sharpness: u32 },
+//! }
+//!
+//! make_guard!(hand_token);
+//! make_guard!(content);
+//! let mut content = content;
+//! let hand = GhostCell::new(Hand::Shield { durability: GhostCell::new(5) });
+//!
+//! Hand::split_variants(&mut content, |variants| {
+//!     let armor = hand.project_variant(&hand_token, &variants.shield).unwrap();
+//!     *armor.borrow_mut(&mut variants.shield) += 2;
+//! });
+//! assert_eq!(Hand::VARIANTS[0].brand, Some("shield"));
+//! ```
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use crate::{GhostCell, GhostToken};
+
+type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// An enum whose variants can be projected out under brands of their own.
+/// Implemented by `#[derive(BrandedEnum)]`.
+///
+/// # Safety
+///
+/// `VARIANTS` must list the variants in the order they are declared, and
+/// `variant_index` must return the index of `self`'s variant in it.
+pub unsafe trait BrandedEnum {
+    /// The variants, in the order they are declared.
+    const VARIANTS: &'static [BrandedVariant];
+    /// The index of `self`'s variant in `VARIANTS`.
+    fn variant_index(&self) -> usize;
+}
+
+/// A variant of a `BrandedEnum`, and the brand its payload has once split.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BrandedVariant {
+    /// The variant's name.
+    pub name: &'static str,
+    /// The brand of the variant's cell, unless its payload isn't one cell.
+    pub brand: Option<&'static str>,
+}
+
+/// A variant of `E`, an enum with the brand `'content`, whose payload is a
+/// single cell. Implemented by `#[derive(BrandedEnum)]` on a marker type per
+/// variant.
+///
+/// # Safety
+///
+/// `cell` must return the payload of this variant and no other, so that no
+/// cell is reachable from two variants.
+pub unsafe trait Variant<'content, E> {
+    /// The type of the value in the cell.
+    type Field;
+    /// The variant's cell, if `value` is this variant.
+    fn cell(value: &E) -> Option<&GhostCell<'content, Self::Field>>;
+}
+
+/// The token for the cells of one variant `V` of the enums with the brand
+/// `'content`, split off the token for `'content` by the derived
+/// `split_variants`.
+#[repr(transparent)]
+pub struct VariantToken<'content, 'variant, V> {
+    token: GhostToken<'variant>,
+    _content: InvariantLifetime<'content>,
+    _variant: PhantomData<V>,
+}
+impl<'content, 'variant, V> Deref for VariantToken<'content, 'variant, V> {
+    type Target = GhostToken<'variant>;
+    #[inline]
+    fn deref(&self) -> &GhostToken<'variant> {
+        &self.token
+    }
+}
+impl<'content, 'variant, V> DerefMut for VariantToken<'content, 'variant, V> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut GhostToken<'variant> {
+        &mut self.token
+    }
+}
+
+impl<'id, E> GhostCell<'id, E> {
+    /// The cell of the variant `V`, if the enum in this cell is that variant,
+    /// under the brand of `variant`, the token split off for it.
+    #[inline]
+    pub fn project_variant<'a, 'content, 'variant, V: Variant<'content, E>>(
+        &'a self,
+        token: &'a GhostToken<'id>,
+        variant: &VariantToken<'content, 'variant, V>,
+    ) -> Option<&'a GhostCell<'variant, V::Field>> {
+        let _ = variant;
+        let cell = V::cell(self.borrow(token))?;
+        // Only the brand changes. `variant` was split off the token for
+        // `'content`, which stays borrowed mutably for as long as `'variant`
+        // lasts, so the variant's token is the only way into its cells; and
+        // the cell lives as long as `token` is borrowed, since the enum can't
+        // be replaced until then.
+        Some(unsafe { &*(cell as *const GhostCell<'content, V::Field>).cast::<GhostCell<'variant, V::Field>>() })
+    }
+}
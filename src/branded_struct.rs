@@ -0,0 +1,66 @@
+//! Describing a struct of cells and its open counterpart, for casts and
+//! tooling.
+//!
+//! `Entity<'content>` keeps all of its fields under one brand, and
+//! `OpenEntity` is the same struct with a brand per field, so that casting a
+//! `&Entity` to a `&OpenEntity` opens the group into a group per field. That
+//! cast is only sound while the two types are laid out the same, which
+//! `#[derive(BrandedStruct)]` checks when the crate is compiled: it names the
+//! open type with `#[branded(open = ...)]`, and fails to compile if the two
+//! differ in size, alignment, or the type or offset of any field, using
+//! `assert_same_layout!`.
+//!
+//! The open type's brands are named after the fields: a field's own cell is
+//! branded with the field's name, and any cells inside it with the field's
+//! name followed by `_content`, in the order the fields are declared.
+//!
+//! ```
+//! use demo::{BrandedStruct, GhostCell};
+//!
+//! #[derive(BrandedStruct)]
+//! #[branded(open = OpenPotion)]
+//! #[repr(C)]
+//! struct Potion<'content> {
+//!     doses: GhostCell<'content, u8>,
+//!     effects: GhostCell<'content, Vec<GhostCell<'content, i32>>>,
+//! }
+//! #[repr(C)]
+//! struct OpenPotion<'doses, 'effects, 'effects_content> {
+//!     doses: GhostCell<'doses, u8>,
+//!     effects: GhostCell<'effects, Vec<GhostCell<'effects_content, i32>>>,
+//! }
+//!
+//! let names: Vec<_> = Potion::FIELDS.iter().map(|field| field.name).collect();
+//! assert_eq!(names, ["doses", "effects"]);
+//! assert_eq!(Potion::FIELDS[1].content_brand, Some("effects_content"));
+//! ```
+
+/// A struct whose fields are cells under one brand, with an open type that
+/// has a brand per field. Implemented by `#[derive(BrandedStruct)]`.
+///
+/// # Safety
+///
+/// `Self` and `Open` must have the same size and alignment, and each field of
+/// `Self` must have the same type and offset as the field of `Open` with its
+/// name.
+pub unsafe trait BrandedStruct {
+    /// The open type, with every brand set to this struct's own.
+    type Open;
+    /// The fields, in the order they are declared.
+    const FIELDS: &'static [BrandedField];
+    /// The number of fields.
+    const FIELD_COUNT: usize = Self::FIELDS.len();
+}
+
+/// A field of a `BrandedStruct`, and the brands it has in the open type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BrandedField {
+    /// The field's name.
+    pub name: &'static str,
+    /// The brand of the field itself, unless it holds no cells.
+    pub brand: Option<&'static str>,
+    /// The brand of the cells inside the field, if there are any.
+    pub content_brand: Option<&'static str>,
+    /// The field's offset, in bytes.
+    pub offset: usize,
+}
@@ -0,0 +1,223 @@
+//! A growable vector of `GhostCell`s whose indices are branded.
+//!
+//! Plain `Vec<GhostCell<'id, T>>` indexing (as in `attack3`) has to check
+//! bounds on every access, and panics when handed an integer that was never
+//! a valid position. `BrandedVec` instead hands out `Index<'id>` values from
+//! `push`, and since the vector consumes the only `GhostToken<'id>` and can
+//! never shrink, every `Index<'id>` that exists is in bounds for the one
+//! vector that carries the brand.
+//!
+//! ```
+//! use demo::{make_guard, BrandedVec};
+//!
+//! make_guard!(rings);
+//! let mut rings = BrandedVec::new(rings);
+//! let weak = rings.push(1u32);
+//! let strong = rings.push(10u32);
+//!
+//! // Hold on to one cell while mutating another through the token.
+//! let (cells, token) = rings.split();
+//! let weak_cell = cells.get(weak);
+//! *cells.get(strong).borrow_mut(token) += *weak_cell.borrow(token);
+//!
+//! assert_eq!(rings[strong], 11);
+//! assert_eq!(rings.iter_indices().map(|i| rings[i]).sum::<u32>(), 12);
+//! assert!(rings.index_from_usize(2).is_none());
+//! ```
+//!
+//! An index only works with the vector that created it, as checked by
+//! `tests/ui/branded_vec_foreign_index.rs`.
+use core::{fmt, marker::PhantomData, ops};
+
+use alloc::vec::Vec;
+
+use crate::{GhostCell, GhostToken};
+
+type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A position in the `BrandedVec<'id, T>` with the same brand.
+///
+/// Indices can only be obtained from the vector itself, so they are always in
+/// bounds and accessing an element through one does not check the length.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Index<'id> {
+    _marker: InvariantLifetime<'id>,
+    index: usize,
+}
+impl<'id> Index<'id> {
+    /// Creates an index without checking it. Only the owning vector may do
+    /// this, and only for positions below its length.
+    #[inline]
+    const fn new_unchecked(index: usize) -> Self {
+        Index {
+            _marker: PhantomData,
+            index,
+        }
+    }
+    /// The position this index refers to.
+    #[inline]
+    pub const fn get(self) -> usize {
+        self.index
+    }
+}
+impl<'id> fmt::Debug for Index<'id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Index").field(&self.index).finish()
+    }
+}
+
+/// A `Vec<GhostCell<'id, T>>` which owns the `GhostToken<'id>` for its cells.
+///
+/// Owning the token is what makes the brand unique to this vector: there can
+/// be no second `BrandedVec<'id, _>` whose shorter length an `Index<'id>` could
+/// overrun. The token is lent back out by `split`, alongside the cells, so the
+/// usual group-borrowing patterns are still available.
+pub struct BrandedVec<'id, T> {
+    token: GhostToken<'id>,
+    cells: Vec<GhostCell<'id, T>>,
+}
+impl<'id, T> BrandedVec<'id, T> {
+    /// Creates an empty vector, taking ownership of the brand's token.
+    #[inline]
+    pub fn new(token: GhostToken<'id>) -> Self {
+        BrandedVec {
+            token,
+            cells: Vec::new(),
+        }
+    }
+    /// Creates an empty vector with space for at least `capacity` elements.
+    #[inline]
+    pub fn with_capacity(token: GhostToken<'id>, capacity: usize) -> Self {
+        BrandedVec {
+            token,
+            cells: Vec::with_capacity(capacity),
+        }
+    }
+    /// Appends a value, returning the index it can be found at.
+    #[inline]
+    pub fn push(&mut self, value: T) -> Index<'id> {
+        let index = Index::new_unchecked(self.cells.len());
+        self.cells.push(GhostCell::new(value));
+        index
+    }
+    /// The number of elements in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+    /// Whether the vector has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+    /// Checks that `index` is in bounds, and brands it if so.
+    #[inline]
+    pub fn index_from_usize(&self, index: usize) -> Option<Index<'id>> {
+        (index < self.cells.len()).then(|| Index::new_unchecked(index))
+    }
+    /// Iterates over every index currently in the vector.
+    #[inline]
+    pub fn iter_indices(&self) -> Indices<'id> {
+        Indices {
+            _marker: PhantomData,
+            range: 0..self.cells.len(),
+        }
+    }
+    /// Gets the cell at `index`, without a bounds check.
+    #[inline]
+    pub fn cell(&self, index: Index<'id>) -> &GhostCell<'id, T> {
+        self.cells().get(index)
+    }
+    /// Gets a reference to the element at `index`, without a bounds check.
+    #[inline]
+    pub fn get(&self, index: Index<'id>) -> &T {
+        self.cell(index).borrow(&self.token)
+    }
+    /// Gets a mutable reference to the element at `index`, without a bounds
+    /// check.
+    #[inline]
+    pub fn get_mut(&mut self, index: Index<'id>) -> &mut T {
+        let (cells, token) = self.split();
+        cells.get(index).borrow_mut(token)
+    }
+    /// A shared view of the cells. Holding onto it keeps the vector from
+    /// growing, but not from being read.
+    #[inline]
+    pub fn cells(&self) -> Cells<'_, 'id, T> {
+        Cells { cells: &self.cells }
+    }
+    /// Splits the vector into its cells and its token, so that references to
+    /// some cells can be held while others are mutated through the token.
+    #[inline]
+    pub fn split(&mut self) -> (Cells<'_, 'id, T>, &mut GhostToken<'id>) {
+        (Cells { cells: &self.cells }, &mut self.token)
+    }
+    /// Unwraps the values, giving up the brand along with the token.
+    pub fn into_vec(self) -> Vec<T> {
+        self.cells.into_iter().map(GhostCell::into_inner).collect()
+    }
+}
+impl<'id, T> ops::Index<Index<'id>> for BrandedVec<'id, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, index: Index<'id>) -> &T {
+        self.get(index)
+    }
+}
+impl<'id, T> ops::IndexMut<Index<'id>> for BrandedVec<'id, T> {
+    #[inline]
+    fn index_mut(&mut self, index: Index<'id>) -> &mut T {
+        self.get_mut(index)
+    }
+}
+
+/// The cells of a `BrandedVec`, borrowed separately from its token.
+pub struct Cells<'a, 'id, T> {
+    cells: &'a [GhostCell<'id, T>],
+}
+impl<'a, 'id, T> Clone for Cells<'a, 'id, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, 'id, T> Copy for Cells<'a, 'id, T> {}
+impl<'a, 'id, T> Cells<'a, 'id, T> {
+    /// Gets the cell at `index`, without a bounds check.
+    #[inline]
+    pub fn get(self, index: Index<'id>) -> &'a GhostCell<'id, T> {
+        // The only `BrandedVec<'id, _>` is the one these cells came from, it
+        // never shrinks, and every `Index<'id>` was created below its length.
+        unsafe { self.cells.get_unchecked(index.index) }
+    }
+    /// The cells as an ordinary slice.
+    #[inline]
+    pub fn as_slice(self) -> &'a [GhostCell<'id, T>] {
+        self.cells
+    }
+}
+
+/// An iterator over the indices of a `BrandedVec`, created by
+/// `BrandedVec::iter_indices`.
+#[derive(Clone)]
+pub struct Indices<'id> {
+    _marker: InvariantLifetime<'id>,
+    range: ops::Range<usize>,
+}
+impl<'id> Iterator for Indices<'id> {
+    type Item = Index<'id>;
+    #[inline]
+    fn next(&mut self) -> Option<Index<'id>> {
+        self.range.next().map(Index::new_unchecked)
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+impl<'id> DoubleEndedIterator for Indices<'id> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Index<'id>> {
+        self.range.next_back().map(Index::new_unchecked)
+    }
+}
+impl<'id> ExactSizeIterator for Indices<'id> {}
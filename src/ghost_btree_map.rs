@@ -0,0 +1,511 @@
+//! A sorted map whose B-tree nodes are `GhostCell`s.
+//!
+//! The map belongs to the group `'id` like any other cell: lookups only need
+//! `&GhostToken<'id>`, so they can run from as many threads as can share the
+//! token, and only `insert` and `remove` need it mutably. Everything a lookup
+//! returns borrows the token, not the map, so a `&V` from one lookup stays
+//! valid while the map is read again, or walked with a `Cursor`.
+//!
+//! ```
+//! use std::ops::Bound;
+//! use demo::{make_guard, GhostBTreeMap};
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let rings = GhostBTreeMap::new();
+//! for (power, name) in [(3, "copper"), (10, "one"), (7, "elven"), (5, "dwarf")] {
+//!     rings.insert(power, name, &mut token);
+//! }
+//! *rings.get_mut(&3, &mut token).unwrap() = "brass";
+//!
+//! let weakest = rings.get(&3, &token).unwrap();
+//! let mut cursor = rings.lower_bound(Bound::Excluded(&5), &token);
+//! let mut stronger = Vec::new();
+//! while let Some((power, name)) = cursor.key_value() {
+//!     stronger.push((*power, *name));
+//!     cursor.move_next();
+//! }
+//! assert_eq!(stronger, [(7, "elven"), (10, "one")]);
+//! assert_eq!(*weakest, "brass");
+//!
+//! assert_eq!(rings.remove(&10, &mut token), Some("one"));
+//! assert_eq!(rings.iter(&token).map(|(power, _)| *power).collect::<Vec<_>>(), [3, 5, 7]);
+//! ```
+use core::{borrow::Borrow, cmp::Ordering, fmt, mem, ops::Bound};
+
+use alloc::vec::Vec;
+
+use crate::{GhostCell, GhostToken};
+
+/// Each node but the root holds between `B - 1` and `CAPACITY` keys.
+const B: usize = 6;
+const CAPACITY: usize = 2 * B - 1;
+
+struct Node<'id, K, V> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    /// Empty for a leaf, and one longer than `keys` otherwise.
+    children: Vec<GhostCell<'id, Node<'id, K, V>>>,
+}
+
+impl<'id, K, V> Node<'id, K, V> {
+    const fn new() -> Self {
+        Node {
+            keys: Vec::new(),
+            vals: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+    fn child<'a>(&'a self, i: usize, token: &'a GhostToken<'id>) -> &'a Self {
+        GhostCell::borrow(&self.children[i], token)
+    }
+    fn child_mut(&mut self, i: usize) -> &mut Self {
+        self.children[i].get_mut()
+    }
+    /// Where `key` is in this node, or which child it would be under.
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.keys.binary_search_by(|k| k.borrow().cmp(key))
+    }
+}
+
+impl<'id, K: Ord, V> Node<'id, K, V> {
+    /// Splits the full child `i` around its middle key, which moves up into
+    /// this node.
+    fn split_child(&mut self, i: usize) {
+        let child = self.child_mut(i);
+        let right = Node {
+            keys: child.keys.split_off(B),
+            vals: child.vals.split_off(B),
+            children: if child.is_leaf() { Vec::new() } else { child.children.split_off(B) },
+        };
+        let (key, val) = (child.keys.pop().unwrap(), child.vals.pop().unwrap());
+        self.keys.insert(i, key);
+        self.vals.insert(i, val);
+        self.children.insert(i + 1, GhostCell::new(right));
+    }
+    /// Inserts into a node that isn't full, splitting full nodes on the way
+    /// down so that there is always room to.
+    fn insert(&mut self, key: K, val: V) -> Option<V> {
+        let mut i = match self.search(&key) {
+            Ok(i) => return Some(mem::replace(&mut self.vals[i], val)),
+            Err(i) if self.is_leaf() => {
+                self.keys.insert(i, key);
+                self.vals.insert(i, val);
+                return None;
+            }
+            Err(i) => i,
+        };
+        if self.child_mut(i).keys.len() == CAPACITY {
+            self.split_child(i);
+            match key.cmp(&self.keys[i]) {
+                Ordering::Less => {}
+                Ordering::Equal => return Some(mem::replace(&mut self.vals[i], val)),
+                Ordering::Greater => i += 1,
+            }
+        }
+        self.child_mut(i).insert(key, val)
+    }
+    /// Makes sure child `i` has a key to spare, by taking one from a sibling
+    /// or merging with it, and returns where that child now is.
+    fn fill_child(&mut self, i: usize) -> usize {
+        if self.child_mut(i).keys.len() >= B {
+            i
+        } else if i > 0 && self.child_mut(i - 1).keys.len() >= B {
+            let (left, right) = self.children.split_at_mut(i);
+            let (left, child) = (left[i - 1].get_mut(), right[0].get_mut());
+            let key = mem::replace(&mut self.keys[i - 1], left.keys.pop().unwrap());
+            let val = mem::replace(&mut self.vals[i - 1], left.vals.pop().unwrap());
+            child.keys.insert(0, key);
+            child.vals.insert(0, val);
+            if let Some(grandchild) = left.children.pop() {
+                child.children.insert(0, grandchild);
+            }
+            i
+        } else if i + 1 < self.children.len() && self.child_mut(i + 1).keys.len() >= B {
+            let (left, right) = self.children.split_at_mut(i + 1);
+            let (child, right) = (left[i].get_mut(), right[0].get_mut());
+            let key = mem::replace(&mut self.keys[i], right.keys.remove(0));
+            let val = mem::replace(&mut self.vals[i], right.vals.remove(0));
+            child.keys.push(key);
+            child.vals.push(val);
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+            i
+        } else if i + 1 < self.children.len() {
+            self.merge_children(i);
+            i
+        } else {
+            self.merge_children(i - 1);
+            i - 1
+        }
+    }
+    /// Merges child `i + 1`, and the key between them, into child `i`.
+    fn merge_children(&mut self, i: usize) {
+        let right = self.children.remove(i + 1).into_inner();
+        let (key, val) = (self.keys.remove(i), self.vals.remove(i));
+        let left = self.child_mut(i);
+        left.keys.push(key);
+        left.vals.push(val);
+        left.keys.extend(right.keys);
+        left.vals.extend(right.vals);
+        left.children.extend(right.children);
+    }
+    /// Removes `key` from the subtree, filling each child before going down
+    /// into it so that it can spare a key.
+    fn remove<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.search(key) {
+            Ok(i) if self.is_leaf() => Some((self.keys.remove(i), self.vals.remove(i))),
+            Ok(i) => {
+                let (k, v) = if self.child_mut(i).keys.len() >= B {
+                    self.child_mut(i).pop_last()
+                } else if self.child_mut(i + 1).keys.len() >= B {
+                    self.child_mut(i + 1).pop_first()
+                } else {
+                    self.merge_children(i);
+                    return self.child_mut(i).remove(key);
+                };
+                Some((mem::replace(&mut self.keys[i], k), mem::replace(&mut self.vals[i], v)))
+            }
+            Err(_) if self.is_leaf() => None,
+            Err(i) => {
+                let i = self.fill_child(i);
+                self.child_mut(i).remove(key)
+            }
+        }
+    }
+    fn pop_first(&mut self) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.remove(0), self.vals.remove(0));
+        }
+        let i = self.fill_child(0);
+        self.child_mut(i).pop_first()
+    }
+    fn pop_last(&mut self) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.vals.pop().unwrap());
+        }
+        let i = self.fill_child(self.children.len() - 1);
+        self.child_mut(i).pop_last()
+    }
+}
+
+/// A sorted map belonging to the group `'id`.
+pub struct GhostBTreeMap<'id, K, V> {
+    root: GhostCell<'id, Node<'id, K, V>>,
+    len: GhostCell<'id, usize>,
+}
+impl<'id, K, V> GhostBTreeMap<'id, K, V> {
+    /// Creates an empty map, which allocates nothing until the first insert.
+    #[inline]
+    pub const fn new() -> Self {
+        GhostBTreeMap {
+            root: GhostCell::new(Node::new()),
+            len: GhostCell::new(0),
+        }
+    }
+    /// The number of entries.
+    #[inline]
+    pub fn len(&self, token: &GhostToken<'id>) -> usize {
+        *self.len.borrow(token)
+    }
+    /// Whether the map has no entries.
+    #[inline]
+    pub fn is_empty(&self, token: &GhostToken<'id>) -> bool {
+        self.len(token) == 0
+    }
+    /// Iterates over the entries in key order.
+    pub fn iter<'a>(&'a self, token: &'a GhostToken<'id>) -> Iter<'a, 'id, K, V> {
+        Iter {
+            cursor: self.cursor_front(token),
+            len: self.len(token),
+        }
+    }
+    /// A cursor at the first entry.
+    pub fn cursor_front<'a>(&'a self, token: &'a GhostToken<'id>) -> Cursor<'a, 'id, K, V> {
+        let mut cursor = Cursor {
+            root: self.root.borrow(token),
+            token,
+            stack: Vec::new(),
+        };
+        cursor.move_next();
+        cursor
+    }
+    /// A cursor at the last entry.
+    pub fn cursor_back<'a>(&'a self, token: &'a GhostToken<'id>) -> Cursor<'a, 'id, K, V> {
+        let mut cursor = Cursor {
+            root: self.root.borrow(token),
+            token,
+            stack: Vec::new(),
+        };
+        cursor.move_prev();
+        cursor
+    }
+}
+impl<'id, K: Ord, V> GhostBTreeMap<'id, K, V> {
+    /// The value for `key`.
+    pub fn get<'a, Q>(&'a self, key: &Q, token: &'a GhostToken<'id>) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut node = self.root.borrow(token);
+        loop {
+            match node.search(key) {
+                Ok(i) => return Some(&node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = node.child(i, token),
+            }
+        }
+    }
+    /// Whether the map has an entry for `key`.
+    pub fn contains_key<Q>(&self, key: &Q, token: &GhostToken<'id>) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key, token).is_some()
+    }
+    /// The value for `key`, mutably.
+    pub fn get_mut<'a, Q>(&'a self, key: &Q, token: &'a mut GhostToken<'id>) -> Option<&'a mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Below the root, the token being borrowed mutably is what lets us
+        // reach each child through `get_mut`.
+        let mut node = self.root.borrow_mut(token);
+        loop {
+            match node.search(key) {
+                Ok(i) => return Some(&mut node.vals[i]),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => node = node.child_mut(i),
+            }
+        }
+    }
+    /// Inserts an entry, returning the value it replaced.
+    pub fn insert(&self, key: K, value: V, token: &mut GhostToken<'id>) -> Option<V> {
+        let root = self.root.borrow_mut(token);
+        if root.keys.len() == CAPACITY {
+            let old = mem::replace(root, Node::new());
+            root.children.push(GhostCell::new(old));
+            root.split_child(0);
+        }
+        let old = root.insert(key, value);
+        if old.is_none() {
+            *self.len.borrow_mut(token) += 1;
+        }
+        old
+    }
+    /// Removes the entry for `key`, returning its value.
+    pub fn remove<Q>(&self, key: &Q, token: &mut GhostToken<'id>) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let root = self.root.borrow_mut(token);
+        let removed = root.remove(key);
+        if root.keys.is_empty()
+            && let Some(child) = root.children.pop()
+        {
+            *root = child.into_inner();
+        }
+        let (_, value) = removed?;
+        *self.len.borrow_mut(token) -= 1;
+        Some(value)
+    }
+    /// A cursor at the first entry whose key is after `bound`.
+    pub fn lower_bound<'a, Q>(&'a self, bound: Bound<&Q>, token: &'a GhostToken<'id>) -> Cursor<'a, 'id, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cursor = Cursor {
+            root: self.root.borrow(token),
+            token,
+            stack: Vec::new(),
+        };
+        let mut node = cursor.root;
+        loop {
+            let i = match bound {
+                Bound::Included(bound) => match node.search(bound) {
+                    Ok(i) => return cursor.at(node, i),
+                    Err(i) => i,
+                },
+                Bound::Excluded(bound) => node.keys.partition_point(|k| k.borrow() <= bound),
+                Bound::Unbounded => 0,
+            };
+            if node.is_leaf() {
+                if i < node.keys.len() {
+                    return cursor.at(node, i);
+                }
+                cursor.stack.push((node, i));
+                cursor.ascend_next();
+                return cursor;
+            }
+            cursor.stack.push((node, i));
+            node = node.child(i, token);
+        }
+    }
+}
+impl<'id, K, V> Default for GhostBTreeMap<'id, K, V> {
+    fn default() -> Self {
+        GhostBTreeMap::new()
+    }
+}
+impl<'id, K: Ord, V> FromIterator<(K, V)> for GhostBTreeMap<'id, K, V> {
+    /// Collects the entries into a map, whose nodes nobody else can be
+    /// reaching yet, so no token is needed.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = GhostBTreeMap::new();
+        for (key, value) in iter {
+            let root = map.root.get_mut();
+            if root.keys.len() == CAPACITY {
+                let old = mem::replace(root, Node::new());
+                root.children.push(GhostCell::new(old));
+                root.split_child(0);
+            }
+            if root.insert(key, value).is_none() {
+                *map.len.get_mut() += 1;
+            }
+        }
+        map
+    }
+}
+
+/// A position in a `GhostBTreeMap`, at an entry or past either end.
+///
+/// The entries it returns borrow the token rather than the cursor, so they
+/// stay valid as it moves. Past either end, moving it again wraps around to
+/// the other end.
+pub struct Cursor<'a, 'id, K, V> {
+    root: &'a Node<'id, K, V>,
+    token: &'a GhostToken<'id>,
+    /// The path from the root: the child taken at each node, and at the top,
+    /// the entry the cursor is at. Empty past either end.
+    stack: Vec<(&'a Node<'id, K, V>, usize)>,
+}
+impl<'a, 'id, K, V> Cursor<'a, 'id, K, V> {
+    /// The entry the cursor is at.
+    pub fn key_value(&self) -> Option<(&'a K, &'a V)> {
+        let &(node, i) = self.stack.last()?;
+        Some((&node.keys[i], &node.vals[i]))
+    }
+    /// The key the cursor is at.
+    pub fn key(&self) -> Option<&'a K> {
+        self.key_value().map(|(key, _)| key)
+    }
+    /// The value the cursor is at.
+    pub fn value(&self) -> Option<&'a V> {
+        self.key_value().map(|(_, value)| value)
+    }
+    /// Moves to the next entry.
+    pub fn move_next(&mut self) {
+        let (node, i) = match self.stack.pop() {
+            Some((node, i)) => (node, i + 1),
+            None if self.root.keys.is_empty() => return,
+            None => (self.root, 0),
+        };
+        if node.is_leaf() {
+            self.stack.push((node, i));
+            if i == node.keys.len() {
+                self.ascend_next();
+            }
+            return;
+        }
+        // Take the child after the old entry, then go all the way left.
+        self.stack.push((node, i));
+        let mut node = node.child(i, self.token);
+        while !node.is_leaf() {
+            self.stack.push((node, 0));
+            node = node.child(0, self.token);
+        }
+        self.stack.push((node, 0));
+    }
+    /// Moves to the previous entry.
+    pub fn move_prev(&mut self) {
+        let (node, i) = match self.stack.pop() {
+            Some((node, i)) => (node, i),
+            None if self.root.keys.is_empty() => return,
+            None => (self.root, self.root.keys.len()),
+        };
+        if node.is_leaf() {
+            if i == 0 {
+                self.ascend_prev();
+            } else {
+                self.stack.push((node, i - 1));
+            }
+            return;
+        }
+        // Take the child before the old entry, then go all the way right.
+        self.stack.push((node, i));
+        let mut node = node.child(i, self.token);
+        while !node.is_leaf() {
+            self.stack.push((node, node.keys.len()));
+            node = node.child(node.keys.len(), self.token);
+        }
+        self.stack.push((node, node.keys.len() - 1));
+    }
+    /// Sets the cursor at entry `i` of `node`, the last on the stack's path.
+    fn at(mut self, node: &'a Node<'id, K, V>, i: usize) -> Self {
+        self.stack.push((node, i));
+        self
+    }
+    /// From one past the last entry of a node, climbs to the next entry.
+    fn ascend_next(&mut self) {
+        self.stack.pop();
+        while let Some(&(node, i)) = self.stack.last() {
+            if i < node.keys.len() {
+                return;
+            }
+            self.stack.pop();
+        }
+    }
+    /// From the first entry of a leaf, climbs to the previous entry.
+    fn ascend_prev(&mut self) {
+        while let Some((node, i)) = self.stack.pop() {
+            if i > 0 {
+                self.stack.push((node, i - 1));
+                return;
+            }
+        }
+    }
+}
+impl<'a, 'id, K: fmt::Debug, V: fmt::Debug> fmt::Debug for Cursor<'a, 'id, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Cursor").field(&self.key_value()).finish()
+    }
+}
+
+/// An iterator over the entries of a `GhostBTreeMap`, in key order.
+pub struct Iter<'a, 'id, K, V> {
+    cursor: Cursor<'a, 'id, K, V>,
+    len: usize,
+}
+impl<'a, 'id, K, V> Iterator for Iter<'a, 'id, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let entry = self.cursor.key_value();
+        self.cursor.move_next();
+        self.len -= 1;
+        entry
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+impl<'a, 'id, K, V> ExactSizeIterator for Iter<'a, 'id, K, V> {}
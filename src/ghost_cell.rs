@@ -70,9 +70,10 @@ So I'm gonna say the license holder is "MPI-SWS" in lieu of it being specified.
 // pub mod dlist_arc;
 // pub mod dlist_arena;
 // pub mod dfs_arena;
-// pub mod list_arena;
 // pub mod dfs_arena_list;
 use core::{cell::UnsafeCell, marker::PhantomData};
+
+use crate::WithToken;
 type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
 /// A ghost token.
 ///
@@ -138,33 +139,8 @@ impl<'id, T> GhostCell<'id, T> {
     /// get a mutable reference to the `T` inside a `GhostCell<'id, T>` is being
     /// bypassed, there could be a soundness bug here. Fortunately, thanks to
     /// `dropck`, such pathological cases appear to be ruled out. For example,
-    /// this code will not compile:
-    ///
-    /// ```compile_fail
-    /// use ghost_cell::{GhostToken, GhostCell};
-    ///
-    /// struct Foo<'a, 'id>(&'a GhostToken<'id>,
-    ///                         GhostCell<'id, GhostCell<Option<&'a Foo<'a, 'id>>>>)
-    ///                              where 'id: 'a;
-    ///
-    /// impl<'a, 'id> Drop for Foo<'a, 'id> {
-    ///     fn drop(&mut self) {
-    ///         match self.0.get(&self.1).get() {
-    ///             Some(ref foo) => {
-    ///                 println!("Oops, have aliasing.");
-    ///             },
-    ///             None => {
-    ///                 println!("Okay")
-    ///             }
-    ///         }
-    ///     }
-    /// }
-    ///
-    /// GhostToken::new(|token| {
-    ///     let foo = Foo(&token, GhostCell::new(GhostCell::new(None)));
-    ///     foo.1.borrow(&token).set(Some(&foo));
-    /// });
-    /// ```
+    /// `tests/ui/drop_token_self_reference.rs` will not compile: there, a `Foo`
+    /// holding the token tries to store a reference to itself in its own cell.
     ///
     /// It will compile if the manual `Drop` implementation is removed, but only
     /// pathological `Drop` implementations are an issue here.  I believe there
@@ -174,20 +150,8 @@ impl<'id, T> GhostCell<'id, T> {
     /// implementation.  As a result, if there is any reference to a `GhostCell`
     /// containing the type being dropped from within the type being dropped,
     /// and it has a nontrivial `Drop` implementation, it will not be possible to
-    /// complete the cycle.  To illustrate more clearly, this fails, too:
-    ///
-    /// ```compile_fail
-    /// fn foo() {
-    ///     struct Foo<'a>(GhostCell<Option<&'a Foo<'a>>>);
-    ///
-    ///     impl<'a> Drop for Foo<'a> {
-    ///         fn drop(&mut self) {}
-    ///     }
-    ///
-    ///     let foo = Foo(GhostCell::new(None));
-    ///     foo.0.set(Some(&foo));
-    /// }
-    /// ```
+    /// complete the cycle.  To illustrate more clearly, this fails, too, with
+    /// `Foo` holding only the cell: `tests/ui/drop_cell_self_reference.rs`.
     ///
     /// So any conceivable way to peek at a self-reference within a `Drop`
     /// implementation is probably covered.
@@ -233,6 +197,35 @@ impl<'id, T> GhostCell<'id, T> {
             &mut *self.value.get()
         }
     }
+    /// Get mutable references to two items at once, or `None` if `a` and `b`
+    /// are the same cell.
+    ///
+    /// Cells are told apart by address, so two distinct cells of a zero-sized
+    /// type may be reported as the same one.
+    #[inline]
+    pub fn borrow_mut_twice<'a>(
+        a: &'a Self,
+        b: &'a Self,
+        _token: &'a mut GhostToken<'id>,
+    ) -> Option<(&'a mut T, &'a mut T)> {
+        if core::ptr::eq(a, b) {
+            return None;
+        }
+        unsafe {
+            // Distinct cells of a sized type never overlap, so these two
+            // references are to disjoint items of the set, and the token being
+            // borrowed mutably for `'a` rules out any other reference into it.
+            Some((&mut *a.value.get(), &mut *b.value.get()))
+        }
+    }
+    /// Swaps the values of two cells. Swapping a cell with itself does
+    /// nothing.
+    #[inline]
+    pub fn swap(&self, other: &Self, token: &mut GhostToken<'id>) {
+        if let Some((a, b)) = GhostCell::borrow_mut_twice(self, other, token) {
+            core::mem::swap(a, b);
+        }
+    }
 }
 impl<'id, T> From<T> for GhostCell<'id, T> {
     #[inline]
@@ -253,9 +246,18 @@ impl<'id, T: ?Sized> GhostCell<'id, T> {
     pub fn get_mut(&mut self) -> &mut T {
         unsafe { &mut *self.value.get() }
     }
+    /// Pairs the cell with its token, so that it can be formatted, compared
+    /// or hashed through `WithToken`.
+    #[inline]
+    pub fn with<'a>(&'a self, token: &'a GhostToken<'id>) -> WithToken<'a, 'id, Self> {
+        WithToken::new(self, token)
+    }
     /// Returns a `&mut GhostCell<'id, T>` from a `&mut T`
     #[inline]
     pub fn from_mut(t: &mut T) -> &mut Self {
+        // `GhostCell` is `repr(transparent)` over `UnsafeCell<T>`, which is
+        // over `T`; that is the guarantee, since `T` may be unsized and have
+        // no size to assert on.
         unsafe { &mut *(t as *mut T as *mut Self) }
 
     }
@@ -264,6 +266,7 @@ impl<'id, T> GhostCell<'id, [T]> {
     /// Returns a `&[GhostCell<'id, T>]` from a `&GhostCell<'id, [T]>`
     #[inline]
     pub fn as_slice_of_cells(&self) -> &[GhostCell<'id, T>] {
+        crate::assert_same_layout!(T, GhostCell<'id, T>);
         unsafe { &*(self as *const GhostCell<'id, [T]> as *const [GhostCell<'id, T>]) }
     }
 }
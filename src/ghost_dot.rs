@@ -0,0 +1,233 @@
+//! GraphViz export of the cells reachable from some roots, coloured by group.
+//!
+//! Brands are erased at runtime, so each group is traced separately, with its
+//! own token and a name to show for it. Cells are identified by address, so
+//! a cell reached while tracing one group and then again while tracing
+//! another is drawn once. Cells of a group that hasn't been traced (yet) are
+//! drawn dashed and uncoloured, since they can't be read without their token.
+//!
+//! For example, a squad in one group, whose members are in another:
+//!
+//! ```
+//! use demo::{make_guard, GhostCell};
+//! use demo::ghost_dot::{Dot, Trace, Tracer};
+//!
+//! struct Squad<'member> {
+//!     members: Vec<GhostCell<'member, Member>>,
+//! }
+//! struct Member {
+//!     hp: u32,
+//! }
+//! impl<'id> Trace<'id> for Member {}
+//! impl<'id, 'member> Trace<'id> for Squad<'member> {
+//!     fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+//!         for member in &self.members {
+//!             tracer.foreign(member);
+//!         }
+//!     }
+//! }
+//!
+//! make_guard!(squad_group);
+//! make_guard!(member_group);
+//! let squad = GhostCell::new(Squad {
+//!     members: vec![GhostCell::new(Member { hp: 3 }), GhostCell::new(Member { hp: 5 })],
+//! });
+//! let members = &squad.borrow(&squad_group).members;
+//!
+//! let dot = Dot::new()
+//!     .group("squad_group", &squad_group, &[&squad])
+//!     .group("member_group", &member_group, &[members])
+//!     .finish();
+//! assert!(dot.starts_with("digraph {"));
+//! assert!(dot.contains(r#"n0 [label="Squad", fillcolor="lightblue"]"#));
+//! assert!(dot.contains(r#"n2 [label="Member", fillcolor="palegreen"]"#));
+//! assert!(dot.contains("n0 -> n1;"));
+//! ```
+//!
+//! `export` is the shorthand for a single group.
+use alloc::{borrow::ToOwned, boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+use core::{any::type_name, fmt::Write};
+
+use crate::{GhostCell, GhostToken};
+
+const COLOURS: &[&str] = &["lightblue", "palegreen", "lightgoldenrod", "lightpink", "lightsalmon", "plum"];
+
+/// A value that may contain `GhostCell`s, whose readable cells belong to the
+/// group `'id`.
+pub trait Trace<'id> {
+    /// Reports every cell directly inside this value to `tracer`: with `cell`
+    /// when it belongs to `'id`, and `foreign` otherwise.
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        let _ = tracer;
+    }
+}
+
+/// Renders the cells of one group, reachable from `roots`, as DOT.
+pub fn export<'id>(roots: &[&dyn Trace<'id>], token: &GhostToken<'id>) -> String {
+    Dot::new().group("'id", token, roots).finish()
+}
+
+/// A graph of cells, built up one group at a time.
+#[derive(Default)]
+pub struct Dot {
+    nodes: Vec<Node>,
+    ids: BTreeMap<(*const (), &'static str), usize>,
+    edges: Vec<(usize, usize)>,
+    groups: Vec<String>,
+}
+struct Node {
+    label: String,
+    group: Option<usize>,
+}
+impl Dot {
+    pub fn new() -> Self {
+        Dot::default()
+    }
+    /// Adds every cell of the group `name` reachable from `roots`.
+    pub fn group<'id>(mut self, name: &str, token: &GhostToken<'id>, roots: &[&dyn Trace<'id>]) -> Self {
+        self.groups.push(name.to_owned());
+        let mut tracer = Tracer {
+            group: self.groups.len() - 1,
+            dot: &mut self,
+            token,
+            parent: None,
+        };
+        for root in roots {
+            root.trace(&mut tracer);
+        }
+        self
+    }
+    /// Writes out the graph, with a legend naming each group's colour.
+    pub fn finish(self) -> String {
+        let mut out = String::from("digraph {\n    node [shape=box, style=filled, fillcolor=white];\n");
+        for (i, group) in self.groups.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    g{i} [label={group:?}, shape=note, fillcolor={:?}];",
+                COLOURS[i % COLOURS.len()]
+            );
+        }
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = &node.label;
+            match node.group {
+                Some(group) => {
+                    let _ = writeln!(out, "    n{i} [label={label:?}, fillcolor={:?}];", COLOURS[group % COLOURS.len()]);
+                }
+                None => {
+                    let _ = writeln!(out, "    n{i} [label={label:?}, style=dashed];");
+                }
+            }
+        }
+        for (from, to) in &self.edges {
+            let _ = writeln!(out, "    n{from} -> n{to};");
+        }
+        out.push_str("}\n");
+        out
+    }
+    fn node<T: ?Sized>(&mut self, cell: &GhostCell<'_, T>) -> usize {
+        // Keyed by type as well, since a struct and its first field share an
+        // address.
+        let key = (cell.as_ptr() as *const (), type_name::<T>());
+        let nodes = &mut self.nodes;
+        *self.ids.entry(key).or_insert_with(|| {
+            nodes.push(Node {
+                label: short_type_name(type_name::<T>()),
+                group: None,
+            });
+            nodes.len() - 1
+        })
+    }
+    fn edge(&mut self, from: Option<usize>, to: usize) {
+        if let Some(from) = from
+            && !self.edges.contains(&(from, to))
+        {
+            self.edges.push((from, to));
+        }
+    }
+}
+
+/// Walks the cells of one group for a `Dot`.
+pub struct Tracer<'a, 'id> {
+    dot: &'a mut Dot,
+    token: &'a GhostToken<'id>,
+    group: usize,
+    parent: Option<usize>,
+}
+impl<'a, 'id> Tracer<'a, 'id> {
+    /// Draws `cell`, and everything it refers to, as part of this group.
+    pub fn cell<T: Trace<'id>>(&mut self, cell: &GhostCell<'id, T>) {
+        let node = self.dot.node(cell);
+        self.dot.edge(self.parent, node);
+        if self.dot.nodes[node].group == Some(self.group) {
+            return;
+        }
+        self.dot.nodes[node].group = Some(self.group);
+        let parent = self.parent.replace(node);
+        cell.borrow(self.token).trace(self);
+        self.parent = parent;
+    }
+    /// Draws `cell`, which belongs to some other group, without reading it.
+    pub fn foreign<T: ?Sized>(&mut self, cell: &GhostCell<'_, T>) {
+        let node = self.dot.node(cell);
+        self.dot.edge(self.parent, node);
+    }
+}
+
+/// Strips the module paths and erased lifetimes from a type name.
+fn short_type_name(name: &str) -> String {
+    let mut out = String::new();
+    let mut path = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            out.extend(path.rsplit("::").next());
+            path.clear();
+            out.push(c);
+        }
+    }
+    out.extend(path.rsplit("::").next());
+    out.replace("<'_>", "").replace("'_, ", "")
+}
+
+impl<'id, T: Trace<'id>> Trace<'id> for GhostCell<'id, T> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        tracer.cell(self)
+    }
+}
+impl<'id, T: ?Sized + Trace<'id>> Trace<'id> for &T {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        (**self).trace(tracer)
+    }
+}
+impl<'id, T: ?Sized + Trace<'id>> Trace<'id> for Box<T> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        (**self).trace(tracer)
+    }
+}
+impl<'id, T: Trace<'id>> Trace<'id> for [T] {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        for x in self {
+            x.trace(tracer);
+        }
+    }
+}
+impl<'id, T: Trace<'id>> Trace<'id> for Vec<T> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        self[..].trace(tracer)
+    }
+}
+impl<'id, T: Trace<'id>> Trace<'id> for Option<T> {
+    fn trace(&self, tracer: &mut Tracer<'_, 'id>) {
+        if let Some(x) = self {
+            x.trace(tracer);
+        }
+    }
+}
+macro_rules! impl_leaf {
+    ($($t:ty),*) => {$(
+        impl<'id> Trace<'id> for $t {}
+    )*};
+}
+impl_leaf!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_leaf!(f32, f64, bool, char, str, String, ());
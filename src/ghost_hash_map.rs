@@ -0,0 +1,168 @@
+//! A hash map whose values are `GhostCell`s.
+//!
+//! Looking an entry up needs no token, and hands back the entry's
+//! `&GhostCell<'id, V>`, to be opened with the token later, the way the
+//! entities' cells are. Since the handle borrows the map and not the token,
+//! it can be held while the token mutates any other entry's value, or this
+//! one's, and the map can be read from any number of threads at once.
+//!
+//! Adding or removing entries moves them around, so it needs the map
+//! exclusively, as `&mut self`; that is what rules out a handle from `get`
+//! outliving its entry.
+//!
+//! ```
+//! use demo::{make_guard, GhostHashMap};
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let mut hp = GhostHashMap::new();
+//! hp.insert("goblin", 7);
+//! hp.insert("troll", 30);
+//!
+//! let goblin = hp.get("goblin").unwrap();
+//! let troll = hp.get("troll").unwrap();
+//! *troll.borrow_mut(&mut token) -= *goblin.borrow(&token);
+//! *goblin.borrow_mut(&mut token) = 0;
+//! assert_eq!(*troll.borrow(&token), 23);
+//!
+//! hp.retain(|_, hp| *hp > 0);
+//! assert_eq!(hp.keys().collect::<Vec<_>>(), [&"troll"]);
+//! ```
+use std::{
+    borrow::Borrow,
+    collections::{hash_map, HashMap},
+    hash::{BuildHasher, Hash, RandomState},
+};
+
+use crate::{GhostCell, GhostToken};
+
+/// A hash map belonging to the group `'id`, with a `GhostCell` per value.
+pub struct GhostHashMap<'id, K, V, S = RandomState> {
+    map: HashMap<K, GhostCell<'id, V>, S>,
+}
+impl<'id, K, V> GhostHashMap<'id, K, V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        GhostHashMap { map: HashMap::new() }
+    }
+    /// Creates an empty map with room for `capacity` entries.
+    pub fn with_capacity(capacity: usize) -> Self {
+        GhostHashMap {
+            map: HashMap::with_capacity(capacity),
+        }
+    }
+}
+impl<'id, K, V, S> GhostHashMap<'id, K, V, S> {
+    /// Creates an empty map which hashes keys with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        GhostHashMap {
+            map: HashMap::with_hasher(hasher),
+        }
+    }
+    /// The number of entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    /// Whether the map has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    /// Iterates over the entries, in no particular order.
+    pub fn iter(&self) -> hash_map::Iter<'_, K, GhostCell<'id, V>> {
+        self.map.iter()
+    }
+    /// Iterates over the keys, in no particular order.
+    pub fn keys(&self) -> hash_map::Keys<'_, K, GhostCell<'id, V>> {
+        self.map.keys()
+    }
+    /// Iterates over the values' cells, in no particular order.
+    pub fn values(&self) -> hash_map::Values<'_, K, GhostCell<'id, V>> {
+        self.map.values()
+    }
+    /// Removes every entry.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+    /// Keeps only the entries for which `f` returns `true`. The map is
+    /// borrowed mutably, so the values can be too, without a token.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.map.retain(|key, value| f(key, value.get_mut()));
+    }
+}
+impl<'id, K: Eq + Hash, V, S: BuildHasher> GhostHashMap<'id, K, V, S> {
+    /// The cell holding the value for `key`.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&GhostCell<'id, V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key)
+    }
+    /// Whether the map has an entry for `key`.
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+    /// The value for `key`, mutably, which needs no token since the map is
+    /// borrowed mutably.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get_mut(key).map(GhostCell::get_mut)
+    }
+    /// The values for two different keys, both mutably, or `None` if either
+    /// is missing or they are the same entry.
+    pub fn get_two_mut<'a, Q>(&'a self, a: &Q, b: &Q, token: &'a mut GhostToken<'id>) -> Option<(&'a mut V, &'a mut V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        GhostCell::borrow_mut_twice(self.get(a)?, self.get(b)?, token)
+    }
+    /// Inserts an entry, returning the value it replaced.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, GhostCell::new(value)).map(GhostCell::into_inner)
+    }
+    /// Removes the entry for `key`, returning its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.remove(key).map(GhostCell::into_inner)
+    }
+}
+impl<'id, K, V, S: Default> Default for GhostHashMap<'id, K, V, S> {
+    fn default() -> Self {
+        GhostHashMap::with_hasher(S::default())
+    }
+}
+impl<'id, K: Eq + Hash, V, S: BuildHasher + Default> FromIterator<(K, V)> for GhostHashMap<'id, K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        GhostHashMap {
+            map: iter.into_iter().map(|(key, value)| (key, GhostCell::new(value))).collect(),
+        }
+    }
+}
+impl<'id, K: Eq + Hash, V, S: BuildHasher> Extend<(K, V)> for GhostHashMap<'id, K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|(key, value)| (key, GhostCell::new(value))));
+    }
+}
+impl<'a, 'id, K, V, S> IntoIterator for &'a GhostHashMap<'id, K, V, S> {
+    type Item = (&'a K, &'a GhostCell<'id, V>);
+    type IntoIter = hash_map::Iter<'a, K, GhostCell<'id, V>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
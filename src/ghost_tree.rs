@@ -0,0 +1,341 @@
+//! A tree whose nodes own their children and point back at their parents.
+//!
+//! With `RefCell`, every step up or down a parent pointer is a runtime
+//! borrow, and reading a parent while its child is borrowed mutably panics.
+//! Here each node is an `Rc<GhostCell<'id, _>>`, its parent link is a
+//! `Weak` to the same, and the whole tree opens with the one token: walks
+//! borrow it immutably, relinking borrows it mutably.
+//!
+//! `visit_mut` covers the case the token alone can't: it lends each node's
+//! value out mutably, alongside its ancestors' values immutably. It splits
+//! the token in two, one for the node being visited and one for the nodes
+//! above it, which is sound because a node is never its own ancestor.
+//!
+//! ```
+//! use demo::{make_guard, GhostTree};
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let world = GhostTree::new(("world", 0));
+//! let castle = GhostTree::new(("castle", 0));
+//! let armory = GhostTree::new(("armory", 0));
+//! world.push_child(castle.clone(), &mut token);
+//! world.push_child(armory.clone(), &mut token);
+//!
+//! // The armory moves into the castle.
+//! castle.push_child(armory.clone(), &mut token);
+//! assert_eq!(armory.parent(&token).unwrap().borrow(&token).0, "castle");
+//!
+//! // Each node records its depth, read off its ancestors.
+//! world.visit_mut(&mut token, |(_, depth), ancestors| *depth = ancestors.len());
+//! let names: Vec<_> = world.pre_order(&token).map(|node| *node.borrow(&token)).collect();
+//! assert_eq!(names, [("world", 0), ("castle", 1), ("armory", 2)]);
+//! ```
+use core::fmt;
+
+use alloc::{
+    rc::{Rc, Weak},
+    vec,
+    vec::Vec,
+};
+
+use crate::{GhostCell, GhostToken};
+
+struct Node<'id, T> {
+    value: T,
+    parent: Weak<GhostCell<'id, Node<'id, T>>>,
+    children: Vec<GhostTree<'id, T>>,
+}
+
+/// A node of a tree belonging to the group `'id`, and the subtree below it.
+///
+/// Cloning it clones the handle, not the subtree.
+pub struct GhostTree<'id, T> {
+    node: Rc<GhostCell<'id, Node<'id, T>>>,
+}
+impl<'id, T> GhostTree<'id, T> {
+    /// Creates a tree of one node.
+    pub fn new(value: T) -> Self {
+        GhostTree {
+            node: Rc::new(GhostCell::new(Node {
+                value,
+                parent: Weak::new(),
+                children: Vec::new(),
+            })),
+        }
+    }
+    /// The node's value.
+    #[inline]
+    pub fn borrow<'a>(&'a self, token: &'a GhostToken<'id>) -> &'a T {
+        &self.node.borrow(token).value
+    }
+    /// The node's value, mutably.
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, token: &'a mut GhostToken<'id>) -> &'a mut T {
+        &mut self.node.borrow_mut(token).value
+    }
+    /// The node's parent, unless it is a root.
+    pub fn parent(&self, token: &GhostToken<'id>) -> Option<Self> {
+        let node = self.node.borrow(token).parent.upgrade()?;
+        Some(GhostTree { node })
+    }
+    /// The node's children, in order.
+    #[inline]
+    pub fn children<'a>(&'a self, token: &'a GhostToken<'id>) -> &'a [Self] {
+        &self.node.borrow(token).children
+    }
+    /// The root of the tree this node is in.
+    pub fn root(&self, token: &GhostToken<'id>) -> Self {
+        let mut root = self.clone();
+        while let Some(parent) = root.parent(token) {
+            root = parent;
+        }
+        root
+    }
+    /// A weak handle to the node, which doesn't keep it alive.
+    pub fn downgrade(&self) -> WeakTree<'id, T> {
+        WeakTree {
+            node: Rc::downgrade(&self.node),
+        }
+    }
+    /// Whether both handles are to the same node.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.node, &other.node)
+    }
+    /// Whether this node is `other` or one of its ancestors.
+    pub fn is_ancestor_of(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+        let mut node = Some(other.clone());
+        while let Some(current) = node {
+            if current.ptr_eq(self) {
+                return true;
+            }
+            node = current.parent(token);
+        }
+        false
+    }
+    /// Adds `child` as this node's last child, moving it from its old parent
+    /// if it had one.
+    ///
+    /// # Panics
+    ///
+    /// If `child` is this node or one of its ancestors.
+    pub fn push_child(&self, child: Self, token: &mut GhostToken<'id>) {
+        let index = self.children(token).len();
+        self.insert_child(index, child, token);
+    }
+    /// Adds `child` as this node's child at `index`, moving it from its old
+    /// parent if it had one. The index is counted after that move.
+    ///
+    /// # Panics
+    ///
+    /// If `child` is this node or one of its ancestors, or `index` is past
+    /// the end of the children.
+    pub fn insert_child(&self, index: usize, child: Self, token: &mut GhostToken<'id>) {
+        // The tree's shape must stay a tree: `visit_mut` relies on no node
+        // being its own ancestor.
+        assert!(!child.is_ancestor_of(self, token), "a node can't become its own descendant");
+        // Check the index before relinking anything, so a panic leaves the
+        // tree as it was. Moving a child of this node frees up one slot.
+        let mut len = self.children(token).len();
+        if child.parent(token).is_some_and(|parent| parent.ptr_eq(self)) {
+            len -= 1;
+        }
+        assert!(index <= len, "insertion index (is {index}) should be <= len (is {len})");
+        child.detach(token);
+        child.node.borrow_mut(token).parent = Rc::downgrade(&self.node);
+        self.node.borrow_mut(token).children.insert(index, child);
+    }
+    /// Detaches the subtree below this node from its parent, making it a tree
+    /// of its own. Returns `false` if it already was one.
+    pub fn detach(&self, token: &mut GhostToken<'id>) -> bool {
+        let Some(parent) = self.parent(token) else {
+            return false;
+        };
+        self.node.borrow_mut(token).parent = Weak::new();
+        parent.node.borrow_mut(token).children.retain(|child| !child.ptr_eq(self));
+        true
+    }
+    /// Walks the subtree, visiting each node before its children.
+    pub fn pre_order<'a>(&'a self, token: &'a GhostToken<'id>) -> PreOrder<'a, 'id, T> {
+        PreOrder {
+            token,
+            stack: vec![self],
+        }
+    }
+    /// Walks the subtree, visiting each node after its children.
+    pub fn post_order<'a>(&'a self, token: &'a GhostToken<'id>) -> PostOrder<'a, 'id, T> {
+        PostOrder {
+            token,
+            stack: vec![(self, 0)],
+        }
+    }
+    /// Walks the subtree in pre-order, calling `f` with each node's value,
+    /// mutably, and its ancestors' values, up to this node.
+    pub fn visit_mut(&self, token: &mut GhostToken<'id>, mut f: impl FnMut(&mut T, Ancestors<'_, 'id, T>)) {
+        crate::assert_same_layout!(GhostToken<'id>, PathTokens<'_, '_>);
+        // Splitting the token only changes brands. Nothing else can reach the
+        // tree while it is borrowed mutably, and `walk` keeps each node in
+        // one of the two groups at a time.
+        let tokens = unsafe { &mut *(token as *mut GhostToken<'id>).cast::<PathTokens<'_, '_>>() };
+        walk(tokens, self, &mut f);
+    }
+}
+
+/// The token of `GhostTree::visit_mut`, split between the node being visited
+/// and its ancestors.
+struct PathTokens<'current, 'ancestors> {
+    current: GhostToken<'current>,
+    ancestors: GhostToken<'ancestors>,
+}
+
+/// An ancestor on `visit_mut`'s path: its value, its children, and the next
+/// of them to visit.
+type Step<'a, 'id, T> = (&'a T, &'a [GhostTree<'id, T>], usize);
+
+/// The pre-order walk of `visit_mut`, with the brands of the two groups kept
+/// opaque by being parameters.
+///
+/// A node is moved into the group `'current` for its visit, and into
+/// `'ancestors` once the walk goes below it. A pre-order walk visits each
+/// node once, before any of its descendants, and no node is its own
+/// ancestor, so no node is in both groups at once.
+fn walk<'current, 'ancestors, 'id, T>(
+    tokens: &mut PathTokens<'current, 'ancestors>,
+    root: &GhostTree<'id, T>,
+    f: &mut impl FnMut(&mut T, Ancestors<'_, 'id, T>),
+) {
+    let PathTokens { current, ancestors } = tokens;
+    let ancestors: &GhostToken<'ancestors> = ancestors;
+    let mut visit = |tree: &GhostTree<'id, T>, path: &[Step<'_, 'id, T>]| {
+        let node: &GhostCell<'current, _> = unsafe { rebrand(&tree.node) };
+        f(&mut node.borrow_mut(current).value, Ancestors { path });
+    };
+    let enter = |tree| {
+        let node: &GhostCell<'ancestors, _> = unsafe { rebrand(tree) };
+        let node = node.borrow(ancestors);
+        (&node.value, &node.children[..], 0)
+    };
+
+    visit(root, &[]);
+    let mut path: Vec<Step<'_, 'id, T>> = vec![enter(&root.node)];
+    while let Some((_, children, next)) = path.last_mut() {
+        let children: &[GhostTree<'id, T>] = children;
+        let Some(child) = children.get(*next) else {
+            path.pop();
+            continue;
+        };
+        *next += 1;
+        visit(child, &path);
+        path.push(enter(&child.node));
+    }
+}
+
+/// The node in `cell`, in the group `'brand`.
+///
+/// # Safety
+///
+/// While the result is in use, the node must not be reachable through any
+/// other brand.
+unsafe fn rebrand<'a, 'brand, 'id, T>(cell: &'a GhostCell<'id, Node<'id, T>>) -> &'a GhostCell<'brand, Node<'id, T>> {
+    unsafe { &*(cell as *const GhostCell<'id, Node<'id, T>>).cast::<GhostCell<'brand, Node<'id, T>>>() }
+}
+
+impl<'id, T> Clone for GhostTree<'id, T> {
+    fn clone(&self) -> Self {
+        GhostTree {
+            node: Rc::clone(&self.node),
+        }
+    }
+}
+impl<'id, T> fmt::Debug for GhostTree<'id, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("GhostTree").field(&Rc::as_ptr(&self.node)).finish()
+    }
+}
+
+/// A weak handle to a node of a `GhostTree`, like the node's parent link.
+pub struct WeakTree<'id, T> {
+    node: Weak<GhostCell<'id, Node<'id, T>>>,
+}
+impl<'id, T> WeakTree<'id, T> {
+    /// The node, if it is still alive.
+    pub fn upgrade(&self) -> Option<GhostTree<'id, T>> {
+        let node = self.node.upgrade()?;
+        Some(GhostTree { node })
+    }
+}
+impl<'id, T> Clone for WeakTree<'id, T> {
+    fn clone(&self) -> Self {
+        WeakTree {
+            node: Weak::clone(&self.node),
+        }
+    }
+}
+
+/// The ancestors of the node `GhostTree::visit_mut` is visiting.
+pub struct Ancestors<'a, 'id, T> {
+    /// From the root down.
+    path: &'a [Step<'a, 'id, T>],
+}
+impl<'a, 'id, T> Ancestors<'a, 'id, T> {
+    /// The number of ancestors, which is the node's depth.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+    /// Whether the node is where the walk started.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.path.is_empty()
+    }
+    /// The parent's value.
+    #[inline]
+    pub fn parent(&self) -> Option<&'a T> {
+        self.iter().next()
+    }
+    /// The ancestors' values, nearest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &'a T> + ExactSizeIterator + 'a {
+        self.path.iter().rev().map(|&(value, _, _)| value)
+    }
+}
+
+/// A pre-order walk of a `GhostTree`.
+pub struct PreOrder<'a, 'id, T> {
+    token: &'a GhostToken<'id>,
+    stack: Vec<&'a GhostTree<'id, T>>,
+}
+impl<'a, 'id, T> Iterator for PreOrder<'a, 'id, T> {
+    type Item = &'a GhostTree<'id, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.stack.pop()?;
+        self.stack.extend(tree.children(self.token).iter().rev());
+        Some(tree)
+    }
+}
+
+/// A post-order walk of a `GhostTree`.
+pub struct PostOrder<'a, 'id, T> {
+    token: &'a GhostToken<'id>,
+    /// Each node on the path down, with the next of its children to walk.
+    stack: Vec<(&'a GhostTree<'id, T>, usize)>,
+}
+impl<'a, 'id, T> Iterator for PostOrder<'a, 'id, T> {
+    type Item = &'a GhostTree<'id, T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (tree, next) = self.stack.last_mut()?;
+            let tree: &'a GhostTree<'id, T> = tree;
+            match tree.children(self.token).get(*next) {
+                Some(child) => {
+                    *next += 1;
+                    self.stack.push((child, 0));
+                }
+                None => {
+                    self.stack.pop();
+                    return Some(tree);
+                }
+            }
+        }
+    }
+}
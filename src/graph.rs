@@ -0,0 +1,218 @@
+//! Saving and loading graphs of `GhostCell`s which share nodes, behind the
+//! `graph` feature.
+//!
+//! Serializing through `WithToken` copies a cell's contents wherever it is
+//! referenced, so two entities holding the same `&GhostCell` ring come back
+//! with a ring each, and a cycle never finishes. `save` instead numbers every
+//! distinct cell reachable from the roots, in the order they are found, and
+//! records edges as those numbers. `load` allocates one cell per number in an
+//! arena, then links them back up, so the aliasing is the same as before.
+//! `demo-game`'s `tests/graph.rs` does this for entities sharing a ring.
+//!
+//! ```
+//! use demo::{make_guard, GhostCell, GhostToken};
+//! use demo::graph::{self, Arena, GraphNode, SavedGraph};
+//!
+//! type Link<'arena, 'id> = &'arena GhostCell<'id, Thing<'arena, 'id>>;
+//! enum Thing<'arena, 'id> {
+//!     Entity { hp: u32, rings: Vec<Link<'arena, 'id>> },
+//!     Ring { power: u32 },
+//! }
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! enum ThingData {
+//!     Entity { hp: u32 },
+//!     Ring { power: u32 },
+//! }
+//! impl<'arena, 'id> GraphNode<'arena, 'id> for Thing<'arena, 'id> {
+//!     type Data = ThingData;
+//!     fn data(&self) -> ThingData {
+//!         match *self {
+//!             Thing::Entity { hp, .. } => ThingData::Entity { hp },
+//!             Thing::Ring { power } => ThingData::Ring { power },
+//!         }
+//!     }
+//!     fn edges(&self) -> Vec<Option<Link<'arena, 'id>>> {
+//!         match self {
+//!             Thing::Entity { rings, .. } => rings.iter().copied().map(Some).collect(),
+//!             Thing::Ring { .. } => Vec::new(),
+//!         }
+//!     }
+//!     fn from_data(data: ThingData) -> Self {
+//!         match data {
+//!             ThingData::Entity { hp } => Thing::Entity { hp, rings: Vec::new() },
+//!             ThingData::Ring { power } => Thing::Ring { power },
+//!         }
+//!     }
+//!     fn set_edges(&mut self, edges: Vec<Option<Link<'arena, 'id>>>) {
+//!         if let Thing::Entity { rings, .. } = self {
+//!             *rings = edges.into_iter().flatten().collect();
+//!         }
+//!     }
+//! }
+//! fn rings<'a, 'arena, 'id>(thing: Link<'arena, 'id>, token: &'a GhostToken<'id>) -> &'a [Link<'arena, 'id>] {
+//!     match thing.borrow(token) {
+//!         Thing::Entity { rings, .. } => rings,
+//!         Thing::Ring { .. } => &[],
+//!     }
+//! }
+//!
+//! let bytes = {
+//!     make_guard!(token);
+//!     let shared = GhostCell::new(Thing::Ring { power: 3 });
+//!     let a = GhostCell::new(Thing::Entity { hp: 10, rings: vec![&shared] });
+//!     let b = GhostCell::new(Thing::Entity { hp: 20, rings: vec![&shared] });
+//!     graph::save(&[&a, &b], &token).to_bytes().unwrap()
+//! };
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let arena = Arena::new();
+//! let saved = SavedGraph::from_bytes(&bytes).unwrap();
+//! let roots = graph::load(saved, &arena, &mut token).unwrap();
+//! let (a, b) = (rings(roots[0], &token), rings(roots[1], &token));
+//! assert!(core::ptr::eq(a[0], b[0]));
+//! ```
+use std::{collections::HashMap, error::Error, fmt, string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{GhostCell, GhostToken};
+
+pub use typed_arena::Arena;
+
+/// A node in a graph of `GhostCell`s, all belonging to the group `'id` and
+/// allocated in an arena that lives for `'arena`.
+pub trait GraphNode<'arena, 'id>: Sized {
+    /// The contents of the node, without its edges.
+    type Data;
+    fn data(&self) -> Self::Data;
+    /// Every cell this node refers to. `None` stands for an empty slot, and
+    /// is kept so that positions line up when the edges are set again.
+    fn edges(&self) -> Vec<Option<&'arena GhostCell<'id, Self>>>;
+    /// Creates a node with no edges yet.
+    fn from_data(data: Self::Data) -> Self;
+    /// Restores the edges, in the order `edges` returned them.
+    fn set_edges(&mut self, edges: Vec<Option<&'arena GhostCell<'id, Self>>>);
+}
+
+/// A graph with each distinct node stored once, and edges stored as the
+/// position of their target in `nodes`.
+#[derive(Serialize, Deserialize)]
+pub struct SavedGraph<D> {
+    pub nodes: Vec<SavedNode<D>>,
+    pub roots: Vec<usize>,
+}
+/// One node of a `SavedGraph`.
+#[derive(Serialize, Deserialize)]
+pub struct SavedNode<D> {
+    pub data: D,
+    pub edges: Vec<Option<usize>>,
+}
+impl<D: Serialize> SavedGraph<D> {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+    /// Encodes the graph with `postcard`, which writes integers as varints,
+    /// so small graphs take a byte per edge.
+    pub fn to_bytes(&self) -> postcard::Result<Vec<u8>> {
+        postcard::to_allocvec(self)
+    }
+}
+impl<D: DeserializeOwned> SavedGraph<D> {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> postcard::Result<Self> {
+        postcard::from_bytes(bytes)
+    }
+}
+
+/// Numbers every cell reachable from `roots`, and records the graph in terms
+/// of those numbers.
+///
+/// Cells are identified by address, so a cell referenced from several places
+/// is saved once, and cycles are saved as edges back to an earlier node.
+pub fn save<'arena, 'id, N: GraphNode<'arena, 'id>>(
+    roots: &[&'arena GhostCell<'id, N>],
+    token: &GhostToken<'id>,
+) -> SavedGraph<N::Data> {
+    let mut ids = HashMap::new();
+    let mut order = Vec::new();
+    let mut id_of = |cell: &'arena GhostCell<'id, N>, order: &mut Vec<_>| {
+        *ids.entry(cell.as_ptr()).or_insert_with(|| {
+            order.push(cell);
+            order.len() - 1
+        })
+    };
+    let roots = roots.iter().map(|&root| id_of(root, &mut order)).collect();
+    let mut nodes = Vec::new();
+    // `order` grows as new cells are found, so this visits each one once.
+    while let Some(&cell) = order.get(nodes.len()) {
+        let node = cell.borrow(token);
+        let edges = node
+            .edges()
+            .into_iter()
+            .map(|edge| edge.map(|target| id_of(target, &mut order)))
+            .collect();
+        nodes.push(SavedNode {
+            data: node.data(),
+            edges,
+        });
+    }
+    SavedGraph { nodes, roots }
+}
+
+/// Rebuilds a saved graph in `arena`, returning the cells of its roots.
+pub fn load<'arena, 'id, N: GraphNode<'arena, 'id>>(
+    graph: SavedGraph<N::Data>,
+    arena: &'arena Arena<GhostCell<'id, N>>,
+    token: &mut GhostToken<'id>,
+) -> Result<Vec<&'arena GhostCell<'id, N>>, DanglingEdge> {
+    let count = graph.nodes.len();
+    let check = |node: Option<usize>, target| {
+        if target < count {
+            Ok(target)
+        } else {
+            Err(DanglingEdge { node, target })
+        }
+    };
+    let mut edges = Vec::with_capacity(count);
+    let mut cells = Vec::with_capacity(count);
+    for (node, saved) in graph.nodes.into_iter().enumerate() {
+        edges.push(
+            saved
+                .edges
+                .into_iter()
+                .map(|edge| edge.map(|target| check(Some(node), target)).transpose())
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+        let cell: &'arena GhostCell<'id, N> = arena.alloc(GhostCell::new(N::from_data(saved.data)));
+        cells.push(cell);
+    }
+    for (cell, edges) in cells.iter().zip(edges) {
+        let edges = edges.into_iter().map(|edge| edge.map(|target| cells[target])).collect();
+        cell.borrow_mut(token).set_edges(edges);
+    }
+    graph
+        .roots
+        .into_iter()
+        .map(|root| check(None, root).map(|root| cells[root]))
+        .collect()
+}
+
+/// A saved graph had an edge to a node it doesn't contain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DanglingEdge {
+    /// The node the edge was from, or `None` for one of the roots.
+    pub node: Option<usize>,
+    pub target: usize,
+}
+impl fmt::Display for DanglingEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node {
+            Some(node) => write!(f, "node {} has an edge to missing node {}", node, self.target),
+            None => write!(f, "root refers to missing node {}", self.target),
+        }
+    }
+}
+impl Error for DanglingEdge {}
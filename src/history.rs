@@ -0,0 +1,359 @@
+//! Cells that remember every committed state of their group, for undo and
+//! redo.
+//!
+//! A `HistoryToken` stands in for a group's `GhostToken`, and keeps the
+//! group's timeline: the versions committed so far, and which of them is
+//! current. Each `HistoryCell` keeps a snapshot per version it was changed
+//! in, so a version shares the snapshots of every cell it didn't touch with
+//! the versions before it. Borrowing a cell mutably through the token
+//! snapshots it into the version being edited, and `commit` seals that
+//! version.
+//!
+//! `undo`, `redo` and `checkout` only move the token along its timeline, so
+//! they take every cell in the group with them at once.
+//!
+//! A value whose state is in cells of its own, like a struct of `GhostCell`s,
+//! can't be snapshotted by cloning it, and changes made through
+//! `token_mut` to its inner cells would skip the snapshots anyway. Such
+//! values go in a `HistoryVec` instead, which owns the token, takes each
+//! value's state through its `Snapshot` impl on `commit`, and writes it back
+//! on `undo`, `redo` and `checkout`.
+//!
+//! ```
+//! use demo::{make_guard, HistoryCell, HistoryToken};
+//!
+//! #[derive(Clone)]
+//! struct Entity {
+//!     hp: i32,
+//!     x: i32,
+//! }
+//!
+//! make_guard!(token);
+//! let mut token = HistoryToken::new(token);
+//! let hero = HistoryCell::new(Entity { hp: 10, x: 0 });
+//! let goblin = HistoryCell::new(Entity { hp: 3, x: 5 });
+//!
+//! hero.borrow_mut(&mut token).x += 4;
+//! let moved = token.commit();
+//! hero.borrow_mut(&mut token).hp -= 2;
+//! goblin.borrow_mut(&mut token).hp = 0;
+//! token.commit();
+//!
+//! assert!(token.undo());
+//! assert_eq!((hero.borrow(&token).hp, hero.borrow(&token).x, goblin.borrow(&token).hp), (10, 4, 3));
+//! assert!(token.redo());
+//! assert_eq!((hero.borrow(&token).hp, goblin.borrow(&token).hp), (8, 0));
+//! assert!(token.checkout(moved));
+//! assert_eq!(goblin.borrow(&token).hp, 3);
+//! ```
+//!
+//! ```
+//! use demo::{make_guard, GhostCell, HistoryVec};
+//!
+//! make_guard!(token);
+//! let mut hp = HistoryVec::new(token, vec![GhostCell::new(10), GhostCell::new(3)]);
+//! let (cells, token) = hp.split();
+//! *cells[0].borrow_mut(token) -= *cells[1].borrow(token);
+//! hp.commit();
+//! let (cells, token) = hp.split();
+//! *cells[1].borrow_mut(token) = 0;
+//!
+//! assert!(hp.undo());
+//! let (cells, token) = hp.values();
+//! assert_eq!((*cells[0].borrow(token), *cells[1].borrow(token)), (7, 3));
+//! assert!(hp.undo());
+//! assert_eq!(*hp.values().0[0].borrow(hp.values().1), 10);
+//! ```
+use core::fmt;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{GhostCell, GhostToken};
+
+/// A version committed by a `HistoryToken`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(u64);
+
+/// A `GhostToken` which keeps the timeline of versions for its group's
+/// `HistoryCell`s.
+pub struct HistoryToken<'id> {
+    token: GhostToken<'id>,
+    /// Every version that can be checked out, oldest first; the ones after
+    /// `pos` are the ones `redo` would return to.
+    timeline: Vec<u64>,
+    pos: usize,
+    /// The version being edited, which isn't on the timeline until it is
+    /// committed.
+    working: u64,
+    dirty: bool,
+}
+impl<'id> HistoryToken<'id> {
+    /// Wraps the group's token, starting the timeline at the cells' initial
+    /// values.
+    pub fn new(token: GhostToken<'id>) -> Self {
+        HistoryToken {
+            token,
+            timeline: vec![0],
+            pos: 0,
+            working: 1,
+            dirty: false,
+        }
+    }
+    /// Returns the token, forgetting the history.
+    pub fn into_inner(self) -> GhostToken<'id> {
+        self.token
+    }
+    /// The token, for the group's other cells.
+    #[inline]
+    pub fn token(&self) -> &GhostToken<'id> {
+        &self.token
+    }
+    /// The token, mutably, for the group's other cells.
+    #[inline]
+    pub fn token_mut(&mut self) -> &mut GhostToken<'id> {
+        &mut self.token
+    }
+    /// The version checked out, which uncommitted changes are on top of.
+    #[inline]
+    pub fn version(&self) -> Version {
+        Version(self.timeline[self.pos])
+    }
+    /// Whether any cell has been borrowed mutably since the last commit.
+    #[inline]
+    pub fn has_changes(&self) -> bool {
+        self.dirty
+    }
+    /// Seals the changes made since the last commit into a new version, and
+    /// returns it. Any versions that `redo` could have returned to are
+    /// dropped. With no changes, this just returns the current version.
+    pub fn commit(&mut self) -> Version {
+        if self.dirty {
+            self.timeline.truncate(self.pos + 1);
+            self.timeline.push(self.working);
+            self.pos += 1;
+            self.working += 1;
+            self.dirty = false;
+        }
+        self.version()
+    }
+    /// Throws away the uncommitted changes, if there are any, and otherwise
+    /// steps back a version. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.discard() {
+            return true;
+        }
+        if self.pos == 0 {
+            return false;
+        }
+        self.pos -= 1;
+        true
+    }
+    /// Throws away any uncommitted changes, and steps forward to the version
+    /// that was last undone. Returns `false` if there was none.
+    pub fn redo(&mut self) -> bool {
+        self.discard();
+        if self.pos + 1 == self.timeline.len() {
+            return false;
+        }
+        self.pos += 1;
+        true
+    }
+    /// Throws away any uncommitted changes, and checks out `version`. Returns
+    /// `false` if it is no longer on the timeline.
+    pub fn checkout(&mut self, version: Version) -> bool {
+        self.discard();
+        match self.timeline.binary_search(&version.0) {
+            Ok(pos) => {
+                self.pos = pos;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+    /// Abandons the working version, which no cell will show again.
+    fn discard(&mut self) -> bool {
+        let dirty = self.dirty;
+        if dirty {
+            self.working += 1;
+            self.dirty = false;
+        }
+        dirty
+    }
+    /// Whether a snapshot from version `id` can show in the checked-out
+    /// state.
+    fn is_visible(&self, id: u64) -> bool {
+        id == self.working || self.timeline[..=self.pos].binary_search(&id).is_ok()
+    }
+}
+impl<'id> fmt::Debug for HistoryToken<'id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistoryToken")
+            .field("version", &self.version())
+            .field("versions", &self.timeline.len())
+            .field("has_changes", &self.dirty)
+            .finish()
+    }
+}
+
+/// A cell belonging to a `HistoryToken`'s group, which keeps a snapshot of
+/// its value for each version it changed in.
+pub struct HistoryCell<'id, T> {
+    /// Oldest first, each tagged with the version it was taken in.
+    snapshots: GhostCell<'id, Vec<(u64, T)>>,
+}
+impl<'id, T> HistoryCell<'id, T> {
+    /// Creates a cell whose value is `value` as far back as the timeline
+    /// goes.
+    pub fn new(value: T) -> Self {
+        HistoryCell {
+            snapshots: GhostCell::new(vec![(0, value)]),
+        }
+    }
+    /// The value in the checked-out version, with any uncommitted changes.
+    pub fn borrow<'a>(&'a self, token: &'a HistoryToken<'id>) -> &'a T {
+        let snapshots = self.snapshots.borrow(&token.token);
+        // The first snapshot is in every version.
+        let (_, value) = snapshots.iter().rev().find(|(id, _)| token.is_visible(*id)).unwrap();
+        value
+    }
+    /// The value in the version being edited, mutably. Its first mutable
+    /// borrow since the last commit takes a snapshot for that version.
+    pub fn borrow_mut<'a>(&'a self, token: &'a mut HistoryToken<'id>) -> &'a mut T
+    where
+        T: Clone,
+    {
+        token.dirty = true;
+        let snapshots = self.snapshots.borrow_mut(&mut token.token);
+        // Snapshots from versions that were undone and then committed over
+        // can never show again.
+        snapshots.retain(|(id, _)| *id == token.working || token.timeline.binary_search(id).is_ok());
+        if snapshots.last().is_none_or(|(id, _)| *id != token.working) {
+            let pos = snapshots.iter().rposition(|(id, _)| token.timeline[..=token.pos].binary_search(id).is_ok()).unwrap();
+            let value = snapshots[pos].1.clone();
+            snapshots.push((token.working, value));
+        }
+        &mut snapshots.last_mut().unwrap().1
+    }
+    /// The number of snapshots the cell is keeping.
+    pub fn snapshots(&self, token: &HistoryToken<'id>) -> usize {
+        self.snapshots.borrow(&token.token).len()
+    }
+}
+
+/// A value whose state is kept in cells of the group, which a `HistoryVec`
+/// takes snapshots of and writes back.
+pub trait Snapshot<'id> {
+    /// The state on its own, outside of any cell.
+    type State: Clone + PartialEq;
+    /// Reads the value's state out of its cells.
+    fn snapshot(&self, token: &GhostToken<'id>) -> Self::State;
+    /// Writes `state` back into the value's cells.
+    fn restore(&self, state: Self::State, token: &mut GhostToken<'id>);
+}
+impl<'id, T: Clone + PartialEq> Snapshot<'id> for GhostCell<'id, T> {
+    type State = T;
+    fn snapshot(&self, token: &GhostToken<'id>) -> T {
+        self.borrow(token).clone()
+    }
+    fn restore(&self, state: T, token: &mut GhostToken<'id>) {
+        *self.borrow_mut(token) = state;
+    }
+}
+
+/// Values whose state is in the group's cells, together with the
+/// `HistoryToken` for that group.
+///
+/// Owning the token means the values can only be changed while `split` lends
+/// it out, so no change escapes the history. `commit` snapshots each value
+/// that changed into a `HistoryCell` of its own, and moving along the
+/// timeline writes the snapshots back into the values' cells.
+pub struct HistoryVec<'id, T: Snapshot<'id>> {
+    token: HistoryToken<'id>,
+    values: Vec<T>,
+    states: Vec<HistoryCell<'id, T::State>>,
+    /// Whether the token has been lent out since the values were last
+    /// snapshotted or restored.
+    dirty: bool,
+}
+impl<'id, T: Snapshot<'id>> HistoryVec<'id, T> {
+    /// Takes ownership of the group's token, starting the timeline at the
+    /// values' current states.
+    pub fn new(token: GhostToken<'id>, values: Vec<T>) -> Self {
+        let states = values.iter().map(|value| HistoryCell::new(value.snapshot(&token))).collect();
+        HistoryVec {
+            token: HistoryToken::new(token),
+            values,
+            states,
+            dirty: false,
+        }
+    }
+    /// The values, with the token to read their cells.
+    #[inline]
+    pub fn values(&self) -> (&[T], &GhostToken<'id>) {
+        (&self.values, self.token.token())
+    }
+    /// Lends out the token alongside the values, to change them. The changes
+    /// are part of the next commit.
+    #[inline]
+    pub fn split(&mut self) -> (&[T], &mut GhostToken<'id>) {
+        self.dirty = true;
+        (&self.values, self.token.token_mut())
+    }
+    /// The version checked out, which uncommitted changes are on top of.
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.token.version()
+    }
+    /// Whether the token has been lent out since the last commit.
+    #[inline]
+    pub fn has_changes(&self) -> bool {
+        self.dirty
+    }
+    /// Snapshots every value whose state changed into a new version, and
+    /// returns it, as `HistoryToken::commit` does.
+    pub fn commit(&mut self) -> Version {
+        if self.dirty {
+            for (value, cell) in self.values.iter().zip(&self.states) {
+                let state = value.snapshot(self.token.token());
+                if *cell.borrow(&self.token) != state {
+                    *cell.borrow_mut(&mut self.token) = state;
+                }
+            }
+            self.dirty = false;
+        }
+        self.token.commit()
+    }
+    /// Throws away the uncommitted changes, if there are any, and otherwise
+    /// steps back a version. Returns `false` if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let undone = self.dirty || self.token.undo();
+        self.restore();
+        undone
+    }
+    /// Throws away any uncommitted changes, and steps forward to the version
+    /// that was last undone. Returns `false` if there was none.
+    pub fn redo(&mut self) -> bool {
+        let redone = self.token.redo();
+        self.restore();
+        redone
+    }
+    /// Throws away any uncommitted changes, and checks out `version`. Returns
+    /// `false` if it is no longer on the timeline.
+    pub fn checkout(&mut self, version: Version) -> bool {
+        let found = self.token.checkout(version);
+        self.restore();
+        found
+    }
+    /// Returns the values and the token, forgetting the history.
+    pub fn into_inner(self) -> (Vec<T>, GhostToken<'id>) {
+        (self.values, self.token.into_inner())
+    }
+    /// Writes the checked-out version's states back into the values.
+    fn restore(&mut self) {
+        for (value, cell) in self.values.iter().zip(&self.states) {
+            let state = cell.borrow(&self.token).clone();
+            value.restore(state, self.token.token_mut());
+        }
+        self.dirty = false;
+    }
+}
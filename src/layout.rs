@@ -0,0 +1,94 @@
+//! Compile-time checks for the layouts that pointer casts rely on.
+//!
+//! A cast between two different types, like opening an `Entity` as an
+//! `OpenEntity`, is only sound while the two are laid out the same.
+//! `assert_same_layout!` checks that when the crate is compiled, so that a
+//! change to either type that breaks a cast is a compile error instead of
+//! undefined behaviour. These casts are checked:
+//!
+//! - `GhostCell::as_slice_of_cells` and `TypeCell::as_slice_of_cells`, `T`
+//!   against its cell;
+//! - `GhostTree::visit_mut`, the token against the two it is split into;
+//! - `#[derive(BrandedStruct)]`, a struct against its open counterpart, field
+//!   by field, and `#[derive(BrandedEnum)]`, the token against the variant
+//!   tokens;
+//! - in `demo-game`, `token_as_entity1_mut` and `World::open`, the token
+//!   against an `EntityAccess` and, for the former, `Entity` against
+//!   `OpenEntity`, and `Column::borrow` and `borrow_mut`, a component against
+//!   its cell.
+//!
+//! The rest need no check, because the layouts can't differ:
+//!
+//! - casts that only change brands, like `RtCell::try_as_ghost`,
+//!   `GhostCell::project_variant` or `entity_cast_group_mut`, are between one
+//!   type at two lifetimes, and lifetimes don't affect layout;
+//! - `GhostCell::from_mut`, `TypeCell::from_mut` and `GhostPinCell::project`
+//!   cast from a `T` that may be unsized, which has no size to assert on; the
+//!   cells are `repr(transparent)` over `UnsafeCell<T>`, which is itself
+//!   over `T`, and that is the guarantee.
+//!
+//! ```
+//! use demo::{assert_same_layout, GhostCell};
+//!
+//! #[repr(C)]
+//! struct Potion<'content> {
+//!     doses: GhostCell<'content, u8>,
+//!     strength: GhostCell<'content, i32>,
+//! }
+//! #[repr(C)]
+//! struct OpenPotion<'doses, 'strength> {
+//!     doses: GhostCell<'doses, u8>,
+//!     strength: GhostCell<'strength, i32>,
+//! }
+//!
+//! const _: () = assert_same_layout!(Potion<'static>, OpenPotion<'static, 'static>; doses, strength);
+//! ```
+//!
+//! Had `OpenPotion::strength` been a `GhostCell<'strength, u32>`, with the
+//! same size, alignment and offset, the field's type would have failed to
+//! match instead.
+
+/// Fails to compile unless two types have the same size and alignment, and
+/// each listed field has the same type and offset in both.
+///
+/// It expands to a `const` block, so on concrete types it can be used as
+/// `const _: () = assert_same_layout!(..);` and is checked by `cargo check`.
+/// Inside a function it can name the function's type parameters, and is
+/// checked when the function is compiled, for each type it is instantiated
+/// with.
+///
+/// The fields' types are compared brands and all, so to compare a type with
+/// its open counterpart, compare both at `'static`; brands don't change a
+/// layout.
+#[macro_export]
+macro_rules! assert_same_layout {
+    ($a:ty, $b:ty $(; $($field:ident),* $(,)?)?) => {
+        const {
+            ::core::assert!(
+                ::core::mem::size_of::<$a>() == ::core::mem::size_of::<$b>(),
+                ::core::concat!("`", ::core::stringify!($a), "` and `", ::core::stringify!($b), "` differ in size"),
+            );
+            ::core::assert!(
+                ::core::mem::align_of::<$a>() == ::core::mem::align_of::<$b>(),
+                ::core::concat!("`", ::core::stringify!($a), "` and `", ::core::stringify!($b), "` differ in alignment"),
+            );
+            $($(
+                ::core::assert!(
+                    ::core::mem::offset_of!($a, $field) == ::core::mem::offset_of!($b, $field),
+                    ::core::concat!(
+                        "`", ::core::stringify!($a), "::", ::core::stringify!($field), "` and `",
+                        ::core::stringify!($b), "::", ::core::stringify!($field), "` are at different offsets",
+                    ),
+                );
+                // never called: only here for the field types to be unified
+                let _ = |a: *const $a, b: *const $b| unsafe {
+                    $crate::layout::same_type(&raw const (*a).$field, &raw const (*b).$field)
+                };
+            )*)?
+        }
+    };
+}
+
+#[doc(hidden)]
+#[inline(always)]
+pub const fn same_type<T: ?Sized>(_: *const T, _: *const T) {}
@@ -0,0 +1,498 @@
+//! Intrusive linked lists whose nodes live in an arena and link to each other
+//! through `GhostCell`s.
+//!
+//! A node embeds its own links, so adding it to a list allocates nothing: the
+//! nodes are allocated once, all together, in whatever holds them for
+//! `'arena` (a `Vec`, a slice, a `typed_arena::Arena`), and the lists only
+//! ever store `&'arena` references to them. The links belong to the group
+//! `'id`, so relinking goes through the `GhostToken<'id>`, like every other
+//! mutation in the group.
+//!
+//! `List` is doubly linked, and can unlink any node in O(1) given a reference
+//! to it. `SList` is singly linked, and half the size per node.
+//!
+//! ```
+//! use demo::{list_arena::{Linked, Links, List}, make_guard, GhostCell};
+//!
+//! struct Unit<'arena, 'id> {
+//!     hp: GhostCell<'id, i32>,
+//!     links: Links<'arena, 'id, Self>,
+//! }
+//! impl<'arena, 'id> Linked<'arena, 'id> for Unit<'arena, 'id> {
+//!     fn links(&self) -> &Links<'arena, 'id, Self> {
+//!         &self.links
+//!     }
+//! }
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let units: Vec<Unit> = (0..5).map(|hp| Unit { hp: GhostCell::new(hp), links: Links::new() }).collect();
+//! let update = List::new();
+//! for unit in &units {
+//!     update.push_back(unit, &mut token);
+//! }
+//!
+//! // Each update, the dead leave the list and the rest take a hit.
+//! let mut cursor = update.cursor_front_mut(&mut token);
+//! while let Some(unit) = cursor.current() {
+//!     if *unit.hp.borrow(cursor.token()) <= 0 {
+//!         cursor.remove_current();
+//!     } else {
+//!         *unit.hp.borrow_mut(cursor.token()) -= 1;
+//!         cursor.move_next();
+//!     }
+//! }
+//! assert!(update.unlink(&units[2], &mut token));
+//!
+//! let hps: Vec<i32> = update.iter(&token).map(|unit| *unit.hp.borrow(&token)).collect();
+//! assert_eq!(hps, [0, 2, 3]);
+//! ```
+use core::ptr;
+
+use crate::{GhostCell, GhostToken};
+
+/// The links a node of a `List` embeds.
+pub struct Links<'arena, 'id, T> {
+    prev: GhostCell<'id, Option<&'arena T>>,
+    next: GhostCell<'id, Option<&'arena T>>,
+}
+impl<'arena, 'id, T> Links<'arena, 'id, T> {
+    /// Links for a node that isn't in a list yet.
+    #[inline]
+    pub const fn new() -> Self {
+        Links {
+            prev: GhostCell::new(None),
+            next: GhostCell::new(None),
+        }
+    }
+    /// The node before this one in its list.
+    #[inline]
+    pub fn prev(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.prev.borrow(token)
+    }
+    /// The node after this one in its list.
+    #[inline]
+    pub fn next(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.next.borrow(token)
+    }
+}
+impl<'arena, 'id, T> Default for Links<'arena, 'id, T> {
+    fn default() -> Self {
+        Links::new()
+    }
+}
+
+/// A node that can be put in a `List`.
+///
+/// A node may be in at most one list at a time. Pushing a node that is in
+/// another list of the same group, or unlinking it from a list it isn't in,
+/// is caught where it can be in O(1), and otherwise garbles the lists
+/// involved, though never memory.
+pub trait Linked<'arena, 'id>: Sized {
+    /// The node's links.
+    fn links(&self) -> &Links<'arena, 'id, Self>;
+}
+
+/// A doubly-linked intrusive list, belonging to the group `'id`.
+pub struct List<'arena, 'id, T> {
+    head: GhostCell<'id, Option<&'arena T>>,
+    tail: GhostCell<'id, Option<&'arena T>>,
+}
+impl<'arena, 'id, T: Linked<'arena, 'id>> List<'arena, 'id, T> {
+    /// Creates an empty list.
+    #[inline]
+    pub const fn new() -> Self {
+        List {
+            head: GhostCell::new(None),
+            tail: GhostCell::new(None),
+        }
+    }
+    /// Whether the list has no nodes.
+    #[inline]
+    pub fn is_empty(&self, token: &GhostToken<'id>) -> bool {
+        self.head.borrow(token).is_none()
+    }
+    /// The first node.
+    #[inline]
+    pub fn front(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.head.borrow(token)
+    }
+    /// The last node.
+    #[inline]
+    pub fn back(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.tail.borrow(token)
+    }
+    /// Whether `node` is in this list, assuming it is in at most one.
+    pub fn contains(&self, node: &T, token: &GhostToken<'id>) -> bool {
+        let links = node.links();
+        links.prev(token).is_some() || links.next(token).is_some() || self.front(token).is_some_and(|head| ptr::eq(head, node))
+    }
+    /// Adds `node` to the front of the list.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is already in this list, or visibly in another.
+    pub fn push_front(&self, node: &'arena T, token: &mut GhostToken<'id>) {
+        self.link(None, node, self.front(token), token);
+    }
+    /// Adds `node` to the back of the list.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is already in this list, or visibly in another.
+    pub fn push_back(&self, node: &'arena T, token: &mut GhostToken<'id>) {
+        self.link(self.back(token), node, None, token);
+    }
+    /// Adds `node` just after `at`, which must be in this list.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is already in this list, or visibly in another.
+    pub fn insert_after(&self, at: &'arena T, node: &'arena T, token: &mut GhostToken<'id>) {
+        self.link(Some(at), node, at.links().next(token), token);
+    }
+    /// Adds `node` just before `at`, which must be in this list.
+    ///
+    /// # Panics
+    ///
+    /// If `node` is already in this list, or visibly in another.
+    pub fn insert_before(&self, at: &'arena T, node: &'arena T, token: &mut GhostToken<'id>) {
+        self.link(at.links().prev(token), node, Some(at), token);
+    }
+    /// Removes and returns the first node.
+    pub fn pop_front(&self, token: &mut GhostToken<'id>) -> Option<&'arena T> {
+        let node = self.front(token)?;
+        self.unlink(node, token);
+        Some(node)
+    }
+    /// Removes and returns the last node.
+    pub fn pop_back(&self, token: &mut GhostToken<'id>) -> Option<&'arena T> {
+        let node = self.back(token)?;
+        self.unlink(node, token);
+        Some(node)
+    }
+    /// Removes `node` from the list in O(1). Returns `false`, and does
+    /// nothing, if it wasn't in the list.
+    pub fn unlink(&self, node: &T, token: &mut GhostToken<'id>) -> bool {
+        if !self.contains(node, token) {
+            return false;
+        }
+        let links = node.links();
+        let prev = links.prev.borrow_mut(token).take();
+        let next = links.next.borrow_mut(token).take();
+        match prev {
+            Some(prev) => *prev.links().next.borrow_mut(token) = next,
+            None => *self.head.borrow_mut(token) = next,
+        }
+        match next {
+            Some(next) => *next.links().prev.borrow_mut(token) = prev,
+            None => *self.tail.borrow_mut(token) = prev,
+        }
+        true
+    }
+    /// Iterates over the nodes, front to back.
+    pub fn iter<'a>(&'a self, token: &'a GhostToken<'id>) -> Iter<'a, 'arena, 'id, T> {
+        Iter {
+            token,
+            front: self.front(token),
+            back: self.back(token),
+        }
+    }
+    /// A cursor at the first node, which relinks nodes through `token`.
+    pub fn cursor_front_mut<'a>(&'a self, token: &'a mut GhostToken<'id>) -> CursorMut<'a, 'arena, 'id, T> {
+        CursorMut {
+            current: self.front(token),
+            list: self,
+            token,
+        }
+    }
+    /// A cursor at the last node, which relinks nodes through `token`.
+    pub fn cursor_back_mut<'a>(&'a self, token: &'a mut GhostToken<'id>) -> CursorMut<'a, 'arena, 'id, T> {
+        CursorMut {
+            current: self.back(token),
+            list: self,
+            token,
+        }
+    }
+    /// Links `node` in between `prev` and `next`, which are adjacent, or the
+    /// ends of the list when `None`.
+    fn link(&self, prev: Option<&'arena T>, node: &'arena T, next: Option<&'arena T>, token: &mut GhostToken<'id>) {
+        assert!(!self.contains(node, token), "node is already in a list");
+        let links = node.links();
+        *links.prev.borrow_mut(token) = prev;
+        *links.next.borrow_mut(token) = next;
+        match prev {
+            Some(prev) => *prev.links().next.borrow_mut(token) = Some(node),
+            None => *self.head.borrow_mut(token) = Some(node),
+        }
+        match next {
+            Some(next) => *next.links().prev.borrow_mut(token) = Some(node),
+            None => *self.tail.borrow_mut(token) = Some(node),
+        }
+    }
+}
+impl<'arena, 'id, T: Linked<'arena, 'id>> Default for List<'arena, 'id, T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+/// An iterator over the nodes of a `List`.
+pub struct Iter<'a, 'arena, 'id, T> {
+    token: &'a GhostToken<'id>,
+    front: Option<&'arena T>,
+    back: Option<&'arena T>,
+}
+impl<'a, 'arena, 'id, T: Linked<'arena, 'id>> Iterator for Iter<'a, 'arena, 'id, T> {
+    type Item = &'arena T;
+    fn next(&mut self) -> Option<&'arena T> {
+        let node = self.front?;
+        if self.back.is_some_and(|back| ptr::eq(back, node)) {
+            (self.front, self.back) = (None, None);
+        } else {
+            self.front = node.links().next(self.token);
+        }
+        Some(node)
+    }
+}
+impl<'a, 'arena, 'id, T: Linked<'arena, 'id>> DoubleEndedIterator for Iter<'a, 'arena, 'id, T> {
+    fn next_back(&mut self) -> Option<&'arena T> {
+        let node = self.back?;
+        if self.front.is_some_and(|front| ptr::eq(front, node)) {
+            (self.front, self.back) = (None, None);
+        } else {
+            self.back = node.links().prev(self.token);
+        }
+        Some(node)
+    }
+}
+
+/// A position in a `List`, holding the token so it can relink as it goes.
+///
+/// Past either end, the cursor is at no node, and moving it again wraps
+/// around to the other end.
+pub struct CursorMut<'a, 'arena, 'id, T> {
+    list: &'a List<'arena, 'id, T>,
+    token: &'a mut GhostToken<'id>,
+    current: Option<&'arena T>,
+}
+impl<'a, 'arena, 'id, T: Linked<'arena, 'id>> CursorMut<'a, 'arena, 'id, T> {
+    /// The node the cursor is at.
+    #[inline]
+    pub fn current(&self) -> Option<&'arena T> {
+        self.current
+    }
+    /// The token, for reaching the nodes' other cells.
+    #[inline]
+    pub fn token(&mut self) -> &mut GhostToken<'id> {
+        self.token
+    }
+    /// Moves to the next node.
+    pub fn move_next(&mut self) {
+        self.current = match self.current {
+            Some(node) => node.links().next(self.token),
+            None => self.list.front(self.token),
+        };
+    }
+    /// Moves to the previous node.
+    pub fn move_prev(&mut self) {
+        self.current = match self.current {
+            Some(node) => node.links().prev(self.token),
+            None => self.list.back(self.token),
+        };
+    }
+    /// Adds `node` after the current one, or at the front if the cursor is
+    /// at no node.
+    pub fn insert_after(&mut self, node: &'arena T) {
+        match self.current {
+            Some(at) => self.list.insert_after(at, node, self.token),
+            None => self.list.push_front(node, self.token),
+        }
+    }
+    /// Adds `node` before the current one, or at the back if the cursor is
+    /// at no node.
+    pub fn insert_before(&mut self, node: &'arena T) {
+        match self.current {
+            Some(at) => self.list.insert_before(at, node, self.token),
+            None => self.list.push_back(node, self.token),
+        }
+    }
+    /// Removes and returns the current node, and moves to the next one.
+    pub fn remove_current(&mut self) -> Option<&'arena T> {
+        let node = self.current?;
+        self.current = node.links().next(self.token);
+        self.list.unlink(node, self.token);
+        Some(node)
+    }
+}
+
+/// The link a node of an `SList` embeds.
+pub struct SLink<'arena, 'id, T> {
+    next: GhostCell<'id, Option<&'arena T>>,
+}
+impl<'arena, 'id, T> SLink<'arena, 'id, T> {
+    /// A link for a node that isn't in a list yet.
+    #[inline]
+    pub const fn new() -> Self {
+        SLink {
+            next: GhostCell::new(None),
+        }
+    }
+    /// The node after this one in its list.
+    #[inline]
+    pub fn next(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.next.borrow(token)
+    }
+}
+impl<'arena, 'id, T> Default for SLink<'arena, 'id, T> {
+    fn default() -> Self {
+        SLink::new()
+    }
+}
+
+/// A node that can be put in an `SList`, under the same rules as `Linked`.
+pub trait SLinked<'arena, 'id>: Sized {
+    /// The node's link.
+    fn link(&self) -> &SLink<'arena, 'id, Self>;
+}
+
+/// A singly-linked intrusive list, belonging to the group `'id`.
+///
+/// Unlike a `List`, an `SList` can't tell whether a node is in a list: the
+/// last node of any list has no next node, just like one in no list at all.
+/// Adding such a node again makes a cycle, after which walking the list
+/// never ends, so keeping each node in one place is up to the caller.
+pub struct SList<'arena, 'id, T> {
+    head: GhostCell<'id, Option<&'arena T>>,
+}
+impl<'arena, 'id, T: SLinked<'arena, 'id>> SList<'arena, 'id, T> {
+    /// Creates an empty list.
+    #[inline]
+    pub const fn new() -> Self {
+        SList {
+            head: GhostCell::new(None),
+        }
+    }
+    /// Whether the list has no nodes.
+    #[inline]
+    pub fn is_empty(&self, token: &GhostToken<'id>) -> bool {
+        self.head.borrow(token).is_none()
+    }
+    /// The first node.
+    #[inline]
+    pub fn front(&self, token: &GhostToken<'id>) -> Option<&'arena T> {
+        *self.head.borrow(token)
+    }
+    /// Adds `node` to the front of the list.
+    ///
+    /// # Panics
+    ///
+    /// If `node` has a next node or is the front of this list. Any list's
+    /// last node passes unnoticed, unless it is also this list's front.
+    pub fn push_front(&self, node: &'arena T, token: &mut GhostToken<'id>) {
+        let in_list = node.link().next(token).is_some() || self.front(token).is_some_and(|head| ptr::eq(head, node));
+        assert!(!in_list, "node is already in a list");
+        let head = self.head.borrow_mut(token).replace(node);
+        *node.link().next.borrow_mut(token) = head;
+    }
+    /// Removes and returns the first node.
+    pub fn pop_front(&self, token: &mut GhostToken<'id>) -> Option<&'arena T> {
+        let node = self.front(token)?;
+        *self.head.borrow_mut(token) = node.link().next.borrow_mut(token).take();
+        Some(node)
+    }
+    /// Iterates over the nodes, front to back.
+    pub fn iter<'a>(&'a self, token: &'a GhostToken<'id>) -> SIter<'a, 'arena, 'id, T> {
+        SIter {
+            token,
+            next: self.front(token),
+        }
+    }
+    /// A cursor before the first node, which relinks nodes through `token`.
+    pub fn cursor_mut<'a>(&'a self, token: &'a mut GhostToken<'id>) -> SCursorMut<'a, 'arena, 'id, T> {
+        SCursorMut {
+            list: self,
+            token,
+            current: None,
+        }
+    }
+}
+impl<'arena, 'id, T: SLinked<'arena, 'id>> Default for SList<'arena, 'id, T> {
+    fn default() -> Self {
+        SList::new()
+    }
+}
+
+/// An iterator over the nodes of an `SList`.
+pub struct SIter<'a, 'arena, 'id, T> {
+    token: &'a GhostToken<'id>,
+    next: Option<&'arena T>,
+}
+impl<'a, 'arena, 'id, T: SLinked<'arena, 'id>> Iterator for SIter<'a, 'arena, 'id, T> {
+    type Item = &'arena T;
+    fn next(&mut self) -> Option<&'arena T> {
+        let node = self.next?;
+        self.next = node.link().next(self.token);
+        Some(node)
+    }
+}
+
+/// A position in an `SList`, holding the token so it can relink as it goes.
+///
+/// A singly-linked node can't reach the one before it, so the cursor edits
+/// the list just after its position, which starts out before the first node.
+pub struct SCursorMut<'a, 'arena, 'id, T> {
+    list: &'a SList<'arena, 'id, T>,
+    token: &'a mut GhostToken<'id>,
+    current: Option<&'arena T>,
+}
+impl<'a, 'arena, 'id, T: SLinked<'arena, 'id>> SCursorMut<'a, 'arena, 'id, T> {
+    /// The node the cursor is at, or `None` before the first.
+    #[inline]
+    pub fn current(&self) -> Option<&'arena T> {
+        self.current
+    }
+    /// The node after the cursor.
+    #[inline]
+    pub fn peek_next(&self) -> Option<&'arena T> {
+        match self.current {
+            Some(node) => node.link().next(self.token),
+            None => self.list.front(self.token),
+        }
+    }
+    /// The token, for reaching the nodes' other cells.
+    #[inline]
+    pub fn token(&mut self) -> &mut GhostToken<'id> {
+        self.token
+    }
+    /// Moves to the next node, returning `false` at the end of the list.
+    pub fn move_next(&mut self) -> bool {
+        let next = self.peek_next();
+        self.current = next.or(self.current);
+        next.is_some()
+    }
+    /// Adds `node` after the cursor.
+    ///
+    /// # Panics
+    ///
+    /// If `node` has a next node or is the cursor's own node, or as
+    /// `SList::push_front` before the first node.
+    pub fn insert_after(&mut self, node: &'arena T) {
+        let Some(at) = self.current else {
+            return self.list.push_front(node, self.token);
+        };
+        let in_list = node.link().next(self.token).is_some() || ptr::eq(node, at);
+        assert!(!in_list, "node is already in a list");
+        let next = at.link().next.borrow_mut(self.token).replace(node);
+        *node.link().next.borrow_mut(self.token) = next;
+    }
+    /// Removes and returns the node after the cursor.
+    pub fn remove_next(&mut self) -> Option<&'arena T> {
+        let Some(at) = self.current else {
+            return self.list.pop_front(self.token);
+        };
+        let node = at.link().next(self.token)?;
+        *at.link().next.borrow_mut(self.token) = node.link().next.borrow_mut(self.token).take();
+        Some(node)
+    }
+}
@@ -0,0 +1,135 @@
+//! Cells whose contents can be pinned, for `!Unpin` values like futures and
+//! intrusive nodes.
+//!
+//! `GhostCell` can't hand out a `Pin<&mut T>`: a `Pin<&GhostCell<'id, T>>`
+//! derefs to a plain `&GhostCell<'id, T>`, and `borrow_mut` or `swap` on that
+//! could move the value after it was pinned. A `GhostPinCell` only hands out
+//! `&mut T` when `T: Unpin`, so once the cell is pinned, so is its value, and
+//! `borrow_pin_mut` lends it out as a `Pin<&mut T>` instead.
+//!
+//! ```
+//! use std::{future::Future, pin::pin, task::{Context, Poll, Waker}};
+//! use demo::{make_guard, GhostCell, GhostPinCell};
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let hp = GhostCell::new(10);
+//! // An entity's behaviour, stored in the group like any other state.
+//! let regen = async { std::future::ready(5).await };
+//! let behaviour = pin!(GhostPinCell::new(regen));
+//!
+//! let mut cx = Context::from_waker(Waker::noop());
+//! let heal = loop {
+//!     if let Poll::Ready(heal) = behaviour.as_ref().borrow_pin_mut(&mut token).poll(&mut cx) {
+//!         break heal;
+//!     }
+//! };
+//! *hp.borrow_mut(&mut token) += heal;
+//! assert_eq!(*hp.borrow(&token), 15);
+//! ```
+use core::{cell::UnsafeCell, marker::PhantomData, pin::Pin};
+
+use crate::GhostToken;
+
+type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A `GhostCell` whose value stays put once the cell is pinned.
+#[derive(Default)]
+#[repr(transparent)]
+pub struct GhostPinCell<'id, T: ?Sized> {
+    _marker: InvariantLifetime<'id>,
+    value: UnsafeCell<T>,
+}
+/// As for `GhostCell`, the token has to come along to reach the contents.
+unsafe impl<'id, T: ?Sized + Send> Send for GhostPinCell<'id, T> {}
+/// As for `GhostCell`, the token has to come along to reach the contents.
+unsafe impl<'id, T: ?Sized + Send + Sync> Sync for GhostPinCell<'id, T> {}
+
+impl<'id, T> GhostPinCell<'id, T> {
+    /// Creates a new cell that belongs to the token at lifetime `'id`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        GhostPinCell {
+            _marker: PhantomData,
+            value: UnsafeCell::new(value),
+        }
+    }
+    /// Unwraps the value. A pinned cell can't be moved, so this only works
+    /// on one that never was.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+impl<'id, T: ?Sized> GhostPinCell<'id, T> {
+    /// Returns a raw pointer to the underlying data in this cell.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+    /// Get an immutable reference to the item, for as long as the token is
+    /// borrowed immutably.
+    #[inline]
+    pub fn borrow<'a>(&'a self, _token: &'a GhostToken<'id>) -> &'a T {
+        // As in `GhostCell::borrow`.
+        unsafe { &*self.value.get() }
+    }
+    /// Get a mutable reference to the item, for as long as the token is
+    /// borrowed mutably. Only for `Unpin` values, which pinning can't hold in
+    /// place anyway.
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, _token: &'a mut GhostToken<'id>) -> &'a mut T
+    where
+        T: Unpin,
+    {
+        // As in `GhostCell::borrow_mut`.
+        unsafe { &mut *self.value.get() }
+    }
+    /// Get a pinned reference to the item of a pinned cell, for as long as
+    /// the token is borrowed immutably.
+    #[inline]
+    pub fn borrow_pin<'a>(self: Pin<&'a Self>, token: &'a GhostToken<'id>) -> Pin<&'a T> {
+        // Pinning the cell pins its value, which is never moved out of it.
+        unsafe { Pin::new_unchecked(self.get_ref().borrow(token)) }
+    }
+    /// Get a pinned mutable reference to the item of a pinned cell, for as
+    /// long as the token is borrowed mutably.
+    #[inline]
+    pub fn borrow_pin_mut<'a>(self: Pin<&'a Self>, _token: &'a mut GhostToken<'id>) -> Pin<&'a mut T> {
+        // As in `GhostCell::borrow_mut`, and the value is pinned because the
+        // cell is, and `&mut T` is never handed out for a `!Unpin` `T`.
+        unsafe { Pin::new_unchecked(&mut *self.get_ref().value.get()) }
+    }
+    /// Get a mutable reference to the item, which needs no token since the
+    /// cell is borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T
+    where
+        T: Unpin,
+    {
+        self.value.get_mut()
+    }
+    /// Projects a pinned cell onto a cell of one of its value's fields, so
+    /// that the field can be borrowed, pinned, on its own.
+    ///
+    /// `field` is given a pointer to the value, and must return a pointer to
+    /// one of its fields, for example with `&raw mut (*value).field`.
+    ///
+    /// # Safety
+    ///
+    /// The field must be pinned structurally: `T` must never move it while
+    /// pinned, its `Drop` impl included, and must only be `Unpin` if the
+    /// field is too.
+    #[inline]
+    pub unsafe fn project<U: ?Sized>(self: Pin<&Self>, field: impl FnOnce(*mut T) -> *mut U) -> Pin<&GhostPinCell<'id, U>> {
+        // `GhostPinCell<'id, U>` is a transparent wrapper around `U`, and the
+        // field lives as long as the cell it is in.
+        unsafe { self.map_unchecked(|cell| &*(field(cell.as_ptr()) as *const GhostPinCell<'id, U>)) }
+    }
+}
+impl<'id, T> From<T> for GhostPinCell<'id, T> {
+    #[inline]
+    fn from(t: T) -> Self {
+        GhostPinCell::new(t)
+    }
+}
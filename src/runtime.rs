@@ -0,0 +1,245 @@
+//! Tokens branded at runtime, for groups that outlive any one scope.
+//!
+//! A `GhostToken<'id>` from `make_guard!` can't be kept in a struct that
+//! outlives the scope that made it, such as a game's world. A `RuntimeToken`
+//! can, since its brand is a unique 64-bit id instead of a lifetime. Each
+//! `RtCell` records the id of the token it was created with, and checks it
+//! on every borrow.
+//!
+//! For code written against `GhostCell`, `RuntimeToken::with_static` lends
+//! the token out as a `GhostToken<'id>` for the length of a closure, and each
+//! cell's id is checked once as it is turned into a `GhostCell<'id, T>`.
+//! Borrows through it are then as free as any other `GhostCell`'s.
+//!
+//! ```
+//! use demo::{GhostCell, GhostToken, RtCell, RuntimeToken};
+//!
+//! struct Inventory {
+//!     token: RuntimeToken,
+//!     gold: RtCell<u32>,
+//!     rings: Vec<RtCell<u32>>,
+//! }
+//!
+//! // Written for any group, with no knowledge of runtime tokens.
+//! fn melt<'id>(ring: &GhostCell<'id, u32>, gold: &GhostCell<'id, u32>, token: &mut GhostToken<'id>) {
+//!     let power = std::mem::take(ring.borrow_mut(token));
+//!     *gold.borrow_mut(token) += power;
+//! }
+//!
+//! let token = RuntimeToken::new();
+//! let mut inventory = Inventory {
+//!     gold: RtCell::new(&token, 5),
+//!     rings: vec![RtCell::new(&token, 3), RtCell::new(&token, 4)],
+//!     token,
+//! };
+//!
+//! *inventory.rings[0].borrow_mut(&mut inventory.token) += 1;
+//! inventory.token.with_static(|token, brand| {
+//!     let gold = inventory.gold.as_ghost(brand);
+//!     for ring in &inventory.rings {
+//!         melt(ring.as_ghost(brand), gold, token);
+//!     }
+//! });
+//! assert_eq!(*inventory.gold.borrow(&inventory.token), 13);
+//!
+//! // A cell only opens with the token it was created with.
+//! let other = RuntimeToken::new();
+//! assert!(inventory.gold.try_borrow(&other).is_err());
+//! ```
+use core::{
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{make_guard, GhostCell, GhostToken};
+
+type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A token whose brand is a unique id, chosen when it is created.
+///
+/// Like a `GhostToken`, it can't be cloned, so borrowing it mutably proves
+/// that nothing else is borrowing any of its cells.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RuntimeToken {
+    id: u64,
+}
+impl RuntimeToken {
+    /// Creates a token with an id no other token has had.
+    ///
+    /// # Panics
+    ///
+    /// If all 2^64 ids have been used.
+    pub fn new() -> Self {
+        let id = NEXT_ID
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| id.checked_add(1))
+            .expect("ran out of runtime token ids");
+        RuntimeToken { id }
+    }
+    /// The id that this token's cells are branded with.
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    /// Runs `f` with a `GhostToken` standing in for this token, and a `Brand`
+    /// that turns this token's `RtCell`s into `GhostCell`s of the same group.
+    ///
+    /// This token stays mutably borrowed throughout, so the cells can't be
+    /// reached through it at the same time.
+    pub fn with_static<R>(&mut self, f: impl for<'id> FnOnce(&mut GhostToken<'id>, Brand<'id>) -> R) -> R {
+        make_guard!(token);
+        let mut token = token;
+        f(
+            &mut token,
+            Brand {
+                _marker: PhantomData,
+                id: self.id,
+            },
+        )
+    }
+}
+impl Default for RuntimeToken {
+    fn default() -> Self {
+        RuntimeToken::new()
+    }
+}
+
+/// Proof, inside `RuntimeToken::with_static`, that the group `'id` is the
+/// token with a given id.
+#[derive(Clone, Copy, Debug)]
+pub struct Brand<'id> {
+    _marker: InvariantLifetime<'id>,
+    id: u64,
+}
+impl<'id> Brand<'id> {
+    /// The id of the token that `'id` stands for.
+    #[inline]
+    pub fn id(self) -> u64 {
+        self.id
+    }
+}
+
+/// A cell belonging to the `RuntimeToken` it was created with.
+pub struct RtCell<T> {
+    owner: u64,
+    // `'static` is a placeholder, which only matters once the cell has been
+    // cast to a `with_static` scope's brand. A `GhostToken<'static>` would
+    // open it unchecked, and none can be made: `make_guard!` brands its token
+    // with the lifetime of a local borrow, and `generativity::Guard::new` is
+    // unsafe. The casts in this workspace that put a token under another
+    // brand either bind it in a higher-ranked closure, where it can't be
+    // `'static` (`BrandedEnum::split_variants`, and in `demo-game`,
+    // `World::open` and `token_as_entity1_mut`), or keep the token inside a
+    // private generic function (`GhostTree::visit_mut`'s `walk`).
+    // `tests/ui/split_token_static.rs` and demo-game's
+    // `tests/ui/world_open_static.rs` check the public ones.
+    cell: GhostCell<'static, T>,
+}
+impl<T> RtCell<T> {
+    /// Creates a cell belonging to `token`.
+    #[inline]
+    pub fn new(token: &RuntimeToken, value: T) -> Self {
+        RtCell {
+            owner: token.id,
+            cell: GhostCell::new(value),
+        }
+    }
+    /// The id of the token this cell belongs to.
+    #[inline]
+    pub fn owner(&self) -> u64 {
+        self.owner
+    }
+    /// Unwraps the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.cell.into_inner()
+    }
+    /// Returns a mutable reference to the value, which needs no token since
+    /// the cell is borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.cell.get_mut()
+    }
+    /// Borrows the value immutably, if `token` is the cell's owner.
+    #[inline]
+    pub fn try_borrow<'a>(&'a self, token: &'a RuntimeToken) -> Result<&'a T, WrongToken> {
+        self.check(token.id)?;
+        // The owner is borrowed immutably for `'a`, as in `GhostCell::borrow`.
+        Ok(unsafe { &*self.cell.as_ptr() })
+    }
+    /// Borrows the value mutably, if `token` is the cell's owner.
+    #[inline]
+    pub fn try_borrow_mut<'a>(&'a self, token: &'a mut RuntimeToken) -> Result<&'a mut T, WrongToken> {
+        self.check(token.id)?;
+        // The owner is borrowed mutably for `'a`, as in `GhostCell::borrow_mut`.
+        Ok(unsafe { &mut *self.cell.as_ptr() })
+    }
+    /// Borrows the value immutably.
+    ///
+    /// # Panics
+    ///
+    /// If `token` is not the cell's owner.
+    #[inline]
+    #[track_caller]
+    pub fn borrow<'a>(&'a self, token: &'a RuntimeToken) -> &'a T {
+        self.try_borrow(token).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Borrows the value mutably.
+    ///
+    /// # Panics
+    ///
+    /// If `token` is not the cell's owner.
+    #[inline]
+    #[track_caller]
+    pub fn borrow_mut<'a>(&'a self, token: &'a mut RuntimeToken) -> &'a mut T {
+        self.try_borrow_mut(token).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Views the cell as a `GhostCell` of the group `'id`, if `brand` is for
+    /// the cell's owner.
+    #[inline]
+    pub fn try_as_ghost<'id>(&self, brand: Brand<'id>) -> Result<&GhostCell<'id, T>, WrongToken> {
+        self.check(brand.id)?;
+        // Brands are only markers, so this changes nothing but the type. The
+        // owner is borrowed mutably for as long as `'id` lasts, leaving the
+        // `GhostToken<'id>` as the only way into the cell.
+        Ok(unsafe { &*(&self.cell as *const GhostCell<'static, T>).cast::<GhostCell<'id, T>>() })
+    }
+    /// Views the cell as a `GhostCell` of the group `'id`.
+    ///
+    /// # Panics
+    ///
+    /// If `brand` is not for the cell's owner.
+    #[inline]
+    #[track_caller]
+    pub fn as_ghost<'id>(&self, brand: Brand<'id>) -> &GhostCell<'id, T> {
+        self.try_as_ghost(brand).unwrap_or_else(|e| panic!("{}", e))
+    }
+    #[inline]
+    fn check(&self, id: u64) -> Result<(), WrongToken> {
+        if id == self.owner {
+            Ok(())
+        } else {
+            Err(WrongToken {
+                owner: self.owner,
+                found: id,
+            })
+        }
+    }
+}
+
+/// An `RtCell` was opened with a token other than its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongToken {
+    /// The id of the cell's owner.
+    pub owner: u64,
+    /// The id of the token it was opened with.
+    pub found: u64,
+}
+impl fmt::Display for WrongToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cell belongs to runtime token {}, not {}", self.owner, self.found)
+    }
+}
+impl core::error::Error for WrongToken {}
@@ -0,0 +1,149 @@
+//! Serde support, behind the `serde` feature.
+//!
+//! Serializing reads the contents of every cell, so it goes through
+//! `WithToken`, which is `Serialize` for any `SerializeWithToken` type.
+//! Deserializing only ever creates new cells, which needs no token at all:
+//! `GhostCell<'id, T>` is `Deserialize` for any brand, and the brand of the
+//! result is whichever one it is used with.
+//!
+//! ```
+//! use demo::{make_guard, GhostCell, PartialEqWithToken, SerializeWithToken, WithTokenExt};
+//!
+//! #[derive(SerializeWithToken, serde::Deserialize, PartialEqWithToken)]
+//! struct Bag<'id> {
+//!     hp: GhostCell<'id, i32>,
+//!     items: GhostCell<'id, Vec<GhostCell<'id, Item>>>,
+//! }
+//! #[derive(SerializeWithToken, serde::Deserialize, PartialEqWithToken)]
+//! enum Item {
+//!     Ring { power: u32 },
+//!     Coin,
+//! }
+//!
+//! make_guard!(token);
+//! let bag = Bag {
+//!     hp: GhostCell::new(-1),
+//!     items: GhostCell::new(vec![GhostCell::new(Item::Ring { power: 2 }), GhostCell::new(Item::Coin)]),
+//! };
+//!
+//! let json = serde_json::to_string(&bag.with(&token)).unwrap();
+//! assert_eq!(json, r#"{"hp":-1,"items":[{"Ring":{"power":2}},"Coin"]}"#);
+//!
+//! let loaded: Bag<'_> = serde_json::from_str(&json).unwrap();
+//! assert!(loaded.with(&token) == bag.with(&token));
+//! ```
+//!
+//! Where nothing else determines the brand, `CellSeed` takes it from a token:
+//!
+//! ```
+//! use demo::{make_guard, serialize::CellSeed};
+//! use serde::de::DeserializeSeed;
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let mut json = serde_json::Deserializer::from_str("[1, 2, 3]");
+//! let cell = CellSeed::<Vec<u8>>::new(&token).deserialize(&mut json).unwrap();
+//! cell.borrow_mut(&mut token).push(4);
+//! assert_eq!(cell.borrow(&token), &[1, 2, 3, 4]);
+//! ```
+//!
+//! `#[derive(SerializeWithToken)]` produces the same format as
+//! `#[derive(Serialize)]` would, but ignores `#[serde(..)]` attributes.
+use core::marker::PhantomData;
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use serde::{
+    de::{Deserialize, DeserializeSeed, Deserializer},
+    ser::{Serialize, Serializer},
+};
+
+use crate::{GhostCell, GhostToken, WithToken};
+
+#[doc(hidden)]
+pub use serde as __serde;
+
+type InvariantLifetime<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// `Serialize`, for types which need a `GhostToken<'id>` to be read.
+pub trait SerializeWithToken<'id> {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error>;
+}
+
+impl<'a, 'id, T: ?Sized + SerializeWithToken<'id>> Serialize for WithToken<'a, 'id, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().serialize(self.token(), serializer)
+    }
+}
+
+impl<'id, T: SerializeWithToken<'id>> SerializeWithToken<'id> for GhostCell<'id, T> {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        self.borrow(token).serialize(token, serializer)
+    }
+}
+impl<'de, 'id, T: Deserialize<'de>> Deserialize<'de> for GhostCell<'id, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(GhostCell::new)
+    }
+}
+
+/// Deserializes a `GhostCell` belonging to an existing token.
+pub struct CellSeed<'id, T> {
+    _marker: PhantomData<(InvariantLifetime<'id>, fn() -> T)>,
+}
+impl<'id, T> CellSeed<'id, T> {
+    /// Fixes the brand of the cell to `token`'s. The token is only used for
+    /// its type, since creating a cell never needs access to the others.
+    #[inline]
+    pub const fn new(_token: &GhostToken<'id>) -> Self {
+        CellSeed { _marker: PhantomData }
+    }
+}
+impl<'de, 'id, T: Deserialize<'de>> DeserializeSeed<'de> for CellSeed<'id, T> {
+    type Value = GhostCell<'id, T>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        GhostCell::deserialize(deserializer)
+    }
+}
+
+/// Types which contain no cells, and so ignore the token.
+macro_rules! impl_without_token {
+    ($($t:ty),*) => {$(
+        impl<'id> SerializeWithToken<'id> for $t {
+            fn serialize<S: Serializer>(&self, _: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+                Serialize::serialize(self, serializer)
+            }
+        }
+    )*};
+}
+impl_without_token!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_without_token!(f32, f64, bool, char, str, String, ());
+
+impl<'id, T: ?Sized + SerializeWithToken<'id>> SerializeWithToken<'id> for &T {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(token, serializer)
+    }
+}
+impl<'id, T: ?Sized + SerializeWithToken<'id>> SerializeWithToken<'id> for Box<T> {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(token, serializer)
+    }
+}
+impl<'id, T: SerializeWithToken<'id>> SerializeWithToken<'id> for [T] {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|x| WithToken::new(x, token)))
+    }
+}
+impl<'id, T: SerializeWithToken<'id>> SerializeWithToken<'id> for Vec<T> {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        self[..].serialize(token, serializer)
+    }
+}
+impl<'id, T: SerializeWithToken<'id>> SerializeWithToken<'id> for Option<T> {
+    fn serialize<S: Serializer>(&self, token: &GhostToken<'id>, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(x) => serializer.serialize_some(&WithToken::new(x, token)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
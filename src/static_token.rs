@@ -0,0 +1,206 @@
+//! Tokens branded by a type, for groups that last the whole program.
+//!
+//! A `GhostToken<'id>` brand has to be threaded through every signature that
+//! touches the group, the way `Entity<'r>` is. A `StaticToken<Tag>` is
+//! branded by a marker type instead, so a global registry can be named
+//! without any lifetime at all. Only one `StaticToken` per tag exists at a
+//! time, which is checked when it is created, against a flag that
+//! `static_tag!` declares alongside the tag.
+//!
+//! ```
+//! use demo::{static_tag, StaticToken, TypeCell};
+//!
+//! static_tag! {
+//!     /// The group of every ring in the catalog.
+//!     struct Catalog;
+//! }
+//! struct Ring {
+//!     power: u32,
+//! }
+//! static RINGS: [TypeCell<Catalog, Ring>; 2] = [TypeCell::new(Ring { power: 1 }), TypeCell::new(Ring { power: 4 })];
+//!
+//! fn strongest(token: &StaticToken<Catalog>) -> &'static TypeCell<Catalog, Ring> {
+//!     RINGS.iter().max_by_key(|ring| ring.borrow(token).power).unwrap()
+//! }
+//!
+//! let mut token = StaticToken::<Catalog>::new();
+//! strongest(&token).borrow_mut(&mut token).power += 1;
+//! assert_eq!(RINGS[1].borrow(&token).power, 5);
+//!
+//! // Only one token per tag at a time.
+//! assert!(StaticToken::<Catalog>::try_new().is_none());
+//! drop(token);
+//! assert!(StaticToken::<Catalog>::try_new().is_some());
+//! ```
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A marker type that brands a `StaticToken`.
+///
+/// # Safety
+///
+/// `taken` must return a flag used by no other type. Use `static_tag!`
+/// rather than implementing this by hand.
+pub unsafe trait Tag: 'static {
+    /// Whether a `StaticToken<Self>` currently exists.
+    fn taken() -> &'static AtomicBool;
+}
+
+/// Declares a unit struct to use as a `Tag`, along with its flag.
+#[macro_export]
+macro_rules! static_tag {
+    ($(#[$attr:meta])* $vis:vis struct $name:ident;) => {
+        $(#[$attr])*
+        $vis struct $name;
+        unsafe impl $crate::static_token::Tag for $name {
+            fn taken() -> &'static ::core::sync::atomic::AtomicBool {
+                static TAKEN: ::core::sync::atomic::AtomicBool = ::core::sync::atomic::AtomicBool::new(false);
+                &TAKEN
+            }
+        }
+    };
+}
+
+/// The token for every `TypeCell<Tag, _>`.
+///
+/// At most one exists per tag at a time; dropping it lets another be made.
+pub struct StaticToken<Tag: self::Tag> {
+    _marker: PhantomData<fn() -> Tag>,
+}
+impl<Tag: self::Tag> StaticToken<Tag> {
+    /// Creates the token for `Tag`, unless it already exists.
+    pub fn try_new() -> Option<Self> {
+        let taken = Tag::taken().swap(true, Ordering::Acquire);
+        (!taken).then_some(StaticToken { _marker: PhantomData })
+    }
+    /// Creates the token for `Tag`.
+    ///
+    /// # Panics
+    ///
+    /// If the token for `Tag` already exists.
+    #[track_caller]
+    pub fn new() -> Self {
+        match Self::try_new() {
+            Some(token) => token,
+            None => panic!("a StaticToken<{}> already exists", core::any::type_name::<Tag>()),
+        }
+    }
+}
+impl<Tag: self::Tag> Default for StaticToken<Tag> {
+    #[track_caller]
+    fn default() -> Self {
+        StaticToken::new()
+    }
+}
+impl<Tag: self::Tag> Drop for StaticToken<Tag> {
+    fn drop(&mut self) {
+        Tag::taken().store(false, Ordering::Release);
+    }
+}
+impl<Tag: self::Tag> fmt::Debug for StaticToken<Tag> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StaticToken<{}>", core::any::type_name::<Tag>())
+    }
+}
+
+/// A cell whose contents are reached through the `StaticToken<Tag>`, with
+/// the same API as `GhostCell`.
+#[repr(transparent)]
+pub struct TypeCell<Tag, T: ?Sized> {
+    _marker: PhantomData<fn() -> Tag>,
+    value: UnsafeCell<T>,
+}
+/// As for `GhostCell`, the token has to come along to reach the contents.
+unsafe impl<Tag, T: ?Sized + Send> Send for TypeCell<Tag, T> {}
+/// As for `GhostCell`, the token has to come along to reach the contents.
+unsafe impl<Tag, T: ?Sized + Send + Sync> Sync for TypeCell<Tag, T> {}
+
+impl<Tag: self::Tag, T> TypeCell<Tag, T> {
+    /// Creates a new cell belonging to `StaticToken<Tag>`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        TypeCell {
+            _marker: PhantomData,
+            value: UnsafeCell::new(value),
+        }
+    }
+    /// Unwraps the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+    /// Get an immutable reference to the item, for as long as the token is
+    /// borrowed immutably.
+    #[inline]
+    pub fn borrow<'a>(&'a self, _token: &'a StaticToken<Tag>) -> &'a T {
+        // There is only one token for `Tag`, and it is borrowed immutably, so
+        // nobody can be holding a mutable reference into any of its cells.
+        unsafe { &*self.value.get() }
+    }
+    /// Get a mutable reference to the item, for as long as the token is
+    /// borrowed mutably.
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, _token: &'a mut StaticToken<Tag>) -> &'a mut T {
+        // There is only one token for `Tag`, and it is borrowed mutably, so
+        // nobody else can be holding a reference into any of its cells.
+        unsafe { &mut *self.value.get() }
+    }
+    /// Get mutable references to two items at once, or `None` if `a` and `b`
+    /// are the same cell.
+    #[inline]
+    pub fn borrow_mut_twice<'a>(
+        a: &'a Self,
+        b: &'a Self,
+        _token: &'a mut StaticToken<Tag>,
+    ) -> Option<(&'a mut T, &'a mut T)> {
+        if core::ptr::eq(a, b) {
+            return None;
+        }
+        // Distinct cells don't overlap, as in `GhostCell::borrow_mut_twice`.
+        unsafe { Some((&mut *a.value.get(), &mut *b.value.get())) }
+    }
+    /// Swaps the values of two cells. Swapping a cell with itself does
+    /// nothing.
+    #[inline]
+    pub fn swap(&self, other: &Self, token: &mut StaticToken<Tag>) {
+        if let Some((a, b)) = TypeCell::borrow_mut_twice(self, other, token) {
+            core::mem::swap(a, b);
+        }
+    }
+}
+impl<Tag: self::Tag, T: ?Sized> TypeCell<Tag, T> {
+    /// Returns a raw pointer to the underlying data in this cell.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+    /// Returns a mutable reference to the underlying data, which needs no
+    /// token since the cell is borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+    /// Returns a `&mut TypeCell<Tag, T>` from a `&mut T`.
+    #[inline]
+    pub fn from_mut(t: &mut T) -> &mut Self {
+        // As with `GhostCell::from_mut`, `repr(transparent)` is the guarantee.
+        unsafe { &mut *(t as *mut T as *mut Self) }
+    }
+}
+impl<Tag: self::Tag, T> TypeCell<Tag, [T]> {
+    /// Returns a `&[TypeCell<Tag, T>]` from a `&TypeCell<Tag, [T]>`.
+    #[inline]
+    pub fn as_slice_of_cells(&self) -> &[TypeCell<Tag, T>] {
+        crate::assert_same_layout!(T, TypeCell<Tag, T>);
+        unsafe { &*(self as *const TypeCell<Tag, [T]> as *const [TypeCell<Tag, T>]) }
+    }
+}
+impl<Tag: self::Tag, T: Default> Default for TypeCell<Tag, T> {
+    fn default() -> Self {
+        TypeCell::new(T::default())
+    }
+}
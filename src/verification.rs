@@ -0,0 +1,111 @@
+//! Kani harnesses for the borrow rules, run with `cargo kani`.
+//!
+//! The paper's Coq proof covers `new`, `borrow`, `borrow_mut`, `from_mut` and
+//! `as_slice_of_cells`, assuming the lifetimes do their job. These harnesses
+//! check the runtime half of each argument, for symbolic indices and values:
+//! that references handed out for distinct cells never overlap, and that the
+//! layout casts land on the same addresses as the values they reinterpret.
+//! Whether a brand can be forged is a question about lifetimes, which Kani
+//! erases, so it is left to the type system here and in `demo-game`.
+//!
+//! **Unverified:** no `cargo kani` run has checked these harnesses, and CI
+//! doesn't build them either, since nothing sets `cfg(kani)`. Until Kani has
+//! proved them they are proof sketches, not verification.
+use core::{mem, ptr};
+
+use crate::{make_guard, GhostCell};
+
+const N: usize = 4;
+
+/// Whether the `size_of::<T>()` bytes at `a` and `b` overlap.
+fn overlaps<T>(a: *const T, b: *const T) -> bool {
+    let (a, b) = (a as usize, b as usize);
+    a < b + mem::size_of::<T>() && b < a + mem::size_of::<T>()
+}
+
+fn any_index() -> usize {
+    let i: usize = kani::any();
+    kani::assume(i < N);
+    i
+}
+
+/// A write through `borrow_mut` to a cell picked by index lands in that cell
+/// and leaves the rest of the array alone.
+#[kani::proof]
+fn borrow_mut_writes_only_its_cell() {
+    make_guard!(token);
+    let mut token = token;
+    let values: [u32; N] = kani::any();
+    let cells = values.map(GhostCell::new);
+    let (i, x) = (any_index(), kani::any());
+    *cells[i].borrow_mut(&mut token) = x;
+    for (j, cell) in cells.iter().enumerate() {
+        let expected = if j == i { x } else { values[j] };
+        assert!(*cell.borrow(&token) == expected);
+    }
+    let shared = cells[i].borrow(&token);
+    assert!(ptr::eq(shared, cells[i].as_ptr()));
+}
+
+/// `borrow_mut_twice` refuses exactly the pairs that would alias, and the
+/// pairs it accepts never overlap.
+#[kani::proof]
+fn borrow_mut_twice_never_aliases() {
+    make_guard!(token);
+    let mut token = token;
+    let cells: [GhostCell<'_, u32>; N] = kani::any::<[u32; N]>().map(GhostCell::new);
+    let (i, j) = (any_index(), any_index());
+    match GhostCell::borrow_mut_twice(&cells[i], &cells[j], &mut token) {
+        Some((a, b)) => {
+            assert!(i != j);
+            assert!(!overlaps(a as *const u32, b as *const u32));
+            *a = 1;
+            *b = 2;
+        }
+        None => assert!(i == j),
+    }
+    if i != j {
+        assert!(*cells[i].borrow(&token) == 1 && *cells[j].borrow(&token) == 2);
+    }
+}
+
+/// `swap` exchanges two cells' values, does nothing to a cell swapped with
+/// itself, and touches no other cell.
+#[kani::proof]
+fn swap_exchanges_values() {
+    make_guard!(token);
+    let mut token = token;
+    let values: [u32; N] = kani::any();
+    let cells = values.map(GhostCell::new);
+    let (i, j) = (any_index(), any_index());
+    cells[i].swap(&cells[j], &mut token);
+    for (k, cell) in cells.iter().enumerate() {
+        let expected = if k == i {
+            values[j]
+        } else if k == j {
+            values[i]
+        } else {
+            values[k]
+        };
+        assert!(*cell.borrow(&token) == expected);
+    }
+}
+
+/// `from_mut` and `as_slice_of_cells` are pointer casts, so every element's
+/// cell must sit exactly where the element does, with the same length, size
+/// and alignment.
+#[kani::proof]
+fn slice_of_cells_layout() {
+    make_guard!(token);
+    let mut token = token;
+    let mut values: [u32; N] = kani::any();
+    let base = values.as_ptr() as usize;
+    let cells = GhostCell::from_mut(&mut values[..]).as_slice_of_cells();
+    assert!(cells.len() == N);
+    assert!(mem::size_of::<GhostCell<'_, u32>>() == mem::size_of::<u32>());
+    assert!(mem::align_of::<GhostCell<'_, u32>>() == mem::align_of::<u32>());
+    let (i, x) = (any_index(), kani::any());
+    assert!(cells[i].as_ptr() as usize == base + i * mem::size_of::<u32>());
+    *cells[i].borrow_mut(&mut token) = x;
+    assert!(values[i] == x);
+}
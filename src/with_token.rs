@@ -0,0 +1,347 @@
+//! Formatting and comparison for values whose contents live in `GhostCell`s.
+//!
+//! A `GhostCell` cannot implement `Debug` or `PartialEq` itself, since reading
+//! it requires its token. Instead, the `*WithToken` traits here take the token
+//! as an extra argument, and `WithToken` pairs a value with the token so that
+//! it implements the standard traits:
+//!
+//! ```
+//! use demo::{make_guard, DebugWithToken, GhostCell, PartialEqWithToken, WithTokenExt};
+//!
+//! #[derive(DebugWithToken, PartialEqWithToken)]
+//! struct Bag<'id> {
+//!     owner: &'static str,
+//!     hp: GhostCell<'id, i32>,
+//!     items: GhostCell<'id, Vec<GhostCell<'id, Item>>>,
+//! }
+//! #[derive(DebugWithToken, PartialEqWithToken)]
+//! struct Item {
+//!     weight: u32,
+//! }
+//!
+//! make_guard!(token);
+//! let mut token = token;
+//! let bag = Bag {
+//!     owner: "knight",
+//!     hp: GhostCell::new(10),
+//!     items: GhostCell::new(vec![GhostCell::new(Item { weight: 2 })]),
+//! };
+//! *bag.hp.borrow_mut(&mut token) -= 1;
+//!
+//! assert_eq!(
+//!     format!("{:?}", bag.with(&token)),
+//!     r#"Bag { owner: "knight", hp: 9, items: [Item { weight: 2 }] }"#,
+//! );
+//! assert_eq!(bag.hp.with(&token).to_string(), "9");
+//! assert!(bag.hp.with(&token) > GhostCell::new(8).with(&token));
+//! ```
+//!
+//! The derives (`DebugWithToken`, `PartialEqWithToken`, `OrdWithToken` and
+//! `HashWithToken`) implement the traits field by field, for any struct or
+//! enum whose fields implement them.
+use core::{cmp::Ordering, fmt, hash::Hasher};
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use crate::{GhostCell, GhostToken};
+
+/// `Debug`, for types which need a `GhostToken<'id>` to be read.
+pub trait DebugWithToken<'id> {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+/// `Display`, for types which need a `GhostToken<'id>` to be read.
+pub trait DisplayWithToken<'id> {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+/// `PartialEq`, for types which need a `GhostToken<'id>` to be read.
+pub trait PartialEqWithToken<'id> {
+    fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool;
+}
+/// `Ord`, for types which need a `GhostToken<'id>` to be read.
+///
+/// Implementing this asserts that `eq` is an equivalence relation, so
+/// `WithToken` is also `Eq` for these types.
+pub trait OrdWithToken<'id>: PartialEqWithToken<'id> {
+    fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering;
+}
+/// `Hash`, for types which need a `GhostToken<'id>` to be read.
+pub trait HashWithToken<'id> {
+    fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H);
+}
+
+/// A value paired with the token needed to read it, which implements the
+/// standard formatting, comparison and hashing traits.
+pub struct WithToken<'a, 'id, T: ?Sized> {
+    value: &'a T,
+    token: &'a GhostToken<'id>,
+}
+impl<'a, 'id, T: ?Sized> WithToken<'a, 'id, T> {
+    #[inline]
+    pub const fn new(value: &'a T, token: &'a GhostToken<'id>) -> Self {
+        WithToken { value, token }
+    }
+    /// The value without its token.
+    #[inline]
+    pub const fn value(&self) -> &'a T {
+        self.value
+    }
+    /// The token the value is read with.
+    #[inline]
+    pub const fn token(&self) -> &'a GhostToken<'id> {
+        self.token
+    }
+}
+impl<'a, 'id, T: ?Sized> Clone for WithToken<'a, 'id, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, 'id, T: ?Sized> Copy for WithToken<'a, 'id, T> {}
+impl<'a, 'id, T: ?Sized + DebugWithToken<'id>> fmt::Debug for WithToken<'a, 'id, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(self.token, f)
+    }
+}
+impl<'a, 'id, T: ?Sized + DisplayWithToken<'id>> fmt::Display for WithToken<'a, 'id, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(self.token, f)
+    }
+}
+impl<'a, 'id, T: ?Sized + PartialEqWithToken<'id>> PartialEq for WithToken<'a, 'id, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value.eq(other.value, self.token)
+    }
+}
+impl<'a, 'id, T: ?Sized + OrdWithToken<'id>> Eq for WithToken<'a, 'id, T> {}
+impl<'a, 'id, T: ?Sized + OrdWithToken<'id>> PartialOrd for WithToken<'a, 'id, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<'a, 'id, T: ?Sized + OrdWithToken<'id>> Ord for WithToken<'a, 'id, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(other.value, self.token)
+    }
+}
+impl<'a, 'id, T: ?Sized + HashWithToken<'id>> core::hash::Hash for WithToken<'a, 'id, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(self.token, state)
+    }
+}
+
+/// Adds `with` to every type, to pair it with a token.
+pub trait WithTokenExt {
+    #[inline]
+    fn with<'a, 'id>(&'a self, token: &'a GhostToken<'id>) -> WithToken<'a, 'id, Self> {
+        WithToken::new(self, token)
+    }
+}
+impl<T: ?Sized> WithTokenExt for T {}
+
+impl<'id, T: DebugWithToken<'id>> DebugWithToken<'id> for GhostCell<'id, T> {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.borrow(token).fmt(token, f)
+    }
+}
+impl<'id, T: DisplayWithToken<'id>> DisplayWithToken<'id> for GhostCell<'id, T> {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.borrow(token).fmt(token, f)
+    }
+}
+impl<'id, T: PartialEqWithToken<'id>> PartialEqWithToken<'id> for GhostCell<'id, T> {
+    fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+        self.borrow(token).eq(other.borrow(token), token)
+    }
+}
+impl<'id, T: OrdWithToken<'id>> OrdWithToken<'id> for GhostCell<'id, T> {
+    fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering {
+        self.borrow(token).cmp(other.borrow(token), token)
+    }
+}
+impl<'id, T: HashWithToken<'id>> HashWithToken<'id> for GhostCell<'id, T> {
+    fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H) {
+        self.borrow(token).hash(token, state)
+    }
+}
+
+/// Types which contain no cells, and so ignore the token.
+macro_rules! impl_without_token {
+    (@partial $($t:ty),*) => {$(
+        impl<'id> DebugWithToken<'id> for $t {
+            fn fmt(&self, _: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(self, f)
+            }
+        }
+        impl<'id> DisplayWithToken<'id> for $t {
+            fn fmt(&self, _: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(self, f)
+            }
+        }
+        impl<'id> PartialEqWithToken<'id> for $t {
+            fn eq(&self, other: &Self, _: &GhostToken<'id>) -> bool {
+                self == other
+            }
+        }
+    )*};
+    ($($t:ty),*) => {$(
+        impl_without_token!(@partial $t);
+        impl<'id> OrdWithToken<'id> for $t {
+            fn cmp(&self, other: &Self, _: &GhostToken<'id>) -> Ordering {
+                Ord::cmp(self, other)
+            }
+        }
+        impl<'id> HashWithToken<'id> for $t {
+            fn hash<H: Hasher>(&self, _: &GhostToken<'id>, state: &mut H) {
+                core::hash::Hash::hash(self, state)
+            }
+        }
+    )*};
+}
+impl_without_token!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_without_token!(bool, char, str);
+#[cfg(feature = "alloc")]
+impl_without_token!(String);
+impl_without_token!(@partial f32, f64);
+
+impl<'id> DebugWithToken<'id> for () {
+    fn fmt(&self, _: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("()")
+    }
+}
+impl<'id> PartialEqWithToken<'id> for () {
+    fn eq(&self, _: &Self, _: &GhostToken<'id>) -> bool {
+        true
+    }
+}
+impl<'id> OrdWithToken<'id> for () {
+    fn cmp(&self, _: &Self, _: &GhostToken<'id>) -> Ordering {
+        Ordering::Equal
+    }
+}
+impl<'id> HashWithToken<'id> for () {
+    fn hash<H: Hasher>(&self, _: &GhostToken<'id>, _: &mut H) {}
+}
+
+/// Pointer-like types, which defer to their pointee.
+macro_rules! impl_deref {
+    ($([$($g:tt)*] $t:ty),*) => {$(
+        impl<'id, $($g)*> DebugWithToken<'id> for $t where T: DebugWithToken<'id> {
+            fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                (**self).fmt(token, f)
+            }
+        }
+        impl<'id, $($g)*> DisplayWithToken<'id> for $t where T: DisplayWithToken<'id> {
+            fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                (**self).fmt(token, f)
+            }
+        }
+        impl<'id, $($g)*> PartialEqWithToken<'id> for $t where T: PartialEqWithToken<'id> {
+            fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+                (**self).eq(&**other, token)
+            }
+        }
+        impl<'id, $($g)*> OrdWithToken<'id> for $t where T: OrdWithToken<'id> {
+            fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering {
+                (**self).cmp(&**other, token)
+            }
+        }
+        impl<'id, $($g)*> HashWithToken<'id> for $t where T: HashWithToken<'id> {
+            fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H) {
+                (**self).hash(token, state)
+            }
+        }
+    )*};
+}
+impl_deref!(['a, T: ?Sized] &'a T);
+#[cfg(feature = "alloc")]
+impl_deref!([T: ?Sized] Box<T>);
+
+impl<'id, T: DebugWithToken<'id>> DebugWithToken<'id> for [T] {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(|x| WithToken::new(x, token))).finish()
+    }
+}
+impl<'id, T: PartialEqWithToken<'id>> PartialEqWithToken<'id> for [T] {
+    fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.eq(b, token))
+    }
+}
+impl<'id, T: OrdWithToken<'id>> OrdWithToken<'id> for [T] {
+    fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering {
+        self.iter()
+            .map(|x| WithToken::new(x, token))
+            .cmp(other.iter().map(|x| WithToken::new(x, token)))
+    }
+}
+impl<'id, T: HashWithToken<'id>> HashWithToken<'id> for [T] {
+    fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H) {
+        state.write_usize(self.len());
+        for x in self {
+            x.hash(token, state);
+        }
+    }
+}
+
+/// Sequences, which defer to their slice.
+macro_rules! impl_as_slice {
+    ($([$($g:tt)*] $t:ty),*) => {$(
+        impl<'id, $($g)*> DebugWithToken<'id> for $t where T: DebugWithToken<'id> {
+            fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                DebugWithToken::fmt(&self[..], token, f)
+            }
+        }
+        impl<'id, $($g)*> PartialEqWithToken<'id> for $t where T: PartialEqWithToken<'id> {
+            fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+                PartialEqWithToken::eq(&self[..], &other[..], token)
+            }
+        }
+        impl<'id, $($g)*> OrdWithToken<'id> for $t where T: OrdWithToken<'id> {
+            fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering {
+                OrdWithToken::cmp(&self[..], &other[..], token)
+            }
+        }
+        impl<'id, $($g)*> HashWithToken<'id> for $t where T: HashWithToken<'id> {
+            fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H) {
+                HashWithToken::hash(&self[..], token, state)
+            }
+        }
+    )*};
+}
+impl_as_slice!([T, const N: usize] [T; N]);
+#[cfg(feature = "alloc")]
+impl_as_slice!([T] Vec<T>);
+
+impl<'id, T: DebugWithToken<'id>> DebugWithToken<'id> for Option<T> {
+    fn fmt(&self, token: &GhostToken<'id>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(x) => f.debug_tuple("Some").field(&WithToken::new(x, token)).finish(),
+            None => f.write_str("None"),
+        }
+    }
+}
+impl<'id, T: PartialEqWithToken<'id>> PartialEqWithToken<'id> for Option<T> {
+    fn eq(&self, other: &Self, token: &GhostToken<'id>) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.eq(b, token),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+impl<'id, T: OrdWithToken<'id>> OrdWithToken<'id> for Option<T> {
+    fn cmp(&self, other: &Self, token: &GhostToken<'id>) -> Ordering {
+        match (self, other) {
+            (Some(a), Some(b)) => a.cmp(b, token),
+            (a, b) => Ord::cmp(&a.is_some(), &b.is_some()),
+        }
+    }
+}
+impl<'id, T: HashWithToken<'id>> HashWithToken<'id> for Option<T> {
+    fn hash<H: Hasher>(&self, token: &GhostToken<'id>, state: &mut H) {
+        state.write_u8(self.is_some() as u8);
+        if let Some(x) = self {
+            x.hash(token, state);
+        }
+    }
+}
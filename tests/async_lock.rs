@@ -0,0 +1,131 @@
+//! `AsyncGhostLock` on a single-threaded executor, polled by hand where the
+//! order of events matters.
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::pin,
+    task::{Context, Poll},
+};
+
+use demo::{make_guard, AsyncGhostLock, GhostCell};
+use futures::{
+    executor::{block_on, LocalPool},
+    future::join3,
+    task::{noop_waker_ref, LocalSpawnExt},
+};
+
+/// Gives the other tasks a turn, like `tokio::task::yield_now`.
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+fn poll<F: Future>(future: std::pin::Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(noop_waker_ref()))
+}
+
+/// Tasks get the token in the order they asked for it, and each keeps it
+/// across its `.await`s until it is done.
+#[test]
+fn tasks_are_served_in_order() {
+    make_guard!(token);
+    let lock = AsyncGhostLock::new(token);
+    let log = GhostCell::new(Vec::new());
+    let task = |name: char| {
+        let (lock, log) = (&lock, &log);
+        async move {
+            let mut token = lock.lock().await;
+            log.borrow_mut(&mut token).push(name);
+            yield_now().await;
+            log.borrow_mut(&mut token).push(name);
+        }
+    };
+    block_on(join3(task('a'), task('b'), task('c')));
+    assert_eq!(log.into_inner(), ['a', 'a', 'b', 'b', 'c', 'c']);
+}
+
+/// An unlock hands the token to the oldest waiter, even if another task asks
+/// for it before that waiter is polled again.
+#[test]
+fn no_barging() {
+    make_guard!(token);
+    let lock = AsyncGhostLock::new(token);
+    let guard = lock.try_lock().unwrap();
+    let mut first = pin!(lock.lock());
+    assert!(poll(first.as_mut()).is_pending());
+    drop(guard);
+    assert!(lock.try_lock().is_none());
+    let mut late = pin!(lock.lock());
+    assert!(poll(late.as_mut()).is_pending());
+    let Poll::Ready(guard) = poll(first.as_mut()) else {
+        panic!("the first waiter should have the token");
+    };
+    assert!(poll(late.as_mut()).is_pending());
+    drop(guard);
+    assert!(poll(late.as_mut()).is_ready());
+}
+
+/// Dropping a waiter, whether still queued or already handed the token,
+/// neither loses the token nor skips anyone.
+#[test]
+fn dropped_waiters_pass_the_token_on() {
+    make_guard!(token);
+    let lock = AsyncGhostLock::new(token);
+    let guard = lock.try_lock().unwrap();
+    let mut queued = Box::pin(lock.lock());
+    let mut granted = Box::pin(lock.lock());
+    let mut last = pin!(lock.lock());
+    for future in [queued.as_mut(), granted.as_mut(), last.as_mut()] {
+        assert!(poll(future).is_pending());
+    }
+    drop(queued);
+    drop(guard);
+    drop(granted);
+    assert!(poll(last.as_mut()).is_ready());
+    assert!(lock.try_lock().is_some());
+}
+
+/// The guard can be held across `.await` in a task spawned on an executor,
+/// here alongside tasks that don't use the lock at all.
+#[test]
+fn guard_held_across_await_on_local_pool() {
+    make_guard!(token);
+    let lock = AsyncGhostLock::new(token);
+    let hp = GhostCell::new(0);
+    let ticks = RefCell::new(0);
+    let mut pool = LocalPool::new();
+    pool.spawner().spawn_local(async {}).unwrap();
+    pool.run_until(async {
+        let mut token = lock.lock().await;
+        for _ in 0..3 {
+            *hp.borrow_mut(&mut token) += 1;
+            *ticks.borrow_mut() += 1;
+            yield_now().await;
+        }
+    });
+    assert_eq!((*hp.borrow(&lock.into_inner()), ticks.into_inner()), (3, 3));
+}
+
+/// Holding the guard across `.await` keeps a future `Send`.
+#[test]
+fn guard_is_send() {
+    fn assert_send<T: Send>(_: &T) {}
+    make_guard!(token);
+    let lock = AsyncGhostLock::new(token);
+    let hp = GhostCell::new(0);
+    let future = async {
+        let mut token = lock.lock().await;
+        yield_now().await;
+        *hp.borrow_mut(&mut token) += 1;
+    };
+    assert_send(&future);
+    block_on(future);
+}
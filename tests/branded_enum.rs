@@ -0,0 +1,57 @@
+//! `#[derive(BrandedEnum)]`: the variants' metadata, and projecting their
+//! cells out under brands of their own.
+use demo::branded_enum::BrandedVariant;
+use demo::{make_guard, BrandedEnum, GhostCell};
+
+#[derive(BrandedEnum)]
+#[branded(variants = ItemVariants)]
+enum Item<'content> {
+    Potion { doses: GhostCell<'content, u8> },
+    MagicScroll(GhostCell<'content, Vec<u32>>),
+    Coin,
+    Key { door: u32, uses: GhostCell<'content, u8> },
+}
+
+#[test]
+fn variants() {
+    let names: Vec<_> = Item::VARIANTS.iter().map(|variant| (variant.name, variant.brand)).collect();
+    assert_eq!(
+        names,
+        [("Potion", Some("potion")), ("MagicScroll", Some("magic_scroll")), ("Coin", None), ("Key", None)]
+    );
+    let mut items = [Item::Coin, Item::MagicScroll(GhostCell::new(vec![])), Item::Key { door: 1, uses: GhostCell::new(2) }];
+    let indices: Vec<_> = items.iter().map(BrandedEnum::variant_index).collect();
+    assert_eq!(indices, [2, 1, 3]);
+    assert_eq!(Item::VARIANTS[3], BrandedVariant { name: "Key", brand: None });
+    let [.., Item::Key { door, uses }] = &mut items else { unreachable!() };
+    assert_eq!((*door, *uses.get_mut()), (1, 2));
+}
+
+#[test]
+fn project_variants() {
+    make_guard!(items_token);
+    let mut items_token = items_token;
+    make_guard!(content);
+    let mut content = content;
+    let potion = GhostCell::new(Item::Potion { doses: GhostCell::new(3) });
+    let scroll = GhostCell::new(Item::MagicScroll(GhostCell::new(vec![1])));
+
+    Item::split_variants(&mut content, |variants| {
+        assert!(potion.project_variant(&items_token, &variants.magic_scroll).is_none());
+        let doses = potion.project_variant(&items_token, &variants.potion).unwrap();
+        let spells = scroll.project_variant(&items_token, &variants.magic_scroll).unwrap();
+        // Each variant's cells open with its own token, so both can be
+        // borrowed mutably at once.
+        let spells = spells.borrow_mut(&mut variants.magic_scroll);
+        *doses.borrow_mut(&mut variants.potion) -= 1;
+        spells.push(2);
+    });
+    let Item::MagicScroll(spells) = scroll.borrow(&items_token) else { unreachable!() };
+    assert_eq!(*spells.borrow(&content), [1, 2]);
+
+    // The items themselves can change once the projections are gone.
+    *potion.borrow_mut(&mut items_token) = Item::Coin;
+    Item::split_variants(&mut content, |variants| {
+        assert!(potion.project_variant(&items_token, &variants.potion).is_none());
+    });
+}
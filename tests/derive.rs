@@ -0,0 +1,136 @@
+//! The `*WithToken` derives on enums, tuple structs, unit variants and
+//! generic types, reading through the cells they contain.
+use std::cmp::Ordering;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use demo::with_token::{HashWithToken, OrdWithToken, PartialEqWithToken};
+use demo::{make_guard, DebugWithToken, GhostCell, GhostToken, HashWithToken, OrdWithToken, PartialEqWithToken, WithTokenExt};
+
+/// Variants compare in the order they are declared, before their fields.
+#[derive(DebugWithToken, PartialEqWithToken, OrdWithToken, HashWithToken)]
+enum Rank<'id> {
+    Low,
+    Mid(GhostCell<'id, u32>),
+    High { level: u32, bonus: GhostCell<'id, u32> },
+}
+
+#[derive(DebugWithToken, PartialEqWithToken, OrdWithToken, HashWithToken)]
+struct Pair<'id>(u32, GhostCell<'id, u32>);
+
+#[derive(DebugWithToken, PartialEqWithToken, OrdWithToken, HashWithToken)]
+struct Tagged<'id, T> {
+    tag: u8,
+    value: GhostCell<'id, T>,
+}
+
+fn hash<'id, T: HashWithToken<'id>>(value: &T, token: &GhostToken<'id>) -> u64 {
+    let mut state = DefaultHasher::new();
+    value.hash(token, &mut state);
+    state.finish()
+}
+
+/// What hashing `parts` one after the other gives.
+fn hash_of(parts: impl FnOnce(&mut DefaultHasher)) -> u64 {
+    let mut state = DefaultHasher::new();
+    parts(&mut state);
+    state.finish()
+}
+
+#[test]
+fn enum_ordering() {
+    make_guard!(token);
+    let mut token = token;
+    let (low, mid1, mid2) = (Rank::Low, Rank::Mid(GhostCell::new(1)), Rank::Mid(GhostCell::new(2)));
+    let high = |level, bonus| Rank::High { level, bonus: GhostCell::new(bonus) };
+
+    assert_eq!(low.cmp(&Rank::Low, &token), Ordering::Equal);
+    assert_eq!(low.cmp(&mid2, &token), Ordering::Less);
+    assert_eq!(high(0, 0).cmp(&mid2, &token), Ordering::Greater);
+    assert_eq!(mid1.cmp(&mid2, &token), Ordering::Less);
+    // Fields compare in order, each through the token.
+    assert_eq!(high(1, 9).cmp(&high(2, 0), &token), Ordering::Less);
+    assert_eq!(high(1, 9).cmp(&high(1, 3), &token), Ordering::Greater);
+
+    if let Rank::Mid(cell) = &mid1 {
+        *cell.borrow_mut(&mut token) = 5;
+    }
+    assert_eq!(mid1.cmp(&mid2, &token), Ordering::Greater);
+    let top = high(0, 0);
+    let mut ranks = [&mid1, &top, &low, &mid2].map(|rank| rank.with(&token));
+    ranks.sort();
+    assert_eq!(format!("{:?}", ranks), "[Low, Mid(2), Mid(5), High { level: 0, bonus: 0 }]");
+}
+
+/// The `_ => false` arm: different variants are never equal, whatever their
+/// fields, while the same variant compares its cells' contents.
+#[test]
+fn enum_equality() {
+    make_guard!(token);
+    let token = token;
+    assert!(Rank::Low.eq(&Rank::Low, &token));
+    assert!(!Rank::Low.eq(&Rank::Mid(GhostCell::new(0)), &token));
+    assert!(!Rank::Mid(GhostCell::new(0)).eq(&Rank::High { level: 0, bonus: GhostCell::new(0) }, &token));
+    assert!(Rank::Mid(GhostCell::new(4)).eq(&Rank::Mid(GhostCell::new(4)), &token));
+    assert!(!Rank::Mid(GhostCell::new(4)).eq(&Rank::Mid(GhostCell::new(3)), &token));
+}
+
+/// An enum hashes its variant's index before the fields, so variants with
+/// equal fields hash apart; a struct has no tag.
+#[test]
+fn hash_tagging() {
+    make_guard!(token);
+    let token = token;
+    assert_eq!(hash(&Rank::Low, &token), hash_of(|state| state.write_usize(0)));
+    assert_eq!(
+        hash(&Rank::Mid(GhostCell::new(7)), &token),
+        hash_of(|state| {
+            state.write_usize(1);
+            Hash::hash(&7u32, state);
+        }),
+    );
+    assert_eq!(
+        hash(&Rank::High { level: 7, bonus: GhostCell::new(1) }, &token),
+        hash_of(|state| {
+            state.write_usize(2);
+            Hash::hash(&7u32, state);
+            Hash::hash(&1u32, state);
+        }),
+    );
+    assert_ne!(hash(&Rank::Mid(GhostCell::new(0)), &token), hash(&Rank::Low, &token));
+    assert_eq!(
+        hash(&Pair(3, GhostCell::new(4)), &token),
+        hash_of(|state| {
+            Hash::hash(&3u32, state);
+            Hash::hash(&4u32, state);
+        }),
+    );
+}
+
+#[test]
+fn tuple_struct() {
+    make_guard!(token);
+    let mut token = token;
+    let (a, b) = (Pair(1, GhostCell::new(2)), Pair(1, GhostCell::new(3)));
+    assert_eq!(a.cmp(&b, &token), Ordering::Less);
+    assert!(!a.eq(&b, &token));
+    *a.1.borrow_mut(&mut token) = 3;
+    assert!(a.eq(&b, &token));
+    assert_eq!(hash(&a, &token), hash(&b, &token));
+    assert_eq!(format!("{:?}", a.with(&token)), "Pair(1, 3)");
+}
+
+/// A type parameter gets each trait only where its fields have it.
+#[test]
+fn generics() {
+    make_guard!(token);
+    let token = token;
+    let tagged = |tag, value: Vec<u32>| Tagged { tag, value: GhostCell::new(value) };
+    assert_eq!(tagged(1, vec![9]).cmp(&tagged(1, vec![9, 0]), &token), Ordering::Less);
+    assert_eq!(tagged(2, vec![]).cmp(&tagged(1, vec![9]), &token), Ordering::Greater);
+    assert!(tagged(0, vec![1, 2]).eq(&tagged(0, vec![1, 2]), &token));
+    assert_eq!(hash(&tagged(0, vec![1]), &token), hash(&tagged(0, vec![1]), &token));
+
+    let nested = Tagged { tag: 0, value: GhostCell::new(Rank::Mid(GhostCell::new(2))) };
+    assert!(nested.eq(&Tagged { tag: 0, value: GhostCell::new(Rank::Mid(GhostCell::new(2))) }, &token));
+    assert_eq!(format!("{:?}", nested.with(&token)), "Tagged { tag: 0, value: Mid(2) }");
+}
@@ -0,0 +1,99 @@
+//! `GhostBTreeMap` against `std::collections::BTreeMap`, through enough
+//! inserts and removes to split and merge nodes at every level.
+use std::{collections::BTreeMap, ops::Bound, thread};
+
+use demo::{make_guard, GhostBTreeMap};
+
+/// A fixed-seed xorshift, so failures reproduce.
+fn keys(seed: u64, n: usize, modulus: u64) -> Vec<u64> {
+    let mut x = seed;
+    (0..n)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x % modulus
+        })
+        .collect()
+}
+
+#[test]
+fn matches_std_through_inserts_and_removes() {
+    make_guard!(token);
+    let mut token = token;
+    let map = GhostBTreeMap::new();
+    let mut expected = BTreeMap::new();
+    for (step, key) in keys(1, 4000, 1000).into_iter().enumerate() {
+        if step % 3 == 2 {
+            assert_eq!(map.remove(&key, &mut token), expected.remove(&key));
+        } else {
+            assert_eq!(map.insert(key, step, &mut token), expected.insert(key, step));
+        }
+        assert_eq!(map.len(&token), expected.len());
+    }
+    assert!(map.iter(&token).eq(expected.iter()));
+    for key in keys(2, 1000, 1000) {
+        assert_eq!(map.remove(&key, &mut token), expected.remove(&key));
+    }
+    assert!(map.iter(&token).eq(expected.iter()));
+    let remaining: Vec<u64> = expected.keys().copied().collect();
+    for key in remaining {
+        assert_eq!(map.remove(&key, &mut token), expected.remove(&key));
+    }
+    assert!(map.is_empty(&token) && map.cursor_front(&token).key_value().is_none());
+}
+
+/// Cursors walk both ways from any bound, and wrap past either end.
+#[test]
+fn cursors_agree_with_range() {
+    make_guard!(token);
+    let map: GhostBTreeMap<u64, ()> = (0..500).map(|key| (key * 2, ())).collect();
+    let expected: BTreeMap<u64, ()> = (0..500).map(|key| (key * 2, ())).collect();
+    for probe in [0, 1, 2, 77, 500, 997, 998, 999, 2000] {
+        for bound in [Bound::Included(&probe), Bound::Excluded(&probe), Bound::Unbounded] {
+            let mut cursor = map.lower_bound(bound, &token);
+            let mut forward = Vec::new();
+            while let Some(key) = cursor.key() {
+                forward.push(*key);
+                cursor.move_next();
+            }
+            let want: Vec<u64> = expected.range((bound, Bound::Unbounded)).map(|(key, _)| *key).collect();
+            assert_eq!(forward, want);
+            cursor.move_prev();
+            assert_eq!(cursor.key(), Some(&998));
+        }
+    }
+    let mut cursor = map.cursor_back(&token);
+    let mut backward = Vec::new();
+    while let Some(key) = cursor.key() {
+        backward.push(*key);
+        cursor.move_prev();
+    }
+    assert!(backward.iter().rev().eq(expected.keys()));
+    cursor.move_next();
+    assert_eq!(cursor.key(), Some(&0));
+}
+
+/// A value from one lookup stays usable across later lookups and cursor
+/// moves, and lookups run in parallel with only the shared token.
+#[test]
+fn shared_token_lookups_across_threads() {
+    make_guard!(token);
+    let mut token = token;
+    let map = GhostBTreeMap::new();
+    for key in 0..1000u32 {
+        map.insert(key, key * 3, &mut token);
+    }
+    *map.get_mut(&10, &mut token).unwrap() = 0;
+    let (map, token) = (&map, &token);
+    let held = map.get(&10, token).unwrap();
+    let sums: Vec<u32> = thread::scope(|s| {
+        let workers: Vec<_> = (0..4)
+            .map(|t| s.spawn(move || (t * 250..(t + 1) * 250).map(|key| *map.get(&key, token).unwrap()).sum()))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert_eq!(sums.iter().sum::<u32>(), (0..1000).map(|key| key * 3).sum::<u32>() - 30);
+    assert_eq!(*held, 0);
+    assert!(!map.contains_key(&1000, token));
+}
@@ -0,0 +1,54 @@
+//! `GhostHashMap` handles, held across mutation of other entries and read
+//! from several threads.
+use std::thread;
+
+use demo::{make_guard, GhostHashMap};
+
+/// Handles from `get` outlive mutation through the token of their own entry
+/// and others', including two entries at once.
+#[test]
+fn handles_survive_value_mutation() {
+    make_guard!(token);
+    let mut token = token;
+    let map: GhostHashMap<u32, Vec<u32>> = (0..100).map(|key| (key, vec![key])).collect();
+    let handles: Vec<_> = (0..100).map(|key| map.get(&key).unwrap()).collect();
+    for (key, handle) in handles.iter().enumerate() {
+        handle.borrow_mut(&mut token).push(key as u32 * 2);
+    }
+    let (a, b) = map.get_two_mut(&3, &4, &mut token).unwrap();
+    std::mem::swap(a, b);
+    assert!(map.get_two_mut(&3, &3, &mut token).is_none());
+    assert!(map.get_two_mut(&3, &100, &mut token).is_none());
+    assert_eq!(*handles[3].borrow(&token), [4, 8]);
+    assert_eq!(handles.iter().map(|handle| handle.borrow(&token).len()).sum::<usize>(), 200);
+}
+
+#[test]
+fn structural_changes_return_values() {
+    let mut map = GhostHashMap::<&str, i32>::default();
+    assert_eq!(map.insert("a", 1), None);
+    assert_eq!(map.insert("a", 2), Some(1));
+    map.extend([("b", 3), ("c", 4)]);
+    *map.get_mut("b").unwrap() += 10;
+    assert_eq!(map.remove("b"), Some(13));
+    assert_eq!(map.remove("b"), None);
+    assert!(map.contains_key("c") && map.len() == 2);
+    map.clear();
+    assert!(map.is_empty());
+}
+
+/// Lookups need only `&self`, and reads through the shared token can run on
+/// several threads at once.
+#[test]
+fn concurrent_reads() {
+    make_guard!(token);
+    let map: GhostHashMap<u32, u64> = (0..1000).map(|key| (key, u64::from(key))).collect();
+    let (map, token) = (&map, &token);
+    let total: u64 = thread::scope(|s| {
+        let workers: Vec<_> = (0..4)
+            .map(|t| s.spawn(move || (t * 250..(t + 1) * 250).map(|key| *map.get(&key).unwrap().borrow(token)).sum::<u64>()))
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).sum()
+    });
+    assert_eq!(total, (0..1000).sum::<u64>());
+}
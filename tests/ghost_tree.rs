@@ -0,0 +1,139 @@
+//! `GhostTree` relinking, checked from both ends of every parent link.
+use demo::{make_guard, GhostToken, GhostTree};
+
+fn values<'a, 'id: 'a>(nodes: impl Iterator<Item = &'a GhostTree<'id, u32>>, token: &'a GhostToken<'id>) -> Vec<u32> {
+    nodes.map(|node| *node.borrow(token)).collect()
+}
+
+#[test]
+fn walks_and_reparenting() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes: Vec<_> = (0..6).map(GhostTree::new).collect();
+    // 0 -> (1 -> (3, 4), 2 -> 5)
+    for (parent, child) in [(0, 1), (0, 2), (1, 3), (1, 4), (2, 5)] {
+        nodes[parent].push_child(nodes[child].clone(), &mut token);
+    }
+    assert_eq!(values(nodes[0].pre_order(&token), &token), [0, 1, 3, 4, 2, 5]);
+    assert_eq!(values(nodes[0].post_order(&token), &token), [3, 4, 1, 5, 2, 0]);
+
+    // Moving 1's subtree under 5 takes 3 and 4 with it.
+    nodes[5].insert_child(0, nodes[1].clone(), &mut token);
+    assert!(nodes[5].ptr_eq(&nodes[1].parent(&token).unwrap()));
+    assert_eq!(values(nodes[0].pre_order(&token), &token), [0, 2, 5, 1, 3, 4]);
+    assert!(nodes[0].ptr_eq(&nodes[4].root(&token)));
+    assert!(nodes[2].is_ancestor_of(&nodes[3], &token) && !nodes[3].is_ancestor_of(&nodes[2], &token));
+
+    assert!(nodes[2].detach(&mut token));
+    assert!(!nodes[2].detach(&mut token));
+    assert_eq!(values(nodes[0].pre_order(&token), &token), [0]);
+    assert!(nodes[3].root(&token).ptr_eq(&nodes[2]));
+}
+
+#[test]
+#[should_panic = "a node can't become its own descendant"]
+fn no_cycles() {
+    make_guard!(token);
+    let mut token = token;
+    let (a, b) = (GhostTree::new(0), GhostTree::new(1));
+    a.push_child(b.clone(), &mut token);
+    b.push_child(a, &mut token);
+}
+
+/// Whether `parent.insert_child(index, child)` returned rather than panicked.
+fn try_insert<'id>(parent: &GhostTree<'id, u32>, index: usize, child: GhostTree<'id, u32>, token: &mut GhostToken<'id>) -> bool {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| parent.insert_child(index, child, token))).is_ok()
+}
+
+/// An index past the end panics before `child` leaves its old parent.
+#[test]
+fn insert_child_out_of_range() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes: Vec<_> = (0..4).map(GhostTree::new).collect();
+    for (parent, child) in [(0, 1), (0, 2), (3, 0)] {
+        nodes[parent].push_child(nodes[child].clone(), &mut token);
+    }
+    assert!(!try_insert(&nodes[1], 1, nodes[2].clone(), &mut token));
+    assert!(nodes[2].parent(&token).unwrap().ptr_eq(&nodes[0]));
+    // The index counts the children after the move, so a child moving
+    // within its parent can't go at the old length.
+    assert!(!try_insert(&nodes[0], 2, nodes[1].clone(), &mut token));
+    assert_eq!(values(nodes[3].pre_order(&token), &token), [3, 0, 1, 2]);
+    assert!(try_insert(&nodes[0], 1, nodes[1].clone(), &mut token));
+    assert_eq!(values(nodes[0].pre_order(&token), &token), [0, 2, 1]);
+    assert!(try_insert(&nodes[1], 0, nodes[2].clone(), &mut token));
+    assert_eq!(values(nodes[3].pre_order(&token), &token), [3, 0, 1, 2]);
+}
+
+/// Parent links are weak: dropping the last handle to a root frees the
+/// whole tree, and its children see no parent.
+#[test]
+fn parents_are_weak() {
+    make_guard!(token);
+    let mut token = token;
+    let root = GhostTree::new(0);
+    let child = GhostTree::new(1);
+    root.push_child(child.clone(), &mut token);
+    let weak_root = root.downgrade();
+    assert!(weak_root.upgrade().is_some());
+    drop(root);
+    assert!(weak_root.upgrade().is_none());
+    assert!(child.parent(&token).is_none());
+}
+
+/// A room, which can hold keys to other rooms of the same tree.
+struct Room<'id> {
+    name: &'static str,
+    keys: Vec<GhostTree<'id, Room<'id>>>,
+}
+
+fn room<'id>(name: &'static str) -> GhostTree<'id, Room<'id>> {
+    GhostTree::new(Room { name, keys: Vec::new() })
+}
+
+/// `visit_mut` hands out nodes' values while the walk holds on to their
+/// ancestors, so it must survive values that own handles into the tree being
+/// dropped or replaced under it, down to the last handle to the root.
+#[test]
+fn visit_mut_drops_handles() {
+    make_guard!(token);
+    let mut token = token;
+    let (hall, cellar, vault, attic) = (room("hall"), room("cellar"), room("vault"), room("attic"));
+    hall.push_child(cellar.clone(), &mut token);
+    cellar.push_child(vault.clone(), &mut token);
+    hall.push_child(attic.clone(), &mut token);
+    // The vault holds the only other handle to the hall, and the attic one
+    // to the cellar.
+    vault.borrow_mut(&mut token).keys.push(hall.clone());
+    attic.borrow_mut(&mut token).keys.push(cellar.clone());
+    let weak_hall = hall.downgrade();
+    drop((hall, vault, attic));
+
+    let mut seen = Vec::new();
+    cellar.visit_mut(&mut token, |room, ancestors| {
+        seen.push((room.name, ancestors.iter().map(|room| room.name).collect::<Vec<_>>()));
+        // Dropping the hall's last handle frees it while the walk is below
+        // it, which leaves the cellar without a parent.
+        room.keys.clear();
+        room.keys.push(GhostTree::new(Room { name: "spare", keys: Vec::new() }));
+    });
+    assert_eq!(seen, [("cellar", vec![]), ("vault", vec!["cellar"])]);
+    assert!(weak_hall.upgrade().is_none());
+    assert!(cellar.parent(&token).is_none());
+
+    let vault = &cellar.children(&token)[0];
+    assert_eq!(vault.borrow(&token).keys[0].borrow(&token).name, "spare");
+
+    // A walk that replaces a value holding the walk's own start.
+    let keep = cellar.clone();
+    cellar.borrow_mut(&mut token).keys = vec![keep];
+    cellar.visit_mut(&mut token, |room, ancestors| {
+        if ancestors.is_empty() {
+            room.keys = Vec::new();
+        } else {
+            assert_eq!(ancestors.parent().unwrap().name, "cellar");
+        }
+    });
+    assert!(cellar.borrow(&token).keys.is_empty());
+}
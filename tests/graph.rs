@@ -0,0 +1,118 @@
+//! `graph::save` and `graph::load` on graphs with cycles, through JSON, and
+//! on saved graphs with edges to nodes they don't have.
+#![cfg(feature = "graph")]
+use demo::graph::{self, Arena, DanglingEdge, GraphNode, SavedGraph, SavedNode};
+use demo::{make_guard, GhostCell, GhostToken};
+
+type Link<'arena, 'id> = &'arena GhostCell<'id, Room<'arena, 'id>>;
+
+/// A room with doors to other rooms, some of them bricked up.
+struct Room<'arena, 'id> {
+    name: String,
+    doors: Vec<Option<Link<'arena, 'id>>>,
+}
+impl<'arena, 'id> GraphNode<'arena, 'id> for Room<'arena, 'id> {
+    type Data = String;
+    fn data(&self) -> String {
+        self.name.clone()
+    }
+    fn edges(&self) -> Vec<Option<Link<'arena, 'id>>> {
+        self.doors.clone()
+    }
+    fn from_data(name: String) -> Self {
+        Room { name, doors: Vec::new() }
+    }
+    fn set_edges(&mut self, doors: Vec<Option<Link<'arena, 'id>>>) {
+        self.doors = doors;
+    }
+}
+
+fn room<'arena, 'id>(arena: &'arena Arena<GhostCell<'id, Room<'arena, 'id>>>, name: &str) -> Link<'arena, 'id> {
+    arena.alloc(GhostCell::new(Room::from_data(name.into())))
+}
+
+fn door<'arena, 'id>(room: Link<'arena, 'id>, door: usize, token: &GhostToken<'id>) -> Option<Link<'arena, 'id>> {
+    room.borrow(token).doors[door]
+}
+
+fn name<'arena, 'id>(room: Link<'arena, 'id>, token: &GhostToken<'id>) -> String {
+    room.borrow(token).name.clone()
+}
+
+/// A hall leading to a study that leads back, and a closet with a door to
+/// itself: each cycle is saved as an edge back to an earlier node, and comes
+/// back as the same cell.
+#[test]
+fn cycles() {
+    let json = {
+        make_guard!(token);
+        let mut token = token;
+        let arena = Arena::new();
+        let (hall, study, closet) = (room(&arena, "hall"), room(&arena, "study"), room(&arena, "closet"));
+        hall.borrow_mut(&mut token).doors = vec![Some(study), None, Some(closet)];
+        study.borrow_mut(&mut token).doors = vec![Some(hall)];
+        closet.borrow_mut(&mut token).doors = vec![Some(closet)];
+        let saved = graph::save(&[hall], &token);
+        assert_eq!(saved.roots, [0]);
+        let edges: Vec<_> = saved.nodes.iter().map(|node| node.edges.clone()).collect();
+        assert_eq!(edges, [vec![Some(1), None, Some(2)], vec![Some(0)], vec![Some(2)]]);
+        saved.to_json().unwrap()
+    };
+
+    make_guard!(token);
+    let mut token = token;
+    let arena = Arena::new();
+    let roots = graph::load(SavedGraph::<String>::from_json(&json).unwrap(), &arena, &mut token).unwrap();
+    let hall = roots[0];
+    let study = door(hall, 0, &token).unwrap();
+    let closet = door(hall, 2, &token).unwrap();
+    assert_eq!([name(hall, &token), name(study, &token), name(closet, &token)], ["hall", "study", "closet"]);
+    assert!(door(hall, 1, &token).is_none());
+    assert!(core::ptr::eq(door(study, 0, &token).unwrap(), hall));
+    assert!(core::ptr::eq(door(closet, 0, &token).unwrap(), closet));
+
+    // The loaded graph saves to the same JSON it came from.
+    assert_eq!(graph::save(&roots, &token).to_json().unwrap(), json);
+    study.borrow_mut(&mut token).name = "library".into();
+    assert_eq!(name(door(door(study, 0, &token).unwrap(), 0, &token).unwrap(), &token), "library");
+}
+
+/// The JSON form is the nodes with their data and edges, then the roots.
+#[test]
+fn json() {
+    make_guard!(token);
+    let mut token = token;
+    let arena = Arena::new();
+    let (a, b) = (room(&arena, "a"), room(&arena, "b"));
+    a.borrow_mut(&mut token).doors = vec![Some(b), None];
+    let json = graph::save(&[b, a], &token).to_json().unwrap();
+    assert_eq!(json, r#"{"nodes":[{"data":"b","edges":[]},{"data":"a","edges":[0,null]}],"roots":[0,1]}"#);
+
+    let saved = SavedGraph::<String>::from_json(&json).unwrap();
+    assert_eq!(saved.nodes[1].data, "a");
+    assert!(SavedGraph::<String>::from_json(r#"{"nodes":[{"data":1,"edges":[]}],"roots":[]}"#).is_err());
+}
+
+/// An edge or a root to a node past the end is reported as the
+/// `DanglingEdge` it is.
+#[test]
+fn dangling_edges() {
+    let node = |data: &str, edges: Vec<Option<usize>>| SavedNode { data: data.to_string(), edges };
+    make_guard!(token);
+    let mut token = token;
+    let arena: Arena<GhostCell<'_, Room<'_, '_>>> = Arena::new();
+
+    let edge = SavedGraph { nodes: vec![node("a", vec![Some(0)]), node("b", vec![None, Some(2)])], roots: vec![0] };
+    let err = graph::load(edge, &arena, &mut token).map(|_| ()).unwrap_err();
+    assert_eq!(err, DanglingEdge { node: Some(1), target: 2 });
+    assert_eq!(err.to_string(), "node 1 has an edge to missing node 2");
+
+    let root = SavedGraph { nodes: vec![node("a", vec![])], roots: vec![0, 1] };
+    let err = graph::load(root, &arena, &mut token).map(|_| ()).unwrap_err();
+    assert_eq!(err, DanglingEdge { node: None, target: 1 });
+    assert_eq!(err.to_string(), "root refers to missing node 1");
+
+    let json = r#"{"nodes":[{"data":"a","edges":[5]}],"roots":[0]}"#;
+    let err = graph::load(SavedGraph::<String>::from_json(json).unwrap(), &arena, &mut token).map(|_| ()).unwrap_err();
+    assert_eq!(err, DanglingEdge { node: Some(0), target: 5 });
+}
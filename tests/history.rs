@@ -0,0 +1,60 @@
+//! `HistoryToken` timelines across several cells, including branches that
+//! are undone and then committed over.
+use demo::{make_guard, HistoryCell, HistoryToken};
+
+#[test]
+fn uncommitted_changes_are_undone_first() {
+    make_guard!(token);
+    let mut token = HistoryToken::new(token);
+    let cell = HistoryCell::new(1);
+    *cell.borrow_mut(&mut token) = 2;
+    let two = token.commit();
+    *cell.borrow_mut(&mut token) = 3;
+    assert!(token.has_changes() && *cell.borrow(&token) == 3);
+    assert!(token.undo());
+    assert!(!token.has_changes());
+    assert_eq!((*cell.borrow(&token), token.version()), (2, two));
+    assert!(token.undo() && *cell.borrow(&token) == 1);
+    assert!(!token.undo());
+    assert_eq!(token.commit(), token.version());
+}
+
+/// Committing after an undo drops the undone versions, from the timeline and,
+/// as they are next borrowed, from the cells.
+#[test]
+fn committing_over_undone_versions() {
+    make_guard!(token);
+    let mut token = HistoryToken::new(token);
+    let (a, b) = (HistoryCell::new('a'), HistoryCell::new('b'));
+    *a.borrow_mut(&mut token) = 'A';
+    token.commit();
+    *b.borrow_mut(&mut token) = 'B';
+    let abandoned = token.commit();
+    assert!(token.undo());
+    assert_eq!((*a.borrow(&token), *b.borrow(&token)), ('A', 'b'));
+    *a.borrow_mut(&mut token) = 'x';
+    let branch = token.commit();
+    assert!(!token.redo());
+    assert!(!token.checkout(abandoned));
+    assert_eq!((*a.borrow(&token), *b.borrow(&token)), ('x', 'b'));
+    *b.borrow_mut(&mut token) = 'y';
+    assert_eq!(b.snapshots(&token), 2);
+    token.undo();
+    assert!(token.checkout(branch));
+    assert_eq!((*a.borrow(&token), *b.borrow(&token)), ('x', 'b'));
+}
+
+/// The group's plain `GhostCell`s are still reachable through the token, and
+/// are not part of the history.
+#[test]
+fn plain_cells_alongside() {
+    make_guard!(token);
+    let mut token = HistoryToken::new(token);
+    let plain = demo::GhostCell::new(0);
+    let tracked = HistoryCell::new(vec![1]);
+    tracked.borrow_mut(&mut token).push(2);
+    *plain.borrow_mut(token.token_mut()) += 1;
+    token.commit();
+    token.undo();
+    assert_eq!((*plain.borrow(token.token()), tracked.borrow(&token).len()), (1, 1));
+}
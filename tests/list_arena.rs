@@ -0,0 +1,137 @@
+//! The intrusive lists, relinked in every order the cursors allow.
+use demo::{
+    list_arena::{Linked, Links, List, SLink, SLinked, SList},
+    make_guard,
+};
+
+struct Node<'arena, 'id> {
+    id: u32,
+    links: Links<'arena, 'id, Self>,
+    link: SLink<'arena, 'id, Self>,
+}
+impl<'arena, 'id> Linked<'arena, 'id> for Node<'arena, 'id> {
+    fn links(&self) -> &Links<'arena, 'id, Self> {
+        &self.links
+    }
+}
+impl<'arena, 'id> SLinked<'arena, 'id> for Node<'arena, 'id> {
+    fn link(&self) -> &SLink<'arena, 'id, Self> {
+        &self.link
+    }
+}
+
+fn nodes<'arena, 'id>(n: u32) -> Vec<Node<'arena, 'id>> {
+    (0..n)
+        .map(|id| Node {
+            id,
+            links: Links::new(),
+            link: SLink::new(),
+        })
+        .collect()
+}
+
+/// Unlinking the head, the tail and a middle node each patch up the right
+/// neighbours, seen from both directions.
+#[test]
+fn unlink_anywhere() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes = nodes(5);
+    let list = List::new();
+    for node in &nodes[1..] {
+        list.push_back(node, &mut token);
+    }
+    list.push_front(&nodes[0], &mut token);
+    assert!(list.unlink(&nodes[0], &mut token));
+    assert!(list.unlink(&nodes[4], &mut token));
+    assert!(list.unlink(&nodes[2], &mut token));
+    assert!(!list.unlink(&nodes[2], &mut token));
+    assert_eq!(list.iter(&token).map(|node| node.id).collect::<Vec<_>>(), [1, 3]);
+    assert_eq!(list.iter(&token).rev().map(|node| node.id).collect::<Vec<_>>(), [3, 1]);
+    assert_eq!(list.pop_back(&mut token).map(|node| node.id), Some(3));
+    assert_eq!(list.pop_front(&mut token).map(|node| node.id), Some(1));
+    assert!(list.is_empty(&token) && list.pop_front(&mut token).is_none());
+}
+
+/// The cursor inserts on either side of its node, and wraps through the
+/// ghost position past either end.
+#[test]
+fn cursor_relinks() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes = nodes(4);
+    let list = List::new();
+    list.push_back(&nodes[1], &mut token);
+    let mut cursor = list.cursor_front_mut(&mut token);
+    cursor.insert_before(&nodes[0]);
+    cursor.insert_after(&nodes[3]);
+    cursor.insert_after(&nodes[2]);
+    cursor.move_prev();
+    assert_eq!(cursor.current().map(|node| node.id), Some(0));
+    cursor.move_prev();
+    assert!(cursor.current().is_none());
+    cursor.move_prev();
+    assert_eq!(cursor.remove_current().map(|node| node.id), Some(3));
+    assert!(cursor.current().is_none());
+    cursor.move_next();
+    assert_eq!(cursor.current().map(|node| node.id), Some(0));
+    assert_eq!(list.iter(&token).map(|node| node.id).collect::<Vec<_>>(), [0, 1, 2]);
+    assert!(list.contains(&nodes[1], &token) && !list.contains(&nodes[3], &token));
+}
+
+#[test]
+#[should_panic = "node is already in a list"]
+fn push_twice() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes = nodes(1);
+    let list = List::new();
+    list.push_back(&nodes[0], &mut token);
+    list.push_front(&nodes[0], &mut token);
+}
+
+/// The same nodes can be in a `List` and an `SList` at once, since they
+/// have separate links for each.
+#[test]
+fn singly_linked() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes = nodes(4);
+    let list = List::new();
+    let stack = SList::new();
+    for node in &nodes {
+        list.push_back(node, &mut token);
+    }
+    stack.push_front(&nodes[3], &mut token);
+    stack.push_front(&nodes[0], &mut token);
+    let mut cursor = stack.cursor_mut(&mut token);
+    assert!(cursor.move_next());
+    cursor.insert_after(&nodes[2]);
+    cursor.insert_after(&nodes[1]);
+    assert!(cursor.move_next() && cursor.move_next() && cursor.move_next());
+    assert!(!cursor.move_next());
+    assert_eq!(cursor.current().map(|node| node.id), Some(3));
+    assert!(cursor.remove_next().is_none());
+    let mut cursor = stack.cursor_mut(&mut token);
+    assert_eq!(cursor.remove_next().map(|node| node.id), Some(0));
+    assert_eq!(cursor.peek_next().map(|node| node.id), Some(1));
+    let ids: Vec<u32> = stack.iter(&token).map(|node| node.id).collect();
+    assert_eq!(ids, [1, 2, 3]);
+    assert_eq!(list.iter(&token).count(), 4);
+}
+
+/// At the end of its list the cursor's own node has no next node, which
+/// mustn't pass for not being in a list.
+#[test]
+#[should_panic = "node is already in a list"]
+fn insert_tail_after_itself() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes = nodes(2);
+    let stack = SList::new();
+    stack.push_front(&nodes[1], &mut token);
+    stack.push_front(&nodes[0], &mut token);
+    let mut cursor = stack.cursor_mut(&mut token);
+    while cursor.move_next() {}
+    cursor.insert_after(&nodes[1]);
+}
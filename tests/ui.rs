@@ -0,0 +1,7 @@
+//! Each case in `ui/` must fail to compile, with exactly the error recorded
+//! next to it, so that a case can't pass by failing for some other reason.
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
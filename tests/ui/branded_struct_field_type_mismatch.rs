@@ -0,0 +1,18 @@
+// The fields agree in size, alignment and offset, but not in type, so a
+// negative `energy` would be read through the open type as a huge one.
+use demo::{BrandedStruct, GhostCell};
+
+#[derive(BrandedStruct)]
+#[branded(open = OpenEntity)]
+#[repr(C)]
+struct Entity<'content> {
+    hp: GhostCell<'content, u32>,
+    energy: GhostCell<'content, i32>,
+}
+#[repr(C)]
+struct OpenEntity<'hp, 'energy> {
+    hp: GhostCell<'hp, u32>,
+    energy: GhostCell<'energy, u32>,
+}
+
+fn main() {}
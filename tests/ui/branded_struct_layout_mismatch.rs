@@ -0,0 +1,18 @@
+// An open type whose fields are declared in another order is laid out
+// differently, so casting to it would be unsound.
+use demo::{BrandedStruct, GhostCell};
+
+#[derive(BrandedStruct)]
+#[branded(open = OpenEntity)]
+#[repr(C)]
+struct Entity<'content> {
+    hp: GhostCell<'content, u32>,
+    energy: GhostCell<'content, i32>,
+}
+#[repr(C)]
+struct OpenEntity<'energy, 'hp> {
+    energy: GhostCell<'energy, i32>,
+    hp: GhostCell<'hp, u32>,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+// An index is only in bounds for the vector that created it, so it can't be
+// used with any other.
+use demo::{make_guard, BrandedVec};
+
+fn main() {
+    make_guard!(a);
+    make_guard!(b);
+    let mut a = BrandedVec::new(a);
+    let b: BrandedVec<'_, u32> = BrandedVec::new(b);
+    let i = a.push(1u32);
+    println!("{}", b[i]);
+}
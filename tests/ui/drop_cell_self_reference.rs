@@ -0,0 +1,16 @@
+// The same cycle without the token inside `Foo`: any nontrivial `Drop` stops
+// a cell from holding a reference to the value that contains it.
+use demo::{make_guard, GhostCell};
+
+struct Foo<'a, 'id>(GhostCell<'id, Option<&'a Foo<'a, 'id>>>);
+
+impl<'a, 'id> Drop for Foo<'a, 'id> {
+    fn drop(&mut self) {}
+}
+
+fn main() {
+    make_guard!(token);
+    let mut token = token;
+    let foo = Foo(GhostCell::new(None));
+    *foo.0.borrow_mut(&mut token) = Some(&foo);
+}
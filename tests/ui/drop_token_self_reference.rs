@@ -0,0 +1,24 @@
+// A `Drop` impl holding the token could read a self-reference stored in one of
+// its cells, but dropck requires `foo` to strictly outlive the reference.
+use std::cell::Cell;
+
+use demo::{make_guard, GhostCell, GhostToken};
+
+struct Foo<'a, 'id>(&'a GhostToken<'id>, GhostCell<'id, Cell<Option<&'a Foo<'a, 'id>>>>)
+where
+    'id: 'a;
+
+impl<'a, 'id> Drop for Foo<'a, 'id> {
+    fn drop(&mut self) {
+        match self.1.borrow(self.0).get() {
+            Some(_) => println!("Oops, have aliasing."),
+            None => println!("Okay"),
+        }
+    }
+}
+
+fn main() {
+    make_guard!(token);
+    let foo = Foo(&token, GhostCell::new(Cell::new(None)));
+    foo.1.borrow(&token).set(Some(&foo));
+}
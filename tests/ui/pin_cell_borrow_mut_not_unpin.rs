@@ -0,0 +1,12 @@
+// A `!Unpin` value can't be borrowed as a plain `&mut`, or it could be moved
+// out of a cell that has been pinned.
+use std::marker::PhantomPinned;
+
+use demo::{make_guard, GhostPinCell};
+
+fn main() {
+    make_guard!(token);
+    let mut token = token;
+    let cell = Box::pin(GhostPinCell::new(PhantomPinned));
+    let _moved = std::mem::replace(cell.borrow_mut(&mut token), PhantomPinned);
+}
@@ -0,0 +1,23 @@
+// A projected cell borrows the token of the enum it was projected from, so
+// the enum can't be replaced, dropping the cell, while the projection lives.
+use demo::{make_guard, BrandedEnum, GhostCell};
+
+#[derive(BrandedEnum)]
+#[branded(variants = HandVariants)]
+enum Hand<'content> {
+    Shield { durability: GhostCell<'content, u32> },
+    Sword { sharpness: u32 },
+}
+
+fn main() {
+    make_guard!(hand_token);
+    let mut hand_token = hand_token;
+    make_guard!(content);
+    let mut content = content;
+    let hand = GhostCell::new(Hand::Shield { durability: GhostCell::new(5) });
+    Hand::split_variants(&mut content, |variants| {
+        let armor = hand.project_variant(&hand_token, &variants.shield).unwrap();
+        *hand.borrow_mut(&mut hand_token) = Hand::Sword { sharpness: 3 };
+        *armor.borrow_mut(&mut variants.shield) += 2;
+    });
+}
@@ -0,0 +1,12 @@
+// The `GhostCell` view of an `RtCell` can't outlive the `with_static` call
+// that branded it, or it could be used while the token is free again.
+use demo::{RtCell, RuntimeToken};
+
+fn main() {
+    let mut token = RuntimeToken::new();
+    let cell = RtCell::new(&token, 1);
+    let mut escaped = None;
+    token.with_static(|_, brand| escaped = Some(cell.as_ghost(brand)));
+    *cell.borrow_mut(&mut token) += 1;
+    drop(escaped);
+}
@@ -0,0 +1,20 @@
+// The tokens `split_variants` hands out are branded by a higher-ranked
+// closure, so none of them can be used as a `GhostToken<'static>`, which
+// would open every `RtCell`.
+use demo::{make_guard, BrandedEnum, GhostCell};
+
+#[derive(BrandedEnum)]
+#[branded(variants = HandVariants)]
+enum Hand<'content> {
+    Shield { durability: GhostCell<'content, u32> },
+    Sword { sharpness: u32 },
+}
+
+fn main() {
+    make_guard!(content);
+    let mut content = content;
+    let placeholder: GhostCell<'static, u32> = GhostCell::new(1);
+    Hand::split_variants(&mut content, |variants| {
+        *placeholder.borrow_mut(&mut variants.shield) += 1;
+    });
+}
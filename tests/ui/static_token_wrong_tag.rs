@@ -0,0 +1,15 @@
+// A `TypeCell` only opens with the token of its own tag.
+use demo::{static_tag, StaticToken, TypeCell};
+
+static_tag! {
+    struct Rings;
+}
+static_tag! {
+    struct Hands;
+}
+
+fn main() {
+    let mut hands = StaticToken::<Hands>::new();
+    let ring = TypeCell::<Rings, u32>::new(1);
+    *ring.borrow_mut(&mut hands) += 1;
+}
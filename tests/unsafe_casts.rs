@@ -0,0 +1,225 @@
+//! Runs each of the crate's unsafe casts, in the patterns most likely to break
+//! them, so that the aliasing models can check them:
+//!
+//! ```text
+//! cargo +nightly miri test --test unsafe_casts
+//! MIRIFLAGS=-Zmiri-tree-borrows cargo +nightly miri test --test unsafe_casts
+//! ```
+//!
+//! Every test should pass under both Stacked and Tree Borrows. The comment on
+//! each one says what it relies on, so a failure points at the cast to blame.
+use demo::{make_guard, BrandedEnum, BrandedVec, GhostCell, GhostPinCell, GhostTree, RtCell, RuntimeToken, StaticToken, TypeCell};
+
+/// `from_mut` reborrows the `&mut T` as `&mut GhostCell<T>`, so writing
+/// through the cell must be visible through `value` once the cell is gone,
+/// and using `value` again must not invalidate anything still in use.
+#[test]
+fn from_mut() {
+    make_guard!(token);
+    let mut token = token;
+    let mut value = 1u32;
+    let cell = GhostCell::from_mut(&mut value);
+    *cell.borrow_mut(&mut token) += 1;
+    *cell.get_mut() += 1;
+    assert_eq!(*cell.borrow(&token), 3);
+    value += 1;
+    assert_eq!(value, 4);
+}
+
+/// `as_slice_of_cells` turns one cell into a cell per element. References to
+/// different elements are taken from the same parent pointer, so writing to
+/// one must not invalidate a shared reference to its neighbour.
+#[test]
+fn as_slice_of_cells() {
+    make_guard!(token);
+    let mut token = token;
+    let mut values = [1u32, 2, 3];
+    let cells = GhostCell::from_mut(&mut values[..]).as_slice_of_cells();
+    let (first, last) = (&cells[0], &cells[2]);
+    *first.borrow_mut(&mut token) += 10;
+    let last_ref = last.borrow(&token);
+    assert_eq!(*last_ref, 3);
+    *cells[1].borrow_mut(&mut token) += *first.borrow(&token);
+    *last.borrow_mut(&mut token) += 1;
+    assert_eq!(values, [11, 13, 4]);
+}
+
+/// `borrow` and `borrow_mut` go through `UnsafeCell::get`, so holding `&`s to
+/// the cells themselves across writes through the token is allowed, as long
+/// as no `&T` is held across a `&mut T` to the same cell.
+#[test]
+fn borrow_across_writes() {
+    make_guard!(token);
+    let mut token = token;
+    let cells = [GhostCell::new(1u32), GhostCell::new(2)];
+    let (a, b) = (&cells[0], &cells[1]);
+    let b_ref = b.borrow(&token);
+    assert_eq!(*b_ref, 2);
+    for _ in 0..3 {
+        *a.borrow_mut(&mut token) *= 2;
+        *b.borrow_mut(&mut token) += *a.borrow(&token);
+    }
+    assert_eq!([*a.borrow(&token), *b.borrow(&token)], [8, 16]);
+    assert_eq!(cells.map(GhostCell::into_inner), [8, 16]);
+}
+
+/// `Cells::get` skips the bounds check, relying on the brand. Cells fetched
+/// before other cells are written must stay usable.
+#[test]
+fn branded_vec_cells() {
+    make_guard!(token);
+    let mut vec = BrandedVec::new(token);
+    let indices: Vec<_> = (1..=4u32).map(|i| vec.push(i)).collect();
+    let (cells, token) = vec.split();
+    let first = cells.get(indices[0]);
+    for &i in &indices[1..] {
+        *cells.get(i).borrow_mut(token) += *first.borrow(token);
+    }
+    *first.borrow_mut(token) = 0;
+    assert_eq!(vec.into_vec(), [0, 3, 4, 5]);
+}
+
+/// `borrow_mut_twice` hands out two `&mut`s from one token, which is only
+/// sound for distinct cells, and `swap` is built on it.
+#[test]
+fn borrow_mut_twice_and_swap() {
+    make_guard!(token);
+    let mut token = token;
+    let cells = [GhostCell::new(1u32), GhostCell::new(2)];
+    let (a, b) = GhostCell::borrow_mut_twice(&cells[0], &cells[1], &mut token).unwrap();
+    std::mem::swap(a, b);
+    *a += 10;
+    assert!(GhostCell::borrow_mut_twice(&cells[0], &cells[0], &mut token).is_none());
+    cells[0].swap(&cells[1], &mut token);
+    cells[1].swap(&cells[1], &mut token);
+    assert_eq!(cells.map(GhostCell::into_inner), [1, 12]);
+}
+
+/// `RtCell::as_ghost` reinterprets the cell's `GhostCell<'static, T>` under
+/// the brand of a `with_static` scope. Writes through it must be seen by
+/// runtime borrows afterwards, and runtime borrows before must not be
+/// invalidated by the cast itself.
+#[test]
+fn runtime_token_as_ghost() {
+    let mut token = RuntimeToken::new();
+    let cells = [RtCell::new(&token, 1u32), RtCell::new(&token, 2)];
+    *cells[0].borrow_mut(&mut token) += 1;
+    let sum = token.with_static(|token, brand| {
+        let (a, b) = (cells[0].as_ghost(brand), cells[1].as_ghost(brand));
+        a.swap(b, token);
+        *a.borrow_mut(token) *= 10;
+        *a.borrow(token) + *b.borrow(token)
+    });
+    assert_eq!(sum, 22);
+    assert_eq!([*cells[0].borrow(&token), *cells[1].borrow(&token)], [20, 2]);
+    assert!(cells[0].try_borrow(&RuntimeToken::new()).is_err());
+}
+
+demo::static_tag! {
+    struct Cases;
+}
+
+/// `TypeCell` repeats `GhostCell`'s casts and borrows under a type brand, and
+/// its token's flag must be released on drop so the next one can be made.
+#[test]
+fn static_token_type_cells() {
+    let mut token = StaticToken::<Cases>::new();
+    let mut values = [1u32, 2, 3];
+    let cells = TypeCell::<Cases, [u32]>::from_mut(&mut values[..]).as_slice_of_cells();
+    let first = cells[0].borrow(&token);
+    assert_eq!(*first, 1);
+    *cells[2].borrow_mut(&mut token) += 10;
+    cells[0].swap(&cells[1], &mut token);
+    assert!(TypeCell::borrow_mut_twice(&cells[1], &cells[1], &mut token).is_none());
+    assert!(StaticToken::<Cases>::try_new().is_none());
+    drop(token);
+    assert_eq!(values, [2, 1, 13]);
+    let token = StaticToken::<Cases>::try_new().unwrap();
+    let cell = TypeCell::<Cases, _>::new(values);
+    assert_eq!(cell.borrow(&token)[2], 13);
+}
+
+struct Node {
+    links: [u32; 2],
+    home: *const Node,
+    _pin: std::marker::PhantomPinned,
+}
+
+/// `GhostPinCell::borrow_pin_mut` hands out `Pin<&mut T>` from a shared
+/// reference, and `project` casts a field pointer to a cell. A value that
+/// points at itself must still find itself through either.
+#[test]
+fn pin_cell_self_reference_and_project() {
+    make_guard!(token);
+    let mut token = token;
+    let cell = std::pin::pin!(GhostPinCell::new(Node {
+        links: [1, 2],
+        home: std::ptr::null(),
+        _pin: std::marker::PhantomPinned,
+    }));
+    let cell = cell.into_ref();
+    let node = cell.borrow_pin_mut(&mut token);
+    let home: *const Node = &*node;
+    unsafe { node.get_unchecked_mut().home = home };
+    let links = unsafe { cell.project(|node| &raw mut (*node).links) };
+    links.get_ref().borrow_mut(&mut token)[1] += 10;
+    let node = cell.borrow_pin(&token);
+    assert!(std::ptr::eq(node.home, &*node));
+    assert_eq!(node.links, [1, 12]);
+}
+
+/// `GhostTree::visit_mut` splits the token between the node being visited
+/// and its ancestors, and rebrands each node's cell into one group and then
+/// the other: it holds `&mut` to one node's value while reading its
+/// ancestors' values and walking their children.
+#[test]
+fn ghost_tree_visit_mut() {
+    make_guard!(token);
+    let mut token = token;
+    let nodes: Vec<_> = (1..=5).map(GhostTree::new).collect();
+    for (parent, child) in [(0, 1), (1, 2), (0, 3), (3, 4)] {
+        nodes[parent].push_child(nodes[child].clone(), &mut token);
+    }
+    let mut seen = Vec::new();
+    nodes[0].visit_mut(&mut token, |value, ancestors| {
+        *value += ancestors.iter().sum::<u32>();
+        seen.push((*value, ancestors.parent().copied()));
+    });
+    assert_eq!(seen, [(1, None), (3, Some(1)), (7, Some(3)), (5, Some(1)), (11, Some(5))]);
+    assert_eq!(nodes.iter().map(|node| *node.borrow(&token)).collect::<Vec<_>>(), [1, 3, 7, 5, 11]);
+}
+
+/// `split_variants` reads the content token as a struct of variant tokens,
+/// and `project_variant` rebrands a variant's cell. Cells of two variants are
+/// written through their own tokens while a third is read, then all of them
+/// are read back under the content brand.
+#[test]
+fn branded_enum_project_variant() {
+    #[derive(BrandedEnum)]
+    #[branded(variants = SlotVariants)]
+    enum Slot<'content> {
+        Ring(GhostCell<'content, u32>),
+        Amulet { charge: GhostCell<'content, i64> },
+    }
+    make_guard!(slots_token);
+    make_guard!(content);
+    let mut content = content;
+    let slots = [Slot::Ring(GhostCell::new(1)), Slot::Amulet { charge: GhostCell::new(-1) }, Slot::Ring(GhostCell::new(3))].map(GhostCell::new);
+    Slot::split_variants(&mut content, |variants| {
+        let last = slots[2].project_variant(&slots_token, &variants.ring).unwrap();
+        let last = *last.borrow(&variants.ring);
+        let first = slots[0].project_variant(&slots_token, &variants.ring).unwrap();
+        let charge = slots[1].project_variant(&slots_token, &variants.amulet).unwrap();
+        let charge = charge.borrow_mut(&mut variants.amulet);
+        *first.borrow_mut(&mut variants.ring) += last;
+        *charge *= 10;
+    });
+    match slots[0].borrow(&slots_token) {
+        Slot::Ring(power) => assert_eq!(*power.borrow(&content), 4),
+        Slot::Amulet { .. } => unreachable!(),
+    }
+    match slots[1].borrow(&slots_token) {
+        Slot::Amulet { charge } => assert_eq!(*charge.borrow(&content), -10),
+        Slot::Ring(_) => unreachable!(),
+    }
+}